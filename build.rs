@@ -0,0 +1,4 @@
+fn main() {
+    // Embedded migrations are only picked up again when the directory changes
+    println!("cargo:rerun-if-changed=migrations");
+}
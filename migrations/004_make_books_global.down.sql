@@ -0,0 +1,20 @@
+-- Books belong to a user again; those without one go to the first account
+CREATE TABLE IF NOT EXISTS books_new (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    author TEXT,
+    isbn TEXT,
+    publication_year INTEGER,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+INSERT INTO books_new (id, user_id, title, author, isbn, publication_year, created_at, updated_at)
+SELECT id, (SELECT id FROM users ORDER BY created_at LIMIT 1), title, author, isbn, publication_year, created_at, updated_at FROM books;
+
+DROP TABLE books;
+ALTER TABLE books_new RENAME TO books;
+
+CREATE INDEX IF NOT EXISTS idx_books_user_id ON books(user_id);
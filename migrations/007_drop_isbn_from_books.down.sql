@@ -0,0 +1,21 @@
+-- Bring back the isbn column and the filepath constraint and index lost with the rebuild
+CREATE TABLE books_new (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    author TEXT,
+    isbn TEXT,
+    publication_year INTEGER,
+    filepath TEXT UNIQUE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    notes TEXT
+);
+
+INSERT INTO books_new (id, title, author, publication_year, filepath, notes, created_at, updated_at)
+SELECT id, title, author, publication_year, filepath, notes, created_at, updated_at FROM books;
+
+DROP TABLE books;
+
+ALTER TABLE books_new RENAME TO books;
+
+CREATE INDEX IF NOT EXISTS idx_books_filepath ON books(filepath);
@@ -0,0 +1,13 @@
+-- Full-text index over book titles, authors and notes
+-- Kept in sync by the Database book methods rather than triggers
+
+CREATE VIRTUAL TABLE IF NOT EXISTS books_fts USING fts5(
+    book_id UNINDEXED,
+    title,
+    author,
+    notes,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO books_fts (book_id, title, author, notes)
+SELECT id, title, COALESCE(author, ''), COALESCE(notes, '') FROM books;
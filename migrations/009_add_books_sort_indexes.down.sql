@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_books_title;
+DROP INDEX IF EXISTS idx_books_author;
+DROP INDEX IF EXISTS idx_books_publication_year;
+DROP INDEX IF EXISTS idx_books_created_at;
+DROP INDEX IF EXISTS idx_books_updated_at;
@@ -0,0 +1,6 @@
+-- Indexes backing the sortable, keyset-paginated book list
+CREATE INDEX IF NOT EXISTS idx_books_title ON books(title COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_author ON books(COALESCE(author, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_publication_year ON books(COALESCE(publication_year, 0), id);
+CREATE INDEX IF NOT EXISTS idx_books_created_at ON books(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_books_updated_at ON books(updated_at, id);
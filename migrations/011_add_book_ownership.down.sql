@@ -0,0 +1,27 @@
+DROP TABLE IF EXISTS book_shares;
+
+-- user_id is a foreign key, which SQLite cannot drop in place, so rebuild the table
+CREATE TABLE books_new (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    author TEXT,
+    publication_year INTEGER,
+    filepath TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+INSERT INTO books_new (id, title, author, publication_year, filepath, notes, created_at, updated_at)
+SELECT id, title, author, publication_year, filepath, notes, created_at, updated_at FROM books;
+
+DROP TABLE books;
+
+ALTER TABLE books_new RENAME TO books;
+
+-- Recreate the sort indexes dropped along with the old table
+CREATE INDEX IF NOT EXISTS idx_books_title ON books(title COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_author ON books(COALESCE(author, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_publication_year ON books(COALESCE(publication_year, 0), id);
+CREATE INDEX IF NOT EXISTS idx_books_created_at ON books(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_books_updated_at ON books(updated_at, id);
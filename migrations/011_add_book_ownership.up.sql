@@ -0,0 +1,23 @@
+-- Books belong to a user again and can be private, shared or public
+-- Books without an owner (eg. added by alayascan) stay editable by every user
+
+ALTER TABLE books ADD COLUMN user_id TEXT REFERENCES users(id) ON DELETE SET NULL;
+
+ALTER TABLE books ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+
+-- Existing books were added by hand through the web UI, give them to the first account
+UPDATE books SET user_id = (SELECT id FROM users ORDER BY created_at LIMIT 1)
+WHERE user_id IS NULL AND filepath IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_books_user_id ON books(user_id);
+
+CREATE TABLE IF NOT EXISTS book_shares (
+    book_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (book_id, user_id),
+    FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_book_shares_user_id ON book_shares(user_id);
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS idx_sessions_expires_at;
+
+ALTER TABLE sessions DROP COLUMN user_agent;
+
+ALTER TABLE sessions DROP COLUMN expires_at;
+
+ALTER TABLE sessions DROP COLUMN last_seen_at;
@@ -0,0 +1,12 @@
+-- Sessions expire on the server, slide forward while in use and remember the client
+ALTER TABLE sessions ADD COLUMN last_seen_at TEXT;
+
+ALTER TABLE sessions ADD COLUMN expires_at TEXT;
+
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+
+UPDATE sessions SET
+    last_seen_at = created_at,
+    expires_at = strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now', '+7 days');
+
+CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions(expires_at);
@@ -0,0 +1,17 @@
+-- One row per read-through of a book, so re-reads keep their own dates
+CREATE TABLE IF NOT EXISTS readings (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    book_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'want_to_read',
+    started_on TEXT,
+    finished_on TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_readings_user_book ON readings(user_id, book_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_readings_user_finished ON readings(user_id, status, finished_on);
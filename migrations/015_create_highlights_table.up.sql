@@ -0,0 +1,15 @@
+-- Quotes kept separately from free-form notes, in a user-defined order
+CREATE TABLE IF NOT EXISTS highlights (
+    id TEXT PRIMARY KEY,
+    book_id TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    location TEXT,
+    chapter TEXT,
+    comment TEXT,
+    position INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_highlights_book_position ON highlights(book_id, position);
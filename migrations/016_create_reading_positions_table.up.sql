@@ -0,0 +1,11 @@
+-- Where each user left off in the in-browser reader, one row per book
+CREATE TABLE IF NOT EXISTS reading_positions (
+    user_id TEXT NOT NULL,
+    book_id TEXT NOT NULL,
+    chapter INTEGER NOT NULL,
+    progress REAL NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, book_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+DROP TABLE IF EXISTS book_identifiers;
+
+ALTER TABLE books DROP COLUMN page_count;
+
+ALTER TABLE books DROP COLUMN description;
+
+ALTER TABLE books DROP COLUMN language;
+
+ALTER TABLE books DROP COLUMN publisher;
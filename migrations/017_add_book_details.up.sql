@@ -0,0 +1,19 @@
+-- Details read from book files by alayascan, or filled in by hand
+ALTER TABLE books ADD COLUMN publisher TEXT;
+
+ALTER TABLE books ADD COLUMN language TEXT;
+
+ALTER TABLE books ADD COLUMN description TEXT;
+
+ALTER TABLE books ADD COLUMN page_count INTEGER;
+
+-- ISBNs, ASINs and the like; a book may have several of each scheme
+CREATE TABLE IF NOT EXISTS book_identifiers (
+    book_id TEXT NOT NULL,
+    scheme TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (book_id, scheme, value),
+    FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_book_identifiers_value ON book_identifiers(value);
@@ -0,0 +1,85 @@
+-- People credited with books, linked to them as author, translator or editor
+CREATE TABLE IF NOT EXISTS authors (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS book_authors (
+    book_id TEXT NOT NULL,
+    author_id TEXT NOT NULL,
+    role TEXT NOT NULL DEFAULT 'author',
+    position INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (book_id, author_id, role),
+    FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE,
+    FOREIGN KEY (author_id) REFERENCES authors(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_book_authors_author_id ON book_authors(author_id);
+
+-- Names of authors merged into another, so books naming them are linked to that one
+CREATE TABLE IF NOT EXISTS author_aliases (
+    name TEXT PRIMARY KEY COLLATE NOCASE,
+    author_id TEXT NOT NULL,
+    FOREIGN KEY (author_id) REFERENCES authors(id) ON DELETE CASCADE
+);
+
+-- Link existing books to their authors, splitting the author field the way
+-- split_author_names does: on semicolons and ampersands when there are any,
+-- otherwise on commas unless it is a single "Last, First" name, which is one
+-- with a single word on either side of the comma or a last name starting with
+-- a lowercase particle, like "de la Cruz, Juana Inés"
+CREATE TEMP TABLE author_lists AS
+SELECT id AS book_id,
+       CASE
+           WHEN instr(author, ';') > 0 OR instr(author, '&') > 0 THEN replace(author, '&', ';')
+           WHEN length(author) - length(replace(author, ',', '')) = 1
+                AND (instr(trim(substr(author, 1, instr(author, ',') - 1)), ' ') = 0
+                     OR instr(trim(substr(author, instr(author, ',') + 1)), ' ') = 0
+                     OR substr(ltrim(author), 1, 1) BETWEEN 'a' AND 'z')
+               THEN author
+           ELSE replace(author, ',', ';')
+       END || ';' AS rest
+FROM books
+WHERE trim(COALESCE(author, '')) != '';
+
+CREATE TEMP TABLE author_names AS
+WITH RECURSIVE split(book_id, name, rest, position) AS (
+    SELECT book_id, NULL, rest, -1 FROM author_lists
+    UNION ALL
+    SELECT book_id,
+           trim(substr(rest, 1, instr(rest, ';') - 1)),
+           substr(rest, instr(rest, ';') + 1),
+           position + 1
+    FROM split
+    WHERE rest != ''
+)
+SELECT book_id, name, position FROM split WHERE name IS NOT NULL AND name != '';
+
+-- A name repeated in one field in another case is the same author, credited
+-- where it first appears
+DELETE FROM author_names
+WHERE EXISTS (
+    SELECT 1 FROM author_names earlier
+    WHERE earlier.book_id = author_names.book_id
+      AND earlier.name = author_names.name COLLATE NOCASE
+      AND earlier.position < author_names.position
+);
+
+INSERT OR IGNORE INTO authors (id, name, created_at)
+SELECT lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' ||
+       substr(lower(hex(randomblob(2))), 2) || '-' ||
+       substr('89ab', 1 + abs(random()) % 4, 1) || substr(lower(hex(randomblob(2))), 2) || '-' ||
+       lower(hex(randomblob(6))),
+       name,
+       strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now')
+FROM author_names;
+
+INSERT OR IGNORE INTO book_authors (book_id, author_id, role, position)
+SELECT n.book_id, a.id, 'author', n.position
+FROM author_names n
+JOIN authors a ON a.name = n.name;
+
+DROP TABLE author_names;
+
+DROP TABLE author_lists;
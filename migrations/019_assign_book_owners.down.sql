@@ -0,0 +1,32 @@
+-- Restores the 'public' default; owners given to books stay, as which books had
+-- none is not recorded
+CREATE TABLE books_new (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    author TEXT,
+    publication_year INTEGER,
+    filepath TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+    visibility TEXT NOT NULL DEFAULT 'public',
+    publisher TEXT,
+    language TEXT,
+    description TEXT,
+    page_count INTEGER
+);
+
+INSERT INTO books_new (id, title, author, publication_year, filepath, notes, created_at, updated_at, user_id, visibility, publisher, language, description, page_count)
+SELECT id, title, author, publication_year, filepath, notes, created_at, updated_at, user_id, visibility, publisher, language, description, page_count FROM books;
+
+DROP TABLE books;
+
+ALTER TABLE books_new RENAME TO books;
+
+CREATE INDEX IF NOT EXISTS idx_books_title ON books(title COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_author ON books(COALESCE(author, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_publication_year ON books(COALESCE(publication_year, 0), id);
+CREATE INDEX IF NOT EXISTS idx_books_created_at ON books(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_books_updated_at ON books(updated_at, id);
+CREATE INDEX IF NOT EXISTS idx_books_user_id ON books(user_id);
@@ -0,0 +1,36 @@
+-- Books without an owner, eg. saved by alayascan before it took --user, go to
+-- the first account, and new books default to private as in the web UI
+UPDATE books SET user_id = (SELECT id FROM users ORDER BY created_at LIMIT 1)
+WHERE user_id IS NULL;
+
+-- Changing a column default means rebuilding the table
+CREATE TABLE books_new (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    author TEXT,
+    publication_year INTEGER,
+    filepath TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    user_id TEXT REFERENCES users(id) ON DELETE SET NULL,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    publisher TEXT,
+    language TEXT,
+    description TEXT,
+    page_count INTEGER
+);
+
+INSERT INTO books_new (id, title, author, publication_year, filepath, notes, created_at, updated_at, user_id, visibility, publisher, language, description, page_count)
+SELECT id, title, author, publication_year, filepath, notes, created_at, updated_at, user_id, visibility, publisher, language, description, page_count FROM books;
+
+DROP TABLE books;
+
+ALTER TABLE books_new RENAME TO books;
+
+CREATE INDEX IF NOT EXISTS idx_books_title ON books(title COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_author ON books(COALESCE(author, '') COLLATE NOCASE, id);
+CREATE INDEX IF NOT EXISTS idx_books_publication_year ON books(COALESCE(publication_year, 0), id);
+CREATE INDEX IF NOT EXISTS idx_books_created_at ON books(created_at, id);
+CREATE INDEX IF NOT EXISTS idx_books_updated_at ON books(updated_at, id);
+CREATE INDEX IF NOT EXISTS idx_books_user_id ON books(user_id);
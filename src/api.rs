@@ -0,0 +1,485 @@
+use axum::{
+    Json,
+    extract::{
+        Path, Query, State,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::AppState;
+use crate::auth::{User, current_user};
+use crate::books::{
+    Book, BookAccessError, BookDetails, BookPageRequest, BookSort, BookVisibility, PageCursor,
+    editable_book,
+};
+use crate::covers::remove_cover;
+use crate::readings::ReadingStatus;
+
+const API_DEFAULT_LIMIT: i64 = 50;
+const API_MAX_LIMIT: i64 = 500;
+
+/// Error returned by the JSON API as `{"error": {"code": ..., "message": ...}}`.
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Authentication required",
+        )
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn internal(error: impl std::fmt::Display) -> Self {
+        eprintln!("API error: {error}");
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Internal server error",
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "error": {
+                "code": self.code,
+                "message": self.message,
+            }
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "invalid_body", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid_query", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "invalid_path", rejection.body_text())
+    }
+}
+
+impl From<BookAccessError> for ApiError {
+    fn from(error: BookAccessError) -> Self {
+        match error {
+            BookAccessError::NotFound => Self::not_found("Book not found"),
+            BookAccessError::Forbidden => Self::forbidden("You can only change your own books"),
+            BookAccessError::Database(error) => Self::internal(error),
+        }
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+pub async fn require_user(db: &AppState, headers: &HeaderMap) -> ApiResult<User> {
+    current_user(db, headers)
+        .await
+        .ok_or_else(ApiError::unauthorized)
+}
+
+#[derive(Deserialize)]
+pub struct ApiBookListQuery {
+    pub notes: Option<bool>,
+    pub mine: Option<bool>,
+    pub tag: Option<String>,
+    pub status: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ApiBookList {
+    pub books: Vec<Book>,
+    pub prev_cursor: Option<String>,
+    pub next_cursor: Option<String>,
+}
+
+/// A book as sent to `POST /books` and `PUT /books/{id}`. Only the title is
+/// required. A PUT replaces the whole book: absent fields are cleared and an
+/// absent visibility is private, as for a new book. Tags, identifiers and
+/// shares are not part of the payload and are left alone.
+#[derive(Deserialize)]
+pub struct ApiBookInput {
+    pub title: String,
+    pub author: Option<String>,
+    pub publication_year: Option<i32>,
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+    pub description: Option<String>,
+    pub page_count: Option<i64>,
+    pub notes: Option<String>,
+    pub visibility: Option<BookVisibility>,
+}
+
+impl ApiBookInput {
+    /// Details from the payload. Blank strings count as absent.
+    fn details(&self) -> BookDetails {
+        let field = |value: &Option<String>| non_empty(value.as_deref()).map(String::from);
+        BookDetails {
+            publisher: field(&self.publisher),
+            language: field(&self.language),
+            description: field(&self.description),
+            page_count: self.page_count.filter(|pages| *pages > 0),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApiNotesInput {
+    pub notes: Option<String>,
+}
+
+/// Trim a string and treat blank values as missing.
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+async fn fetch_book(db: &AppState, book_id: &str, viewer: Option<&User>) -> ApiResult<Book> {
+    db.get_visible_book(book_id, viewer.map(|u| u.id.as_str()))
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("Book not found"))
+}
+
+pub async fn api_books_list(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    query: Result<Query<ApiBookListQuery>, QueryRejection>,
+) -> ApiResult<Json<ApiBookList>> {
+    let Query(query) = query?;
+    let user = current_user(&db, &headers).await;
+    let owned_only = query.mine.unwrap_or(false);
+    if owned_only && user.is_none() {
+        return Err(ApiError::unauthorized());
+    }
+
+    let status = match query.status.as_deref() {
+        Some(value) => Some(
+            ReadingStatus::parse(value)
+                .ok_or_else(|| ApiError::bad_request(format!("Unknown status: {value}")))?,
+        ),
+        None => None,
+    };
+    if status.is_some() && user.is_none() {
+        return Err(ApiError::unauthorized());
+    }
+
+    let sort = match query.sort.as_deref() {
+        Some(value) => BookSort::parse(value)
+            .ok_or_else(|| ApiError::bad_request(format!("Unknown sort: {value}")))?,
+        None => BookSort::CreatedAt,
+    };
+    let descending = match query.order.as_deref() {
+        Some("asc") => false,
+        Some("desc") | None => true,
+        Some(value) => return Err(ApiError::bad_request(format!("Unknown order: {value}"))),
+    };
+    let cursor = match (query.after, query.before) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "Only one of after and before may be given",
+            ));
+        }
+        (Some(id), None) => Some(PageCursor::After(id)),
+        (None, Some(id)) => Some(PageCursor::Before(id)),
+        (None, None) => None,
+    };
+
+    let request = BookPageRequest {
+        viewer_id: user.map(|u| u.id),
+        sort,
+        descending,
+        owned_only,
+        notes_only: query.notes.unwrap_or(false),
+        tag: query.tag,
+        status,
+        cursor,
+        limit: query
+            .limit
+            .unwrap_or(API_DEFAULT_LIMIT)
+            .clamp(1, API_MAX_LIMIT),
+    };
+
+    let page = db
+        .get_books_page(&request)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let prev_cursor = page
+        .books
+        .first()
+        .filter(|_| page.has_prev)
+        .map(|b| b.id.clone());
+    let next_cursor = page
+        .books
+        .last()
+        .filter(|_| page.has_next)
+        .map(|b| b.id.clone());
+
+    Ok(Json(ApiBookList {
+        books: page.books,
+        prev_cursor,
+        next_cursor,
+    }))
+}
+
+pub async fn api_book_get(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    book_id: Result<Path<String>, PathRejection>,
+) -> ApiResult<Json<Book>> {
+    let Path(book_id) = book_id?;
+    let user = current_user(&db, &headers).await;
+    Ok(Json(fetch_book(&db, &book_id, user.as_ref()).await?))
+}
+
+pub async fn api_book_create(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Json<ApiBookInput>, JsonRejection>,
+) -> ApiResult<(StatusCode, Json<Book>)> {
+    let user = require_user(&db, &headers).await?;
+    let Json(input) = payload?;
+
+    let title = input.title.trim();
+    if title.is_empty() {
+        return Err(ApiError::bad_request("Title is required"));
+    }
+
+    let book_id = db
+        .create_book(
+            title,
+            non_empty(input.author.as_deref()),
+            input.publication_year,
+            non_empty(input.notes.as_deref()),
+            Some(&user.id),
+            input.visibility.unwrap_or_default(),
+            &input.details(),
+            &[],
+        )
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(fetch_book(&db, &book_id, Some(&user)).await?),
+    ))
+}
+
+pub async fn api_book_update(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    book_id: Result<Path<String>, PathRejection>,
+    payload: Result<Json<ApiBookInput>, JsonRejection>,
+) -> ApiResult<Json<Book>> {
+    let Path(book_id) = book_id?;
+    let user = require_user(&db, &headers).await?;
+    let Json(input) = payload?;
+    editable_book(&db, &book_id, &user).await?;
+
+    let title = input.title.trim();
+    if title.is_empty() {
+        return Err(ApiError::bad_request("Title is required"));
+    }
+
+    db.replace_book(
+        &book_id,
+        title,
+        non_empty(input.author.as_deref()),
+        input.publication_year,
+        non_empty(input.notes.as_deref()),
+        input.visibility.unwrap_or_default(),
+        &input.details(),
+    )
+    .await
+    .map_err(ApiError::internal)?;
+
+    Ok(Json(fetch_book(&db, &book_id, Some(&user)).await?))
+}
+
+pub async fn api_book_notes_update(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    book_id: Result<Path<String>, PathRejection>,
+    payload: Result<Json<ApiNotesInput>, JsonRejection>,
+) -> ApiResult<Json<Book>> {
+    let Path(book_id) = book_id?;
+    let user = require_user(&db, &headers).await?;
+    let Json(input) = payload?;
+    editable_book(&db, &book_id, &user).await?;
+
+    db.update_book_notes(&book_id, non_empty(input.notes.as_deref()))
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(Json(fetch_book(&db, &book_id, Some(&user)).await?))
+}
+
+pub async fn api_book_delete(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    book_id: Result<Path<String>, PathRejection>,
+) -> ApiResult<StatusCode> {
+    let Path(book_id) = book_id?;
+    let user = require_user(&db, &headers).await?;
+    editable_book(&db, &book_id, &user).await?;
+
+    db.delete_book(&book_id).await.map_err(ApiError::internal)?;
+    remove_cover(&book_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn api_not_found() -> ApiError {
+    ApiError::not_found("No such API endpoint")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::test_database;
+    use axum::{body::Body, http::Request};
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn send(
+        app: &axum::Router,
+        method: &str,
+        uri: &str,
+        token: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn put_replaces_the_whole_book() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let token = db.create_api_token(&user_id, "test").await.unwrap();
+        let db = std::sync::Arc::new(db);
+        let app = crate::create_app(db.clone());
+
+        let (status, book) = send(
+            &app,
+            "POST",
+            "/api/v1/books",
+            &token,
+            json!({
+                "title": "Invisible Cities",
+                "author": "Italo Calvino",
+                "publication_year": 1972,
+                "publisher": "Einaudi",
+                "page_count": 165,
+                "notes": "Kublai Khan",
+                "visibility": "public",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = book["id"].as_str().unwrap().to_string();
+        db.set_book_tags(&id, &["fiction"]).await.unwrap();
+
+        let uri = format!("/api/v1/books/{id}");
+        let (status, book) = send(
+            &app,
+            "PUT",
+            &uri,
+            &token,
+            json!({ "title": "Le città invisibili", "author": "Italo Calvino" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(book["title"], "Le città invisibili");
+        assert_eq!(book["author"], "Italo Calvino");
+        for field in ["publication_year", "publisher", "page_count", "notes"] {
+            assert_eq!(book[field], Value::Null, "{field} was kept");
+        }
+        assert_eq!(book["visibility"], "private");
+        assert_eq!(db.get_book_tags(&id).await.unwrap(), ["fiction"]);
+
+        let (status, _) = send(&app, "PUT", &uri, &token, json!({ "title": " " })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let book = db.get_book_by_id(&id).await.unwrap().unwrap();
+        assert_eq!(book.title, "Le città invisibili");
+    }
+
+    #[tokio::test]
+    async fn writes_need_a_live_token() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let token = db.create_api_token(&user_id, "script").await.unwrap();
+        let db = std::sync::Arc::new(db);
+        let app = crate::create_app(db.clone());
+        let book = json!({ "title": "Invisible Cities" });
+
+        let (status, body) = send(&app, "POST", "/api/v1/books", "alaya_nope", book.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "unauthorized");
+
+        let (status, _) = send(&app, "POST", "/api/v1/books", &token, book.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let tokens = db.get_api_tokens(&user_id).await.unwrap();
+        assert!(tokens[0].last_used_at.is_some());
+
+        db.delete_api_token(&user_id, &tokens[0].id).await.unwrap();
+        let (status, _) = send(&app, "POST", "/api/v1/books", &token, book).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,14 +1,16 @@
 use askama::Template;
 use axum::{
-    extract::{Form, State},
+    extract::{Form, Path, Request, State},
     http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
     response::{Html, IntoResponse, Redirect, Response},
 };
 use serde::{Deserialize, Serialize};
 use std::env;
 
 use crate::AppState;
-use crate::database::Database;
+use crate::database::{Database, SESSION_TTL};
+use crate::readings::group_by_year;
 use crate::templates::{ChangePasswordTemplate, LoginTemplate, ProfileTemplate, SignupTemplate};
 
 // User-related structures
@@ -21,6 +23,58 @@ pub struct User {
     pub created_at: String,
 }
 
+// Personal API token, as listed on the profile page (never the secret itself)
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+impl ApiToken {
+    pub fn created_date(&self) -> &str {
+        self.created_at
+            .split('T')
+            .next()
+            .unwrap_or(&self.created_at)
+    }
+
+    pub fn last_used_date(&self) -> Option<&str> {
+        self.last_used_at
+            .as_deref()
+            .map(|date| date.split('T').next().unwrap_or(date))
+    }
+}
+
+// Login session, as listed on the profile page
+pub struct Session {
+    pub id: String,
+    pub created_at: String,
+    pub last_seen_at: Option<String>,
+    pub user_agent: Option<String>,
+    pub is_current: bool,
+}
+
+impl Session {
+    pub fn created_display(&self) -> String {
+        short_timestamp(&self.created_at)
+    }
+
+    pub fn last_seen_display(&self) -> String {
+        short_timestamp(self.last_seen_at.as_deref().unwrap_or(&self.created_at))
+    }
+}
+
+/// Format an RFC 3339 timestamp as `YYYY-MM-DD HH:MM`.
+fn short_timestamp(value: &str) -> String {
+    value.get(..16).unwrap_or(value).replace('T', " ")
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiTokenForm {
+    pub name: String,
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub username: String,
@@ -42,7 +96,11 @@ pub async fn login_page(State(db): State<AppState>, headers: HeaderMap) -> impl
     render_login(String::new(), None)
 }
 
-pub async fn login_submit(State(db): State<AppState>, Form(form): Form<LoginRequest>) -> Response {
+pub async fn login_submit(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<LoginRequest>,
+) -> Response {
     let username = form.username.trim().to_string();
     let password = form.password;
 
@@ -58,7 +116,7 @@ pub async fn login_submit(State(db): State<AppState>, Form(form): Form<LoginRequ
     }
 
     match db.verify_user(&username, &password).await {
-        Ok(Some(user)) => match db.create_session(&user.id).await {
+        Ok(Some(user)) => match db.create_session(&user.id, user_agent(&headers)).await {
             Ok(token) => {
                 let mut response = Redirect::to("/").into_response();
                 if let Some(cookie) = build_session_cookie(&token) {
@@ -94,7 +152,11 @@ pub async fn signup_page(State(db): State<AppState>, headers: HeaderMap) -> impl
     render_signup(String::new(), None)
 }
 
-pub async fn signup_submit(State(db): State<AppState>, Form(form): Form<SignupForm>) -> Response {
+pub async fn signup_submit(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<SignupForm>,
+) -> Response {
     if signups_disabled() {
         return signup_disabled_response();
     }
@@ -119,7 +181,7 @@ pub async fn signup_submit(State(db): State<AppState>, Form(form): Form<SignupFo
     }
 
     match db.create_user(&username, &password).await {
-        Ok(user_id) => match db.create_session(&user_id).await {
+        Ok(user_id) => match db.create_session(&user_id, user_agent(&headers)).await {
             Ok(token) => {
                 let mut response = Redirect::to("/").into_response();
                 if let Some(cookie) = build_session_cookie(&token) {
@@ -166,17 +228,132 @@ pub async fn logout(State(db): State<AppState>, headers: HeaderMap) -> Response
 pub async fn profile_page(State(db): State<AppState>, headers: HeaderMap) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
+    };
+
+    render_profile(&db, &headers, user, None, None).await
+}
+
+pub async fn session_delete(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = db.delete_session_by_id(&user.id, &session_id).await {
+        eprintln!("Session deletion error: {error}");
     }
 
-    let book_count = db.get_book_count().await.unwrap_or(0);
+    Redirect::to("/profile").into_response()
+}
+
+pub async fn session_delete_all(State(db): State<AppState>, headers: HeaderMap) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = db.delete_user_sessions(&user.id, None).await {
+        eprintln!("Session deletion error: {error}");
+    }
+
+    let mut response = Redirect::to("/login").into_response();
+    response
+        .headers_mut()
+        .insert(header::SET_COOKIE, clear_session_cookie());
+    response
+}
+
+pub async fn api_token_create(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<CreateApiTokenForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return render_profile(
+            &db,
+            &headers,
+            user,
+            None,
+            Some("Token name is required".to_string()),
+        )
+        .await;
+    }
+
+    match db.create_api_token(&user.id, name).await {
+        Ok(token) => render_profile(&db, &headers, user, Some(token), None).await,
+        Err(error) => {
+            eprintln!("API token creation error: {error}");
+            render_profile(
+                &db,
+                &headers,
+                user,
+                None,
+                Some("Could not create token. Please try again.".to_string()),
+            )
+            .await
+        }
+    }
+}
+
+pub async fn api_token_delete(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(token_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = db.delete_api_token(&user.id, &token_id).await {
+        eprintln!("API token deletion error: {error}");
+    }
+
+    Redirect::to("/profile").into_response()
+}
+
+async fn render_profile(
+    db: &Database,
+    headers: &HeaderMap,
+    user: User,
+    new_token: Option<String>,
+    error_message: Option<String>,
+) -> Response {
+    let book_count = db.get_owned_book_count(&user.id).await.unwrap_or(0);
+    let finished_years = group_by_year(db.get_finished_books(&user.id).await.unwrap_or_default());
+    let api_tokens = db.get_api_tokens(&user.id).await.unwrap_or_default();
+    let current_token = extract_session_token(headers);
+    let sessions = db
+        .get_sessions(&user.id, current_token.as_deref())
+        .await
+        .unwrap_or_default();
 
     let template = ProfileTemplate {
         is_authenticated: true,
         signups_disabled: signups_disabled(),
-        username: user.map(|u| u.username).unwrap_or_default(),
+        username: user.username,
         book_count,
+        finished_years,
+        api_tokens,
+        new_token,
+        sessions,
+        error_message,
     };
 
     Html(template.render().unwrap()).into_response()
@@ -231,6 +408,22 @@ pub async fn change_password(
     // Update password
     match db.update_password(&user.id, &form.new_password).await {
         Ok(_) => {
+            // Sign out every other session and give this one a fresh token
+            let current_token = extract_session_token(&headers);
+            if let Err(error) = db
+                .delete_user_sessions(&user.id, current_token.as_deref())
+                .await
+            {
+                eprintln!("Session invalidation error: {error}");
+            }
+            let new_token = match current_token {
+                Some(token) => db.rotate_session(&token).await.unwrap_or_else(|error| {
+                    eprintln!("Session rotation error: {error}");
+                    None
+                }),
+                None => None,
+            };
+
             let template = ChangePasswordTemplate {
                 is_authenticated: true,
                 signups_disabled: signups_disabled(),
@@ -238,7 +431,11 @@ pub async fn change_password(
                 error_message: None,
                 success_message: Some("Password changed successfully".to_string()),
             };
-            Html(template.render().unwrap()).into_response()
+            let mut response = Html(template.render().unwrap()).into_response();
+            if let Some(cookie) = new_token.as_deref().and_then(build_session_cookie) {
+                response.headers_mut().insert(header::SET_COOKIE, cookie);
+            }
+            response
         }
         Err(error) => {
             eprintln!("Password update error: {error}");
@@ -279,10 +476,55 @@ fn render_signup(form_username: String, error_message: Option<String>) -> Respon
 }
 
 pub async fn current_user(db: &Database, headers: &HeaderMap) -> Option<User> {
+    if let Some(token) = extract_bearer_token(headers) {
+        return db.validate_api_token(&token).await.ok()?;
+    }
+
     let token = extract_session_token(headers)?;
     db.validate_session(&token).await.ok()?
 }
 
+fn extract_bearer_token(headers: &HeaderMap) -> Option<String> {
+    let auth_header = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = auth_header.trim().split_once(' ')?;
+
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Middleware that slides the expiry of the session used for a request,
+/// re-issuing the cookie whenever the server-side expiry moved.
+pub async fn renew_session(State(db): State<AppState>, request: Request, next: Next) -> Response {
+    let token = extract_session_token(request.headers());
+    let mut response = next.run(request).await;
+
+    // Handlers that set their own cookie (login, logout, rotation) win
+    if let Some(token) = token
+        && !response.headers().contains_key(header::SET_COOKIE)
+    {
+        match db.touch_session(&token).await {
+            Ok(true) => {
+                if let Some(cookie) = build_session_cookie(&token) {
+                    response.headers_mut().insert(header::SET_COOKIE, cookie);
+                }
+            }
+            Ok(false) => {}
+            Err(error) => eprintln!("Session renewal error: {error}"),
+        }
+    }
+
+    response
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+}
+
 fn extract_session_token(headers: &HeaderMap) -> Option<String> {
     let cookie_header = headers.get(header::COOKIE)?.to_str().ok()?;
 
@@ -298,7 +540,8 @@ fn extract_session_token(headers: &HeaderMap) -> Option<String> {
 
 fn build_session_cookie(token: &str) -> Option<HeaderValue> {
     HeaderValue::from_str(&format!(
-        "session_token={token}; HttpOnly; Path=/; SameSite=Lax; Max-Age=604800"
+        "session_token={token}; HttpOnly; Path=/; SameSite=Lax; Max-Age={}",
+        SESSION_TTL.num_seconds()
     ))
     .ok()
 }
@@ -0,0 +1,296 @@
+use askama::Template;
+use axum::{
+    extract::{Form, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use crate::auth::{current_user, signups_disabled};
+use crate::books::Book;
+use crate::templates::{AuthorDetailTemplate, AuthorListTemplate};
+
+/// What someone did for a book.
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorRole {
+    Author,
+    Translator,
+    Editor,
+}
+
+impl AuthorRole {
+    pub const ALL: [AuthorRole; 3] = [
+        AuthorRole::Author,
+        AuthorRole::Translator,
+        AuthorRole::Editor,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthorRole::Author => "author",
+            AuthorRole::Translator => "translator",
+            AuthorRole::Editor => "editor",
+        }
+    }
+}
+
+pub struct Author {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+/// An author with the number of books the viewer can see.
+#[derive(Clone)]
+pub struct AuthorCount {
+    pub id: String,
+    pub name: String,
+    pub count: i64,
+}
+
+/// Someone credited with a book, in one role.
+pub struct BookAuthor {
+    pub id: String,
+    pub name: String,
+    pub role: AuthorRole,
+}
+
+/// Those credited with a book in one role.
+pub fn credited_as(authors: &[BookAuthor], role: AuthorRole) -> Vec<&BookAuthor> {
+    authors
+        .iter()
+        .filter(|author| author.role == role)
+        .collect()
+}
+
+/// A book on an author's page, with what the author did for it.
+pub struct AuthorBook {
+    pub book: Book,
+    pub role: AuthorRole,
+}
+
+#[derive(Deserialize)]
+pub struct MergeAuthorForm {
+    pub into: String,
+}
+
+/// What is left of a name once order, case, spacing and punctuation are set
+/// aside, so "Calvino, Italo", "Italo Calvino" and "italo  calvino." compare equal.
+pub fn duplicate_key(name: &str) -> String {
+    let name = match name.split_once(',') {
+        Some((last, first)) if !first.contains(',') => format!("{first} {last}"),
+        _ => name.to_string(),
+    };
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Groups of two or more authors whose names look like the same person.
+pub fn find_duplicates(authors: &[AuthorCount]) -> Vec<Vec<AuthorCount>> {
+    let mut groups: Vec<(String, Vec<AuthorCount>)> = Vec::new();
+    for author in authors {
+        let key = duplicate_key(&author.name);
+        if key.is_empty() {
+            continue;
+        }
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, group)) => group.push(author.clone()),
+            None => groups.push((key, vec![author.clone()])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, group)| group)
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+pub async fn author_list(State(db): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let authors = db
+        .get_author_counts(viewer_id)
+        .await
+        .unwrap_or_else(|error| {
+            eprintln!("Error fetching authors: {error}");
+            Vec::new()
+        });
+    let duplicates = if user.is_some() {
+        find_duplicates(&authors)
+    } else {
+        Vec::new()
+    };
+
+    let template = AuthorListTemplate {
+        is_authenticated: user.is_some(),
+        signups_disabled: signups_disabled(),
+        username: user.map(|u| u.username).unwrap_or_default(),
+        authors,
+        duplicates,
+    };
+
+    Html(template.render().unwrap())
+}
+
+pub async fn author_detail(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(author_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let author = match db.get_author(&author_id).await {
+        Ok(Some(author)) => author,
+        Ok(None) => return Redirect::to("/authors").into_response(),
+        Err(error) => {
+            eprintln!("Error fetching author: {error}");
+            return Redirect::to("/authors").into_response();
+        }
+    };
+
+    let books = db
+        .get_author_books(&author.id, viewer_id)
+        .await
+        .unwrap_or_else(|error| {
+            eprintln!("Error fetching author books: {error}");
+            Vec::new()
+        });
+
+    // Other authors who look like the same person, offered for merging
+    let duplicates = if user.is_some() {
+        let key = duplicate_key(&author.name);
+        db.get_author_counts(viewer_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|other| other.id != author.id && duplicate_key(&other.name) == key)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let template = AuthorDetailTemplate {
+        is_authenticated: user.is_some(),
+        signups_disabled: signups_disabled(),
+        username: user.map(|u| u.username).unwrap_or_default(),
+        author,
+        books,
+        duplicates,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+/// Merge the author into another one. Renames the author in the books they are
+/// credited with, so the user must be allowed to change all of them, and the
+/// other author must be one they can see. The merged name only becomes an alias
+/// when the user also owns all of the other author's books.
+pub async fn author_merge(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(author_id): Path<String>,
+    Form(form): Form<MergeAuthorForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if form.into == author_id {
+        return Redirect::to(&format!("/authors/{}", author_id)).into_response();
+    }
+
+    for id in [&author_id, &form.into] {
+        match db.get_author(id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::NOT_FOUND, "Author not found").into_response(),
+            Err(error) => {
+                eprintln!("Error fetching author: {error}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+            }
+        }
+    }
+
+    match db.get_author_books(&form.into, Some(&user.id)).await {
+        Ok(books) if !books.is_empty() => {}
+        Ok(_) => return (StatusCode::NOT_FOUND, "Author not found").into_response(),
+        Err(error) => {
+            eprintln!("Error fetching author books: {error}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    }
+
+    match db.author_has_books_of_others(&author_id, &user.id).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return (
+                StatusCode::FORBIDDEN,
+                "You can only merge authors whose books you can change",
+            )
+                .into_response();
+        }
+        Err(error) => {
+            eprintln!("Error fetching author books: {error}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    }
+
+    let keep_alias = match db.author_has_books_of_others(&form.into, &user.id).await {
+        Ok(others) => !others,
+        Err(error) => {
+            eprintln!("Error fetching author books: {error}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    };
+
+    match db.merge_authors(&author_id, &form.into, keep_alias).await {
+        Ok(()) => Redirect::to(&format!("/authors/{}", form.into)).into_response(),
+        Err(error) => {
+            eprintln!("Author merge error: {error}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not merge authors").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_key_ignores_order_and_punctuation() {
+        let key = duplicate_key("Italo Calvino");
+        assert_eq!(key, "italocalvino");
+        assert_eq!(duplicate_key("Calvino, Italo"), key);
+        assert_eq!(duplicate_key("italo  calvino."), key);
+        assert_eq!(duplicate_key("CALVINO,Italo"), key);
+    }
+
+    #[test]
+    fn duplicate_key_keeps_particles_and_accents() {
+        let key = duplicate_key("Juana Inés de la Cruz");
+        assert_eq!(key, "juanainésdelacruz");
+        assert_eq!(duplicate_key("de la Cruz, Juana Inés"), key);
+        assert_ne!(duplicate_key("Juana Ines de la Cruz"), key);
+    }
+
+    #[test]
+    fn duplicate_key_of_lists_is_not_reordered() {
+        assert_eq!(
+            duplicate_key("Calvino, Italo, Weaver"),
+            "calvinoitaloweaver"
+        );
+        assert_eq!(duplicate_key(",.;"), "");
+    }
+}
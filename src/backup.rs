@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+
+use crate::database::{BackupInfo, Database};
+
+type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Scheduled backups are named `alaya-YYYYMMDD-HHMMSS.mmm.db`, so they sort by
+/// age and two taken within the same second do not clash.
+const BACKUP_PREFIX: &str = "alaya-";
+const BACKUP_EXTENSION: &str = ".db";
+
+/// Copy the live database to `path` while the server keeps running.
+pub async fn create_backup(db: &Database, path: &Path) -> Result<(), DynError> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+    db.backup_to(path).await?;
+    Ok(())
+}
+
+/// Back up into `dir` under a timestamped name, then delete all but the newest
+/// `keep` scheduled backups there. Returns the new backup's path.
+pub async fn scheduled_backup(db: &Database, dir: &Path, keep: usize) -> Result<PathBuf, DynError> {
+    std::fs::create_dir_all(dir)?;
+    let name = format!(
+        "{BACKUP_PREFIX}{}{BACKUP_EXTENSION}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S%.3f")
+    );
+    let path = dir.join(name);
+    create_backup(db, &path).await?;
+
+    for old in prune_backups(dir, keep)? {
+        println!("Removed old backup {}", old.display());
+    }
+    Ok(path)
+}
+
+/// Delete scheduled backups beyond the newest `keep`. Other files are left alone.
+fn prune_backups(dir: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_EXTENSION)
+                })
+        })
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.into_iter().take(excess).collect();
+    for path in &removed {
+        std::fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Open a backup read-only and check it before it is restored.
+pub async fn validate_backup(path: &Path) -> Result<BackupInfo, DynError> {
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.display()).into());
+    }
+    let backup = Database::open_read_only(path).await?;
+    let info = backup.check_backup().await;
+    backup.pool.close().await;
+    info
+}
+
+/// Replace the database at `database_path` with a validated backup. The current
+/// file is kept next to it, and its path returned. The server must be stopped.
+pub async fn restore_backup(
+    backup: &Path,
+    database_path: &Path,
+) -> Result<(BackupInfo, Option<PathBuf>), DynError> {
+    let info = validate_backup(backup).await?;
+
+    // A leftover journal would be replayed onto the restored file
+    for suffix in ["-journal", "-wal"] {
+        let journal = with_suffix(database_path, suffix);
+        if journal.exists() {
+            return Err(format!(
+                "{} exists; stop the server before restoring",
+                journal.display()
+            )
+            .into());
+        }
+    }
+
+    // Copy next to the target first so the final swap is a rename on one filesystem
+    let staging = with_suffix(database_path, ".restoring");
+    std::fs::copy(backup, &staging)?;
+
+    let previous = if database_path.exists() {
+        let previous = with_suffix(
+            database_path,
+            &format!(
+                ".{}.before-restore",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            ),
+        );
+        std::fs::rename(database_path, &previous)?;
+        Some(previous)
+    } else {
+        None
+    };
+
+    if let Err(error) = std::fs::rename(&staging, database_path) {
+        if let Some(previous) = &previous {
+            std::fs::rename(previous, database_path)?;
+        }
+        return Err(error.into());
+    }
+
+    Ok((info, previous))
+}
+
+/// `path` with `suffix` appended to its file name, eg. `alaya.db-journal`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::books::BookVisibility;
+    use crate::database::tests::{add_book, test_database};
+
+    #[tokio::test]
+    async fn backup_validates_and_restores() {
+        let (dir, db) = test_database().await;
+        db.create_user("reader", "secret").await.unwrap();
+        add_book(&db, "Invisible Cities", None, None, BookVisibility::Public).await;
+
+        let backup = dir.path().join("backup.db");
+        create_backup(&db, &backup).await.unwrap();
+        assert!(create_backup(&db, &backup).await.is_err());
+
+        let info = validate_backup(&backup).await.unwrap();
+        assert_eq!((info.users, info.books), (1, 1));
+        assert_eq!(info.migrations, db.migration_status().await.unwrap().len());
+
+        let target = dir.path().join("restored.db");
+        std::fs::write(&target, "old database").unwrap();
+        let (info, previous) = restore_backup(&backup, &target).await.unwrap();
+        assert_eq!(info.books, 1);
+        let previous = previous.unwrap();
+        assert_eq!(std::fs::read_to_string(previous).unwrap(), "old database");
+        assert_eq!(validate_backup(&target).await.unwrap().users, 1);
+    }
+
+    #[tokio::test]
+    async fn other_files_are_not_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("alaya.db");
+        std::fs::write(&target, "current").unwrap();
+
+        let text = dir.path().join("notes.db");
+        std::fs::write(&text, "not a database").unwrap();
+        assert!(restore_backup(&text, &target).await.is_err());
+
+        let other = dir.path().join("other.db");
+        let url = format!("sqlite://{}?mode=rwc", other.display());
+        let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+        sqlx::query("CREATE TABLE things (id INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        assert!(restore_backup(&other, &target).await.is_err());
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "current");
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "alaya-20240101-000000.000.db",
+            "alaya-20240102-000000.000.db",
+            "alaya-20240103-000000.000.db",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let removed = prune_backups(dir.path(), 2).unwrap();
+        assert_eq!(removed, [dir.path().join("alaya-20240101-000000.000.db")]);
+        assert!(dir.path().join("notes.txt").exists());
+        assert!(dir.path().join("alaya-20240103-000000.000.db").exists());
+    }
+}
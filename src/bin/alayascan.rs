@@ -1,13 +1,15 @@
 use alaya::Database;
+use alaya::auth::User;
+use alaya::authors::AuthorRole;
+use alaya::books::{BookDetails, BookVisibility};
+use alaya::clippings::{import_clippings, parse_clippings};
+use alaya::covers::{cover_outdated, extract_cover, save_cover};
 use alaya::gpt::{GptClient, GptConfig, GptError};
-use epub::doc::EpubDoc;
-use lopdf::Document;
-use std::path::Path;
-use std::{env, process};
+use alaya::metadata::{BookMetadata, extract_metadata, join_author_names, supported_extensions};
+use std::path::{Component, Path};
+use std::{env, fs, process};
 use walkdir::WalkDir;
 
-const BOOK_EXTENSIONS: &[&str] = &["epub", "mobi", "pdf", "docx", "txt"];
-
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
@@ -28,13 +30,44 @@ async fn main() {
         // Check for --save flag
         let save_to_db = args.iter().any(|a| a == "--save" || a == "-s");
 
-        if let Err(e) = scan_directory(&args[1], save_to_db).await {
+        // Check for --tag-dirs flag
+        let tag_dirs = args.iter().any(|a| a == "--tag-dirs" || a == "-t");
+
+        // Check for --user and --visibility options
+        let username = option_value(&args, "--user", "-u");
+        let visibility = match option_value(&args, "--visibility", "-v") {
+            Some(value) => BookVisibility::parse(value).unwrap_or_else(|| {
+                eprintln!("Error: --visibility must be private, shared or public");
+                process::exit(1);
+            }),
+            None => BookVisibility::default(),
+        };
+
+        if let Err(e) = scan_directory(&args[1], save_to_db, tag_dirs, username, visibility).await {
             eprintln!("Error scanning directory: {}", e);
             process::exit(1);
         }
         return;
     }
 
+    // Check for --clippings option
+    if args[0] == "--clippings" || args[0] == "-c" {
+        if args.len() < 2 {
+            eprintln!("Error: --clippings requires a file path");
+            print_usage();
+            process::exit(1);
+        }
+
+        // Check for --user option
+        let username = option_value(&args, "--user", "-u");
+
+        if let Err(e) = import_clippings_file(&args[1], username).await {
+            eprintln!("Error importing clippings: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     // Default behavior: summarize book title
     let title = args.join(" ");
 
@@ -53,6 +86,19 @@ async fn main() {
     }
 }
 
+/// The value following `--long`/`-short` in the arguments, if given.
+fn option_value<'a>(args: &'a [String], long: &str, short: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == long || a == short)
+        .map(|i| match args.get(i + 1) {
+            Some(value) => value.as_str(),
+            None => {
+                eprintln!("Error: {} requires a value", long);
+                process::exit(1);
+            }
+        })
+}
+
 fn print_usage() {
     eprintln!("Usage:");
     eprintln!("  alayascan \"Book Title\"              - Summarize a book by title");
@@ -60,13 +106,30 @@ fn print_usage() {
     eprintln!("  alayascan -d <directory>            - Scan directory for book files (short form)");
     eprintln!("  alayascan --scan-dir <dir> --save   - Scan and save books to database");
     eprintln!("  alayascan -d <dir> -s               - Scan and save (short form)");
+    eprintln!("  alayascan -d <dir> -s --tag-dirs    - Also tag books with their directory names");
+    eprintln!("  alayascan -d <dir> -s -t            - Scan, save and tag (short form)");
+    eprintln!(
+        "  alayascan -d <dir> -s --user <name> - Save new books as a user's (default: first account)"
+    );
+    eprintln!("  alayascan -d <dir> -s --visibility <private|shared|public>");
+    eprintln!("                                      - Visibility of new books (default: private)");
+    eprintln!("  alayascan --clippings <file>        - Import Kindle My Clippings.txt highlights");
+    eprintln!(
+        "  alayascan -c <file> --user <name>   - Import clippings into a user's books (default: first account)"
+    );
     eprintln!();
-    eprintln!("Supported file types: epub, mobi, pdf, docx, txt");
+    eprintln!(
+        "Supported file types: {}",
+        supported_extensions().collect::<Vec<_>>().join(", ")
+    );
 }
 
 async fn scan_directory(
     dir_path: &str,
     save_to_db: bool,
+    tag_dirs: bool,
+    username: Option<&str>,
+    visibility: BookVisibility,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path = Path::new(dir_path);
 
@@ -89,6 +152,11 @@ async fn scan_directory(
         None
     };
 
+    let owner = match &db {
+        Some(db) => Some(library_owner(db, username).await?),
+        None => None,
+    };
+
     // Canonicalize the base path for proper relative path calculation
     let base_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
 
@@ -96,6 +164,9 @@ async fn scan_directory(
     if save_to_db {
         println!("Saving books to database...");
         println!("(storing paths relative to LIBRARY_PATH)");
+        if let Some(owner) = &owner {
+            println!("(new books belong to {})", owner.username);
+        }
     }
     println!();
 
@@ -110,7 +181,7 @@ async fn scan_directory(
             && let Some(ext_str) = ext.to_str()
         {
             let ext_lower = ext_str.to_lowercase();
-            if BOOK_EXTENSIONS.contains(&ext_lower.as_str()) {
+            if supported_extensions().any(|supported| supported == ext_lower) {
                 println!("{}", file_path.display());
 
                 // Calculate relative path from the base directory
@@ -121,55 +192,63 @@ async fn scan_directory(
                     .unwrap_or_else(|| file_path.to_path_buf());
                 let relative_path_str = relative_path.to_string_lossy().to_string();
 
-                // Extract metadata based on file type
-                let book_data = if ext_lower == "epub" {
-                    extract_epub_metadata(file_path).map(|m| {
-                        print_epub_metadata(&m);
-                        BookData {
-                            title: m.title,
-                            author: m.author,
-                            publication_year: parse_year(&m.date),
-                            filepath: relative_path_str.clone(),
-                        }
-                    })
-                } else if ext_lower == "pdf" {
-                    extract_pdf_metadata(file_path).map(|m| {
-                        print_pdf_metadata(&m);
-                        BookData {
-                            title: m.title,
-                            author: m.author,
-                            publication_year: parse_year(&m.creation_date),
-                            filepath: relative_path_str.clone(),
-                        }
-                    })
-                } else {
-                    // For other formats, use filename as title
-                    Some(BookData {
-                        title: file_path
-                            .file_stem()
-                            .and_then(|s| s.to_str())
-                            .map(String::from),
-                        author: None,
-                        publication_year: None,
-                        filepath: relative_path_str.clone(),
-                    })
-                };
+                let metadata = extract_metadata(file_path).unwrap_or_default();
+                print_metadata(&metadata);
+                // Files without a title of their own go by their file name
+                let title = metadata
+                    .title
+                    .clone()
+                    .or_else(|| file_stem_title(file_path));
 
                 // Save to database if requested
-                if let (Some(db), Some(data)) = (&db, book_data) {
-                    if let Some(title) = &data.title {
+                if let Some(db) = &db {
+                    if let Some(title) = &title {
                         match db
                             .upsert_book_by_filepath(
-                                &data.filepath,
+                                &relative_path_str,
                                 title,
-                                data.author.as_deref(),
-                                data.publication_year,
+                                metadata.author().as_deref(),
+                                metadata.publication_year(),
+                                &BookDetails::from_metadata(&metadata),
+                                &metadata.identifiers,
+                                owner.as_ref().map(|u| u.id.as_str()),
+                                visibility,
                             )
                             .await
                         {
-                            Ok(_) => {
+                            Ok(book_id) => {
                                 println!("  [SAVED]");
                                 saved_count += 1;
+
+                                // Files without translators or editors keep those added by hand
+                                for (role, names) in [
+                                    (AuthorRole::Translator, &metadata.translators),
+                                    (AuthorRole::Editor, &metadata.editors),
+                                ] {
+                                    if !names.is_empty()
+                                        && let Err(e) =
+                                            db.set_book_contributors(&book_id, role, names).await
+                                    {
+                                        eprintln!("  [ERROR saving {}s: {}]", role.as_str(), e);
+                                    }
+                                }
+
+                                if cover_outdated(&book_id, file_path)
+                                    && let Some(image) = extract_cover(file_path)
+                                {
+                                    match save_cover(&book_id, &image) {
+                                        Ok(()) => println!("  [COVER]"),
+                                        Err(e) => eprintln!("  [ERROR saving cover: {}]", e),
+                                    }
+                                }
+
+                                let tags = directory_tags(&relative_path_str);
+                                if tag_dirs && !tags.is_empty() {
+                                    match db.add_book_tags(&book_id, &tags).await {
+                                        Ok(_) => println!("  [TAGGED: {}]", tags.join(", ")),
+                                        Err(e) => eprintln!("  [ERROR tagging: {}]", e),
+                                    }
+                                }
                             }
                             Err(e) => {
                                 eprintln!("  [ERROR saving: {}]", e);
@@ -194,73 +273,97 @@ async fn scan_directory(
     Ok(())
 }
 
-/// Data extracted from a book file for database storage
-struct BookData {
-    title: Option<String>,
-    author: Option<String>,
-    publication_year: Option<i32>,
-    filepath: String,
+/// The user books are saved for: `--user`, or else the first account (users
+/// are listed newest first).
+async fn library_owner(
+    db: &Database,
+    username: Option<&str>,
+) -> Result<User, Box<dyn std::error::Error>> {
+    match username {
+        Some(username) => Ok(db
+            .get_user_by_username(username)
+            .await?
+            .ok_or_else(|| format!("No user named '{}'", username))?),
+        None => Ok(db.get_all_users().await?.pop().ok_or(
+            "No accounts yet: sign up in the web UI before saving books, so they have an owner",
+        )?),
+    }
 }
 
-/// Parse a year from various date formats
-fn parse_year(date: &Option<String>) -> Option<i32> {
-    let date = date.as_ref()?;
-
-    // Only work with ASCII digits to avoid UTF-8 boundary issues
-    let chars: Vec<char> = date.chars().collect();
+async fn import_clippings_file(
+    file_path: &str,
+    username: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let contents =
+        fs::read(file_path).map_err(|e| format!("Could not read '{}': {}", file_path, e))?;
+    let clippings = parse_clippings(&String::from_utf8_lossy(&contents));
 
-    // Try to extract a 4-digit year from the beginning
-    if chars.len() >= 4 {
-        let first_four: String = chars[..4].iter().collect();
-        if let Ok(year) = first_four.parse::<i32>()
-            && (1000..=2100).contains(&year)
-        {
-            return Some(year);
-        }
+    println!("Found {} clipping(s) in {}", clippings.len(), file_path);
+    if clippings.is_empty() {
+        return Ok(());
     }
 
-    // Try to find any 4-digit year in the string
-    for i in 0..chars.len().saturating_sub(3) {
-        let four_chars: String = chars[i..i + 4].iter().collect();
-        if let Ok(year) = four_chars.parse::<i32>()
-            && (1800..=2100).contains(&year)
-        {
-            return Some(year);
-        }
-    }
+    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:alaya.db".to_string());
+    let db = Database::new(&database_url).await?;
+    db.run_migrations().await?;
+
+    let owner = library_owner(&db, username).await?;
+    println!("(highlights go to books of {})", owner.username);
+
+    let summary = import_clippings(&db, &clippings, &owner.id)
+        .await
+        .map_err(|e| e.to_string())?;
 
-    None
+    println!("Matched {} existing book(s)", summary.books_matched);
+    println!("Created {} new book(s)", summary.books_created);
+    println!("Imported {} highlight(s)", summary.highlights_imported);
+    println!("Skipped {} already imported", summary.duplicates_skipped);
+
+    Ok(())
 }
 
-struct EpubMetadata {
-    title: Option<String>,
-    author: Option<String>,
-    publisher: Option<String>,
-    date: Option<String>,
-    language: Option<String>,
-    description: Option<String>,
+/// Tag names from the directories a book sits in, relative to the scanned directory,
+/// so that `Fiction/Russian/book.epub` is tagged "Fiction" and "Russian".
+fn directory_tags(filepath: &str) -> Vec<&str> {
+    Path::new(filepath)
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => name.to_str(),
+                    _ => None,
+                })
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
-fn extract_epub_metadata(path: &Path) -> Option<EpubMetadata> {
-    let doc = EpubDoc::new(path).ok()?;
-
-    Some(EpubMetadata {
-        title: doc.mdata("title").map(|m| m.value.clone()),
-        author: doc.mdata("creator").map(|m| m.value.clone()),
-        publisher: doc.mdata("publisher").map(|m| m.value.clone()),
-        date: doc.mdata("date").map(|m| m.value.clone()),
-        language: doc.mdata("language").map(|m| m.value.clone()),
-        description: doc.mdata("description").map(|m| m.value.clone()),
-    })
+fn file_stem_title(path: &Path) -> Option<String> {
+    path.file_stem().and_then(|s| s.to_str()).map(String::from)
 }
 
-fn print_epub_metadata(metadata: &EpubMetadata) {
+fn print_metadata(metadata: &BookMetadata) {
     if let Some(title) = &metadata.title {
         println!("  Title: {}", title);
     }
-    if let Some(author) = &metadata.author {
+    if let Some(author) = metadata.author() {
         println!("  Author: {}", author);
     }
+    if !metadata.translators.is_empty() {
+        println!("  Translator: {}", join_author_names(&metadata.translators));
+    }
+    if !metadata.editors.is_empty() {
+        println!("  Editor: {}", join_author_names(&metadata.editors));
+    }
+    if let Some(series) = &metadata.series {
+        match &metadata.series_index {
+            Some(index) => println!("  Series: {} #{}", series, index),
+            None => println!("  Series: {}", series),
+        }
+    }
     if let Some(publisher) = &metadata.publisher {
         println!("  Publisher: {}", publisher);
     }
@@ -270,10 +373,20 @@ fn print_epub_metadata(metadata: &EpubMetadata) {
     if let Some(language) = &metadata.language {
         println!("  Language: {}", language);
     }
+    if let Some(pages) = metadata.page_count {
+        println!("  Pages: {}", pages);
+    }
+    for identifier in &metadata.identifiers {
+        println!(
+            "  {}: {}",
+            identifier.scheme.to_uppercase(),
+            identifier.value
+        );
+    }
     if let Some(description) = &metadata.description {
         // Truncate long descriptions
-        let desc = if description.len() > 200 {
-            format!("{}...", &description[..200])
+        let desc = if description.chars().count() > 200 {
+            format!("{}...", description.chars().take(200).collect::<String>())
         } else {
             description.clone()
         };
@@ -281,128 +394,47 @@ fn print_epub_metadata(metadata: &EpubMetadata) {
     }
 }
 
-struct PdfMetadata {
-    title: Option<String>,
-    author: Option<String>,
-    subject: Option<String>,
-    creator: Option<String>,
-    producer: Option<String>,
-    creation_date: Option<String>,
-}
-
-fn extract_pdf_metadata(path: &Path) -> Option<PdfMetadata> {
-    let doc = Document::load(path).ok()?;
-
-    // Get the Info dictionary reference from trailer
-    let info_ref = doc.trailer.get(b"Info").ok()?;
-    let info_ref = info_ref.as_reference().ok()?;
-    let info_dict = doc.get_dictionary(info_ref).ok()?;
-
-    Some(PdfMetadata {
-        title: get_pdf_string(&doc, info_dict, b"Title"),
-        author: get_pdf_string(&doc, info_dict, b"Author"),
-        subject: get_pdf_string(&doc, info_dict, b"Subject"),
-        creator: get_pdf_string(&doc, info_dict, b"Creator"),
-        producer: get_pdf_string(&doc, info_dict, b"Producer"),
-        creation_date: get_pdf_string(&doc, info_dict, b"CreationDate"),
-    })
-}
-
-fn get_pdf_string(doc: &Document, dict: &lopdf::Dictionary, key: &[u8]) -> Option<String> {
-    let obj = dict.get(key).ok()?;
-
-    // Handle both direct strings and references
-    match obj {
-        lopdf::Object::String(bytes, _) => {
-            // Try UTF-16 BE first (starts with BOM 0xFE 0xFF)
-            if bytes.len() >= 2 && bytes[0] == 0xFE && bytes[1] == 0xFF {
-                let utf16: Vec<u16> = bytes[2..]
-                    .chunks(2)
-                    .filter_map(|chunk| {
-                        if chunk.len() == 2 {
-                            Some(u16::from_be_bytes([chunk[0], chunk[1]]))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                String::from_utf16(&utf16).ok()
-            } else {
-                // Try as Latin-1/UTF-8
-                Some(String::from_utf8_lossy(bytes).to_string())
-            }
-        }
-        lopdf::Object::Reference(r) => {
-            if let Ok(lopdf::Object::String(bytes, _)) = doc.get_object(*r) {
-                Some(String::from_utf8_lossy(bytes).to_string())
-            } else {
-                None
-            }
-        }
-        _ => None,
-    }
+async fn run_scan(client: &GptClient, title: &str) -> Result<(), GptError> {
+    println!("Scanning \"{title}\"...");
+    let summary = client.summarize_book(title).await?;
+    println!("\nSummary: {summary}");
+    Ok(())
 }
 
-/// Check if a string contains disallowed control characters (Unicode Cc category, except \t \n \r)
-fn is_printable_text(s: &str) -> bool {
-    if s.is_empty() {
-        return false;
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Check for replacement characters (indicates failed UTF-8 decoding)
-    if s.contains('\u{FFFD}') {
-        return false;
+    async fn test_database() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}", dir.path().join("alaya.db").display());
+        let db = Database::new(&url).await.unwrap();
+        db.run_migrations().await.unwrap();
+        (dir, db)
     }
 
-    // Disallowed control characters (Unicode Cc category except \t, \n, \r)
-    for c in s.chars() {
-        match c {
-            '\x00'..='\x08' | '\x0B' | '\x0C' | '\x0E'..='\x1F' | '\x7F' | '\u{80}'..='\u{9F}' => {
-                return false;
-            }
-            _ => {}
-        }
+    #[tokio::test]
+    async fn books_belong_to_the_user_or_the_first_account() {
+        let (_dir, db) = test_database().await;
+        assert!(library_owner(&db, None).await.is_err());
+
+        db.create_user("first", "secret").await.unwrap();
+        db.create_user("second", "secret").await.unwrap();
+        assert_eq!(library_owner(&db, None).await.unwrap().username, "first");
+        assert_eq!(
+            library_owner(&db, Some("second")).await.unwrap().username,
+            "second"
+        );
+        assert!(library_owner(&db, Some("nobody")).await.is_err());
     }
 
-    true
-}
-
-fn print_pdf_metadata(metadata: &PdfMetadata) {
-    if let Some(title) = &metadata.title
-        && is_printable_text(title)
-    {
-        println!("  Title: {}", title);
-    }
-    if let Some(author) = &metadata.author
-        && is_printable_text(author)
-    {
-        println!("  Author: {}", author);
-    }
-    if let Some(subject) = &metadata.subject
-        && is_printable_text(subject)
-    {
-        println!("  Subject: {}", subject);
-    }
-    if let Some(creator) = &metadata.creator
-        && is_printable_text(creator)
-    {
-        println!("  Creator: {}", creator);
-    }
-    if let Some(producer) = &metadata.producer
-        && is_printable_text(producer)
-    {
-        println!("  Producer: {}", producer);
-    }
-    if let Some(date) = &metadata.creation_date
-        && is_printable_text(date)
-    {
-        println!("  Created: {}", date);
+    #[test]
+    fn directories_become_tags() {
+        assert_eq!(
+            directory_tags("Fiction/Russian/book.epub"),
+            ["Fiction", "Russian"]
+        );
+        assert_eq!(directory_tags("./Poetry/ /book.pdf"), ["Poetry"]);
+        assert!(directory_tags("book.epub").is_empty());
     }
 }
-
-async fn run_scan(client: &GptClient, title: &str) -> Result<(), GptError> {
-    println!("Scanning \"{title}\"...");
-    let summary = client.summarize_book(title).await?;
-    println!("\nSummary: {summary}");
-    Ok(())
-}
@@ -1,5 +1,14 @@
+use alaya::backup::{create_backup, restore_backup, scheduled_backup};
+use alaya::export::{
+    ConflictPolicy, ExportFormat, RestoreAs, build_export, parse_export, render_export,
+    restore_library,
+};
 use alaya::{Database, create_app};
-use std::{env, sync::Arc};
+use std::{env, path::Path, path::PathBuf, sync::Arc, time::Duration};
+
+const SESSION_CLEANUP_INTERVAL_SECS: u64 = 60 * 60;
+const DEFAULT_BACKUP_EVERY_HOURS: u64 = 24;
+const DEFAULT_BACKUP_KEEP: usize = 7;
 
 #[tokio::main]
 async fn main() {
@@ -10,17 +19,43 @@ async fn main() {
     let mut port = 3000;
     let mut database_path = "./alaya.db".to_string();
     let mut serve = false;
+    let mut backup_dir: Option<PathBuf> = None;
+    let mut backup_every_hours = DEFAULT_BACKUP_EVERY_HOURS;
+    let mut backup_keep = DEFAULT_BACKUP_KEEP;
 
     if args.len() == 1 {
         println!("alaya book abode");
         println!();
         println!("Usage:");
         println!("  {} --serve [OPTIONS]", args[0]);
+        println!(
+            "  {} export <json|csv|markdown> [--output <PATH>] [--database <PATH>]",
+            args[0]
+        );
+        println!("  {} migrate <status|up|down> [--database <PATH>]", args[0]);
+        println!("  {} backup <PATH> [--database <PATH>]", args[0]);
+        println!(
+            "  {} restore <PATH> [--database <PATH>]   (with the server stopped)",
+            args[0]
+        );
+        println!(
+            "  {} import <FILE.json> [--policy skip|overwrite|newest] [--user <NAME>] [--database <PATH>]",
+            args[0]
+        );
         println!();
         println!("Options:");
         println!("  --serve                    Start the alaya server");
         println!("  --port <PORT>             Port to bind to (default: 3000)");
         println!("  --database <PATH>         Database file path (default: ./alaya.db)");
+        println!("  --backup-dir <DIR>        Back up the database into DIR while serving");
+        println!(
+            "  --backup-every <HOURS>    Hours between backups (default: {})",
+            DEFAULT_BACKUP_EVERY_HOURS
+        );
+        println!(
+            "  --backup-keep <COUNT>     Backups to keep in DIR (default: {})",
+            DEFAULT_BACKUP_KEEP
+        );
         println!();
         println!("Example:");
         println!(
@@ -31,6 +66,26 @@ async fn main() {
         return;
     }
 
+    if args[1] == "export" {
+        export(&args).await;
+        return;
+    }
+
+    if args[1] == "import" {
+        import(&args).await;
+        return;
+    }
+
+    if args[1] == "migrate" {
+        migrate(&args).await;
+        return;
+    }
+
+    if args[1] == "backup" || args[1] == "restore" {
+        backup_or_restore(&args).await;
+        return;
+    }
+
     // Parse arguments
     let mut i = 1;
     while i < args.len() {
@@ -62,6 +117,32 @@ async fn main() {
                     std::process::exit(1);
                 }
             }
+            "--backup-dir" => {
+                if i + 1 < args.len() {
+                    backup_dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                } else {
+                    eprintln!("Error: --backup-dir requires a value");
+                    std::process::exit(1);
+                }
+            }
+            "--backup-every" | "--backup-keep" => {
+                let value = args.get(i + 1).and_then(|v| v.parse::<usize>().ok());
+                match value {
+                    Some(value) if value > 0 => {
+                        if args[i] == "--backup-every" {
+                            backup_every_hours = value as u64;
+                        } else {
+                            backup_keep = value;
+                        }
+                        i += 1;
+                    }
+                    _ => {
+                        eprintln!("Error: {} requires a positive number", args[i]);
+                        std::process::exit(1);
+                    }
+                }
+            }
             _ => {
                 eprintln!("Error: Unknown argument: {}", args[i]);
                 println!("Use '{}' to see usage information.", args[0]);
@@ -96,6 +177,37 @@ async fn main() {
 
     let app_state = Arc::new(db);
 
+    // Periodically drop expired sessions
+    let cleanup_db = app_state.clone();
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(SESSION_CLEANUP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match cleanup_db.delete_expired_sessions().await {
+                Ok(0) => {}
+                Ok(count) => println!("Removed {} expired session(s)", count),
+                Err(e) => eprintln!("Failed to remove expired sessions: {}", e),
+            }
+        }
+    });
+
+    // Periodically back up the database, keeping the newest few
+    if let Some(backup_dir) = backup_dir {
+        let backup_db = app_state.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(backup_every_hours * 60 * 60));
+            loop {
+                interval.tick().await;
+                match scheduled_backup(&backup_db, &backup_dir, backup_keep).await {
+                    Ok(path) => println!("Backed up database to {}", path.display()),
+                    Err(e) => eprintln!("Failed to back up database: {}", e),
+                }
+            }
+        });
+    }
+
     // Build the router using the shared function
     let app = create_app(app_state);
 
@@ -111,3 +223,335 @@ async fn main() {
         .await
         .expect("Failed to start server");
 }
+
+/// Write the whole library to a file, named after today's date without `--output`.
+async fn export(args: &[String]) {
+    let mut format = None;
+    let mut output = None;
+    let mut database_path = "./alaya.db".to_string();
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--output" | "--database" => {
+                let Some(value) = args.get(i + 1) else {
+                    eprintln!("Error: {} requires a value", args[i]);
+                    std::process::exit(1);
+                };
+                if args[i] == "--output" {
+                    output = Some(value.clone());
+                } else {
+                    database_path = value.clone();
+                }
+                i += 1;
+            }
+            value if format.is_none() => match ExportFormat::parse(value) {
+                Some(f) => format = Some(f),
+                None => {
+                    eprintln!("Error: Unknown export format: {}", value);
+                    std::process::exit(1);
+                }
+            },
+            _ => {
+                eprintln!("Error: Unknown argument: {}", args[i]);
+                println!("Use '{}' to see usage information.", args[0]);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let Some(format) = format else {
+        eprintln!("Error: export requires a format: json, csv or markdown");
+        std::process::exit(1);
+    };
+
+    let db = open_read_only(&database_path).await;
+
+    let export = match build_export(&db, None).await {
+        Ok(export) => export,
+        Err(e) => {
+            eprintln!("Failed to export library: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let book_count = export.books.len();
+
+    let output = output.unwrap_or_else(|| {
+        format!(
+            "alaya-{}.{}",
+            chrono::Utc::now().format("%Y-%m-%d"),
+            format.extension()
+        )
+    });
+
+    match render_export(&export, format).and_then(|body| Ok(std::fs::write(&output, body)?)) {
+        Ok(()) => println!("Exported {} book(s) to {}", book_count, output),
+        Err(e) => {
+            eprintln!("Failed to write export: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Restore a JSON export. Owners and readers are matched by username; `--user`
+/// takes the books of owners without an account here.
+async fn import(args: &[String]) {
+    let mut input = None;
+    let mut policy = ConflictPolicy::Skip;
+    let mut fallback_username = None;
+    let mut database_path = "./alaya.db".to_string();
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--policy" | "--user" | "--database" => {
+                let Some(value) = args.get(i + 1) else {
+                    eprintln!("Error: {} requires a value", args[i]);
+                    std::process::exit(1);
+                };
+                match args[i].as_str() {
+                    "--policy" => match ConflictPolicy::parse(value) {
+                        Some(p) => policy = p,
+                        None => {
+                            eprintln!("Error: Unknown conflict policy: {}", value);
+                            std::process::exit(1);
+                        }
+                    },
+                    "--user" => fallback_username = Some(value.clone()),
+                    _ => database_path = value.clone(),
+                }
+                i += 1;
+            }
+            value if input.is_none() => input = Some(value.to_string()),
+            _ => {
+                eprintln!("Error: Unknown argument: {}", args[i]);
+                println!("Use '{}' to see usage information.", args[0]);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let Some(input) = input else {
+        eprintln!("Error: import requires a JSON file");
+        std::process::exit(1);
+    };
+
+    let export = match std::fs::read_to_string(&input)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| parse_export(&contents))
+    {
+        Ok(export) => export,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", input, e);
+            std::process::exit(1);
+        }
+    };
+
+    let db = open_database(&database_path).await;
+
+    let fallback = match &fallback_username {
+        Some(username) => match db.get_user_by_username(username).await {
+            Ok(Some(user)) => Some(user),
+            Ok(None) => {
+                eprintln!("Error: User not found: {}", username);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Failed to look up user: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let restore_as = RestoreAs::Admin {
+        fallback: fallback.as_ref(),
+    };
+    match restore_library(&db, &export, policy, restore_as).await {
+        Ok(summary) => println!(
+            "Created {} book(s), updated {}, skipped {}; restored {} highlight(s) and {} reading(s)",
+            summary.created, summary.updated, summary.skipped, summary.highlights, summary.readings
+        ),
+        Err(e) => {
+            eprintln!("Failed to import {}: {}", input, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// List, apply or revert database migrations.
+async fn migrate(args: &[String]) {
+    let mut command = None;
+    let mut database_path = "./alaya.db".to_string();
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--database" => {
+                let Some(value) = args.get(i + 1) else {
+                    eprintln!("Error: --database requires a value");
+                    std::process::exit(1);
+                };
+                database_path = value.clone();
+                i += 1;
+            }
+            value @ ("status" | "up" | "down") if command.is_none() => {
+                command = Some(value.to_string())
+            }
+            _ => {
+                eprintln!("Error: Unknown argument: {}", args[i]);
+                println!("Use '{}' to see usage information.", args[0]);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let Some(command) = command else {
+        eprintln!("Error: migrate requires a command: status, up or down");
+        std::process::exit(1);
+    };
+
+    let database_url = format!("sqlite:{}", database_path);
+    let db = Database::new(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    match command.as_str() {
+        "status" => match db.migration_status().await {
+            Ok(migrations) => {
+                for migration in migrations {
+                    let state = match (&migration.applied_at, migration.modified) {
+                        (Some(_), true) => "modified since applied".to_string(),
+                        (Some(applied_at), false) => format!("applied {}", applied_at),
+                        (None, _) => "pending".to_string(),
+                    };
+                    println!(
+                        "{:03}  {:<32} {}",
+                        migration.version, migration.description, state
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to read migrations: {}", e);
+                std::process::exit(1);
+            }
+        },
+        "up" => {
+            if let Err(e) = db.run_migrations().await {
+                eprintln!("Failed to run migrations: {}", e);
+                std::process::exit(1);
+            }
+            println!("Database is up to date");
+        }
+        _ => match db.revert_last_migration().await {
+            Ok(Some(migration)) => println!(
+                "Reverted {:03} {}",
+                migration.version, migration.description
+            ),
+            Ok(None) => println!("No migrations to revert"),
+            Err(e) => {
+                eprintln!("Failed to revert migration: {}", e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Copy the database to a file, or swap a checked backup in for it.
+async fn backup_or_restore(args: &[String]) {
+    let mut path = None;
+    let mut database_path = "./alaya.db".to_string();
+
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--database" => {
+                let Some(value) = args.get(i + 1) else {
+                    eprintln!("Error: --database requires a value");
+                    std::process::exit(1);
+                };
+                database_path = value.clone();
+                i += 1;
+            }
+            value if path.is_none() => path = Some(PathBuf::from(value)),
+            _ => {
+                eprintln!("Error: Unknown argument: {}", args[i]);
+                println!("Use '{}' to see usage information.", args[0]);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let Some(path) = path else {
+        eprintln!("Error: {} requires a file path", args[1]);
+        std::process::exit(1);
+    };
+
+    if args[1] == "backup" {
+        let db = open_read_only(&database_path).await;
+        match create_backup(&db, &path).await {
+            Ok(()) => println!("Backed up {} to {}", database_path, path.display()),
+            Err(e) => {
+                eprintln!("Failed to back up database: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    match restore_backup(&path, Path::new(&database_path)).await {
+        Ok((info, previous)) => {
+            println!(
+                "Restored {} ({} user(s), {} book(s), {} migration(s)) to {}",
+                path.display(),
+                info.users,
+                info.books,
+                info.migrations,
+                database_path
+            );
+            if let Some(previous) = previous {
+                println!("The replaced database was kept as {}", previous.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to restore {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Open the database and bring it up to date, for commands that write to it.
+async fn open_database(database_path: &str) -> Database {
+    let database_url = format!("sqlite:{}", database_path);
+    let db = Database::new(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    if let Err(e) = db.run_migrations().await {
+        eprintln!("Failed to run migrations: {}", e);
+        std::process::exit(1);
+    }
+
+    db
+}
+
+/// Open the database without changing it, for commands that only read it while
+/// a server, perhaps of another version, may be using it.
+async fn open_read_only(database_path: &str) -> Database {
+    if !Path::new(database_path).exists() {
+        eprintln!("Error: Database not found: {}", database_path);
+        std::process::exit(1);
+    }
+
+    match Database::open_read_only(Path::new(database_path)).await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,18 +1,24 @@
 use askama::Template;
 use axum::{
     extract::{Form, Path, State},
-    http::{HeaderMap, StatusCode, header},
+    http::{HeaderMap, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
 };
 use serde::{Deserialize, Serialize};
-use std::env;
 
 use crate::AppState;
-use crate::auth::{current_user, signups_disabled};
+use crate::auth::{User, current_user, signups_disabled};
+use crate::authors::AuthorRole;
+use crate::covers::remove_cover;
+use crate::database::{Database, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::files::{book_file_response, library_file};
 use crate::gpt::{GptClient, GptConfig};
+use crate::markdown::render_notes;
+use crate::metadata::{BookMetadata, Identifier, join_author_names, split_author_names};
+use crate::readings::ReadingStatus;
 use crate::templates::{
     BookDetailTemplate, BookEditChatTemplate, BookEditNotesTemplate, BookEditTemplate,
-    BookFormTemplate, BookListTemplate, QuickAddTemplate,
+    BookFormTemplate, BookListTemplate, BookSearchTemplate, QuickAddTemplate, TagListTemplate,
 };
 
 // Book-related structures
@@ -22,9 +28,16 @@ pub struct Book {
     pub title: String,
     pub author: Option<String>,
     pub publication_year: Option<i32>,
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+    pub description: Option<String>,
+    pub page_count: Option<i64>,
     pub filepath: Option<String>,
     pub notes: Option<String>,
+    pub user_id: Option<String>,
+    pub visibility: BookVisibility,
     pub created_at: String,
+    pub updated_at: String,
 }
 
 impl Book {
@@ -34,6 +47,143 @@ impl Book {
             .next()
             .unwrap_or(&self.created_at)
     }
+
+    /// Only owners may change their books. Books without an owner cannot be
+    /// changed until `alayascan --user` rescans them.
+    pub fn can_edit(&self, user_id: &str) -> bool {
+        self.user_id.as_deref() == Some(user_id)
+    }
+}
+
+/// What is known about a book beyond its title, author and year, as read from
+/// its file by alayascan or filled in by hand.
+#[derive(Default, Debug)]
+pub struct BookDetails {
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+    pub description: Option<String>,
+    pub page_count: Option<i64>,
+}
+
+impl BookDetails {
+    pub fn from_metadata(metadata: &BookMetadata) -> Self {
+        BookDetails {
+            publisher: metadata.publisher.clone(),
+            language: metadata.language.clone(),
+            description: metadata.description.clone(),
+            page_count: metadata.page_count.map(i64::from),
+        }
+    }
+}
+
+/// Who besides the owner can see a book.
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum BookVisibility {
+    #[default]
+    Private,
+    Shared,
+    Public,
+}
+
+impl BookVisibility {
+    pub const ALL: [BookVisibility; 3] = [
+        BookVisibility::Private,
+        BookVisibility::Shared,
+        BookVisibility::Public,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|visibility| visibility.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookVisibility::Private => "private",
+            BookVisibility::Shared => "shared",
+            BookVisibility::Public => "public",
+        }
+    }
+}
+
+pub enum BookAccessError {
+    NotFound,
+    Forbidden,
+    Database(sqlx::Error),
+}
+
+/// Load a book the user may change. Books hidden from the user look missing,
+/// visible books owned by someone else are forbidden.
+pub async fn editable_book(
+    db: &Database,
+    book_id: &str,
+    user: &User,
+) -> Result<Book, BookAccessError> {
+    match db.get_visible_book(book_id, Some(&user.id)).await {
+        Ok(Some(book)) if book.can_edit(&user.id) => Ok(book),
+        Ok(Some(_)) => Err(BookAccessError::Forbidden),
+        Ok(None) => Err(BookAccessError::NotFound),
+        Err(error) => Err(BookAccessError::Database(error)),
+    }
+}
+
+pub(crate) fn access_error_response(error: BookAccessError) -> Response {
+    match error {
+        BookAccessError::NotFound => Redirect::to("/").into_response(),
+        BookAccessError::Forbidden => {
+            (StatusCode::FORBIDDEN, "You can only change your own books").into_response()
+        }
+        BookAccessError::Database(error) => {
+            eprintln!("Error fetching book: {error}");
+            Redirect::to("/").into_response()
+        }
+    }
+}
+
+/// A book matched by full-text search, with highlighted fields.
+pub struct SearchResult {
+    pub book: Book,
+    pub title_highlight: String,
+    pub author_highlight: String,
+    pub notes_snippet: String,
+}
+
+impl SearchResult {
+    pub fn title_html(&self) -> String {
+        highlight_to_html(&self.title_highlight)
+    }
+
+    pub fn author_html(&self) -> String {
+        highlight_to_html(&self.author_highlight)
+    }
+
+    pub fn notes_html(&self) -> String {
+        highlight_to_html(&self.notes_snippet)
+    }
+
+    pub fn notes_matched(&self) -> bool {
+        self.notes_snippet.contains(HIGHLIGHT_START)
+    }
+}
+
+/// Escape a highlighted string and turn its match markers into `<mark>` tags.
+fn highlight_to_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            _ => html.push(c),
+        }
+    }
+    html.replace(HIGHLIGHT_START, "<mark>")
+        .replace(HIGHLIGHT_END, "</mark>")
 }
 
 #[derive(Deserialize)]
@@ -41,7 +191,10 @@ pub struct CreateBookForm {
     pub title: String,
     pub author: String,
     pub publication_year: String,
+    #[serde(flatten)]
+    pub details: BookDetailsForm,
     pub notes: String,
+    pub visibility: String,
 }
 
 #[derive(Deserialize)]
@@ -55,6 +208,101 @@ pub struct EditBookForm {
     pub title: String,
     pub author: String,
     pub publication_year: String,
+    #[serde(flatten)]
+    pub details: BookDetailsForm,
+    #[serde(default)]
+    pub translators: String,
+    #[serde(default)]
+    pub editors: String,
+    pub tags: String,
+}
+
+/// The publisher, language, description, page count and identifier fields
+/// shared by the new and edit book forms.
+#[derive(Deserialize, Default)]
+pub struct BookDetailsForm {
+    #[serde(default)]
+    pub publisher: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub page_count: String,
+    #[serde(default)]
+    pub identifiers: String,
+}
+
+impl BookDetailsForm {
+    pub fn details(&self) -> BookDetails {
+        let optional = |value: &str| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        BookDetails {
+            publisher: optional(&self.publisher),
+            language: optional(&self.language),
+            description: optional(&self.description),
+            page_count: self
+                .page_count
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|pages| *pages > 0),
+        }
+    }
+
+    pub fn identifiers(&self) -> Vec<Identifier> {
+        parse_identifiers(&self.identifiers)
+    }
+}
+
+/// A tag with the number of books it is attached to.
+pub struct TagCount {
+    pub name: String,
+    pub count: i64,
+}
+
+/// Split a comma separated tag field into trimmed, non-empty names.
+pub fn parse_tags(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Parse comma separated identifiers such as `isbn:9780099573586, asin:B00ABCDEFG`.
+/// Values without a scheme are taken as ISBNs when they look like one.
+pub fn parse_identifiers(value: &str) -> Vec<Identifier> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((scheme, value))
+                if !scheme.eq_ignore_ascii_case("urn")
+                    && !scheme.is_empty()
+                    && scheme.chars().all(|c| c.is_ascii_alphanumeric()) =>
+            {
+                Identifier::new(scheme, value)
+            }
+            _ => Identifier::guess(entry, None),
+        })
+        .filter(|identifier| !identifier.value.is_empty())
+        .collect()
+}
+
+/// Identifiers as written in the edit form.
+pub fn format_identifiers(identifiers: &[Identifier]) -> String {
+    identifiers
+        .iter()
+        .map(|identifier| format!("{}:{}", identifier.scheme, identifier.value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Deserialize)]
+pub struct SharingForm {
+    pub visibility: String,
+    pub shared_with: String,
 }
 
 #[derive(Deserialize)]
@@ -78,32 +326,298 @@ pub struct EditChatApplyForm {
 #[derive(Deserialize)]
 pub struct BookListQuery {
     pub notes: Option<String>,
+    pub mine: Option<String>,
+    pub tag: Option<String>,
+    pub status: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+}
+
+const BOOKS_PER_PAGE: i64 = 50;
+
+/// Column the book list is ordered by.
+#[derive(Clone, Copy, PartialEq)]
+pub enum BookSort {
+    Title,
+    Author,
+    PublicationYear,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl BookSort {
+    pub const ALL: [BookSort; 5] = [
+        BookSort::Title,
+        BookSort::Author,
+        BookSort::PublicationYear,
+        BookSort::CreatedAt,
+        BookSort::UpdatedAt,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookSort::Title => "title",
+            BookSort::Author => "author",
+            BookSort::PublicationYear => "publication_year",
+            BookSort::CreatedAt => "created_at",
+            BookSort::UpdatedAt => "updated_at",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BookSort::Title => "title",
+            BookSort::Author => "author",
+            BookSort::PublicationYear => "pub year",
+            BookSort::CreatedAt => "added",
+            BookSort::UpdatedAt => "updated",
+        }
+    }
+
+    /// SQL expression used both for ordering and for keyset comparisons.
+    /// Never NULL, so row-value comparisons against a cursor stay well defined.
+    pub fn sql_expr(&self) -> &'static str {
+        match self {
+            BookSort::Title => "title COLLATE NOCASE",
+            BookSort::Author => "COALESCE(author, '') COLLATE NOCASE",
+            BookSort::PublicationYear => "COALESCE(publication_year, 0)",
+            BookSort::CreatedAt => "created_at",
+            BookSort::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+/// Position in the book list, given as the id of a book on a neighbouring page.
+pub enum PageCursor {
+    After(String),
+    Before(String),
+}
+
+pub struct BookPageRequest {
+    pub viewer_id: Option<String>,
+    pub sort: BookSort,
+    pub descending: bool,
+    pub owned_only: bool,
+    pub notes_only: bool,
+    pub tag: Option<String>,
+    pub status: Option<ReadingStatus>,
+    pub cursor: Option<PageCursor>,
+    pub limit: i64,
 }
 
+pub struct BookPage {
+    pub books: Vec<Book>,
+    pub has_prev: bool,
+    pub has_next: bool,
+}
+
+/// Filter and sort settings of the book list, carried across its links.
+#[derive(Clone)]
+pub struct BookListParams {
+    pub notes: bool,
+    pub mine: bool,
+    pub tag: Option<String>,
+    pub status: Option<ReadingStatus>,
+    pub sort: BookSort,
+    pub descending: bool,
+}
+
+impl BookListParams {
+    /// Build a `/` URL for these settings, optionally with a page cursor.
+    pub fn url(&self, cursor: Option<(&str, &str)>) -> String {
+        let mut params = Vec::new();
+        if self.notes {
+            params.push(("notes", "true"));
+        }
+        if self.mine {
+            params.push(("mine", "true"));
+        }
+        if let Some(tag) = &self.tag {
+            params.push(("tag", tag));
+        }
+        if let Some(status) = self.status {
+            params.push(("status", status.as_str()));
+        }
+        params.push(("sort", self.sort.as_str()));
+        params.push(("order", if self.descending { "desc" } else { "asc" }));
+        if let Some(cursor) = cursor {
+            params.push(cursor);
+        }
+        format!(
+            "/?{}",
+            serde_urlencoded::to_string(&params).unwrap_or_default()
+        )
+    }
+
+    fn filtered(&self, notes: bool, mine: bool) -> String {
+        BookListParams {
+            notes,
+            mine,
+            ..self.clone()
+        }
+        .url(None)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+}
+
+const SEARCH_RESULT_LIMIT: i64 = 100;
+
 pub async fn book_list(
     State(db): State<AppState>,
     headers: HeaderMap,
     axum::extract::Query(query): axum::extract::Query<BookListQuery>,
 ) -> impl IntoResponse {
     let user = current_user(&db, &headers).await;
-    let all_books = db.get_all_books().await.unwrap_or_default();
 
-    let notes = query.notes.as_deref() == Some("true");
-    let books = if notes {
-        all_books
-            .into_iter()
-            .filter(|b| b.notes.is_some())
-            .collect()
-    } else {
-        all_books
+    let sort = query
+        .sort
+        .as_deref()
+        .and_then(BookSort::parse)
+        .unwrap_or(BookSort::CreatedAt);
+    let params = BookListParams {
+        notes: query.notes.as_deref() == Some("true"),
+        mine: user.is_some() && query.mine.as_deref() == Some("true"),
+        tag: query
+            .tag
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty()),
+        status: query
+            .status
+            .as_deref()
+            .and_then(ReadingStatus::parse)
+            .filter(|_| user.is_some()),
+        sort,
+        descending: match query.order.as_deref() {
+            Some("asc") => false,
+            Some("desc") => true,
+            // Dates read best newest first, everything else alphabetically
+            _ => matches!(sort, BookSort::CreatedAt | BookSort::UpdatedAt),
+        },
+    };
+    let cursor = match (query.after, query.before) {
+        (Some(id), _) => Some(PageCursor::After(id)),
+        (None, Some(id)) => Some(PageCursor::Before(id)),
+        (None, None) => None,
+    };
+
+    let request = BookPageRequest {
+        viewer_id: user.as_ref().map(|u| u.id.clone()),
+        sort: params.sort,
+        descending: params.descending,
+        owned_only: params.mine,
+        notes_only: params.notes,
+        tag: params.tag.clone(),
+        status: params.status,
+        cursor,
+        limit: BOOKS_PER_PAGE,
+    };
+
+    let page = db.get_books_page(&request).await.unwrap_or_else(|error| {
+        eprintln!("Error fetching books: {error}");
+        BookPage {
+            books: Vec::new(),
+            has_prev: false,
+            has_next: false,
+        }
+    });
+
+    let prev_url = match (page.has_prev, page.books.first()) {
+        (true, Some(book)) => Some(params.url(Some(("before", &book.id)))),
+        _ => None,
+    };
+    let next_url = match (page.has_next, page.books.last()) {
+        (true, Some(book)) => Some(params.url(Some(("after", &book.id)))),
+        _ => None,
     };
 
     let template = BookListTemplate {
         is_authenticated: user.is_some(),
         signups_disabled: signups_disabled(),
         username: user.map(|u| u.username).unwrap_or_default(),
-        books,
-        notes,
+        books: page.books,
+        notes: params.notes,
+        mine: params.mine,
+        status: params
+            .status
+            .map(|status| status.as_str())
+            .unwrap_or_default(),
+        sort: params.sort.as_str().to_string(),
+        descending: params.descending,
+        sort_options: BookSort::ALL
+            .iter()
+            .map(|sort| (sort.as_str(), sort.label()))
+            .collect(),
+        all_url: params.filtered(false, false),
+        notes_url: params.filtered(true, false),
+        mine_url: params.filtered(false, true),
+        tag: params.tag.clone(),
+        untagged_url: BookListParams {
+            tag: None,
+            ..params.clone()
+        }
+        .url(None),
+        prev_url,
+        next_url,
+    };
+
+    Html(template.render().unwrap())
+}
+
+pub async fn tag_list(State(db): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let tags = db.get_tag_counts(viewer_id).await.unwrap_or_else(|error| {
+        eprintln!("Error fetching tags: {error}");
+        Vec::new()
+    });
+
+    let template = TagListTemplate {
+        is_authenticated: user.is_some(),
+        signups_disabled: signups_disabled(),
+        username: user.map(|u| u.username).unwrap_or_default(),
+        tags,
+    };
+
+    Html(template.render().unwrap())
+}
+
+pub async fn book_search(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<SearchQuery>,
+) -> impl IntoResponse {
+    let user = current_user(&db, &headers).await;
+    let query = query.q.unwrap_or_default().trim().to_string();
+
+    let results = if query.is_empty() {
+        Vec::new()
+    } else {
+        let viewer_id = user.as_ref().map(|u| u.id.as_str());
+        db.search_books(&query, viewer_id, SEARCH_RESULT_LIMIT)
+            .await
+            .unwrap_or_else(|error| {
+                eprintln!("Search error: {error}");
+                Vec::new()
+            })
+    };
+
+    let template = BookSearchTemplate {
+        is_authenticated: user.is_some(),
+        signups_disabled: signups_disabled(),
+        username: user.map(|u| u.username).unwrap_or_default(),
+        query,
+        results,
     };
 
     Html(template.render().unwrap())
@@ -162,7 +676,22 @@ pub async fn book_create(
         Some(form.notes.trim())
     };
 
-    match db.create_book(title, author, publication_year, notes).await {
+    let visibility = BookVisibility::parse(&form.visibility).unwrap_or_default();
+
+    let result = db
+        .create_book(
+            title,
+            author,
+            publication_year,
+            notes,
+            Some(&user.id),
+            visibility,
+            &form.details.details(),
+            &form.details.identifiers(),
+        )
+        .await;
+
+    match result {
         Ok(_) => Redirect::to("/").into_response(),
         Err(error) => {
             eprintln!("Book creation error: {error}");
@@ -183,14 +712,43 @@ pub async fn book_detail(
     Path(book_id): Path<String>,
 ) -> Response {
     let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
 
-    match db.get_book_by_id(&book_id).await {
+    match db.get_visible_book(&book_id, viewer_id).await {
         Ok(Some(book)) => {
+            let can_edit = viewer_id.is_some_and(|id| book.can_edit(id));
+            let owner = match &book.user_id {
+                Some(owner_id) => db.get_username(owner_id).await.ok().flatten(),
+                None => None,
+            };
+            let authors = db.get_book_authors(&book.id).await.unwrap_or_default();
+            let tags = db.get_book_tags(&book.id).await.unwrap_or_default();
+            let identifiers = db.get_book_identifiers(&book.id).await.unwrap_or_default();
+            let notes_html = match &book.notes {
+                Some(notes) => Some(render_notes(&db, notes, viewer_id).await),
+                None => None,
+            };
+            let highlights = db.get_highlights(&book.id).await.unwrap_or_default();
+            let readings = match &user {
+                Some(user) => db
+                    .get_readings(&user.id, &book.id)
+                    .await
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
             let template = BookDetailTemplate {
                 is_authenticated: user.is_some(),
                 signups_disabled: signups_disabled(),
                 username: user.map(|u| u.username).unwrap_or_default(),
                 book,
+                authors,
+                tags,
+                identifiers,
+                notes_html,
+                highlights,
+                readings,
+                owner,
+                can_edit,
             };
             Html(template.render().unwrap()).into_response()
         }
@@ -209,12 +767,19 @@ pub async fn book_delete(
 ) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
     }
 
     match db.delete_book(&book_id).await {
-        Ok(_) => Redirect::to("/").into_response(),
+        Ok(_) => {
+            remove_cover(&book_id);
+            Redirect::to("/").into_response()
+        }
         Err(error) => {
             eprintln!("Error deleting book: {error}");
             (StatusCode::INTERNAL_SERVER_ERROR, "Could not delete book").into_response()
@@ -222,8 +787,15 @@ pub async fn book_delete(
     }
 }
 
-pub async fn book_download(State(db): State<AppState>, Path(book_id): Path<String>) -> Response {
-    let book = match db.get_book_by_id(&book_id).await {
+pub async fn book_download(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let book = match db.get_visible_book(&book_id, viewer_id).await {
         Ok(Some(book)) => book,
         Ok(None) => {
             return (StatusCode::NOT_FOUND, "Book not found").into_response();
@@ -238,48 +810,11 @@ pub async fn book_download(State(db): State<AppState>, Path(book_id): Path<Strin
         return (StatusCode::NOT_FOUND, "No file associated with this book").into_response();
     };
 
-    // Get library path from environment, default to current directory
-    let library_path = env::var("LIBRARY_PATH").unwrap_or_else(|_| ".".to_string());
-    let full_path = std::path::Path::new(&library_path).join(filepath);
-
-    if !full_path.exists() {
-        eprintln!("File not found: {}", full_path.display());
+    let Some(full_path) = library_file(filepath) else {
         return (StatusCode::NOT_FOUND, "File not found on disk").into_response();
-    }
-
-    let file_contents = match std::fs::read(&full_path) {
-        Ok(contents) => contents,
-        Err(error) => {
-            eprintln!("Error reading file: {error}");
-            return (StatusCode::INTERNAL_SERVER_ERROR, "Could not read file").into_response();
-        }
-    };
-
-    // Determine content type based on extension
-    let content_type = match full_path.extension().and_then(|e| e.to_str()) {
-        Some("pdf") => "application/pdf",
-        Some("epub") => "application/epub+zip",
-        Some("mobi") => "application/x-mobipocket-ebook",
-        Some("txt") => "text/plain",
-        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
-        _ => "application/octet-stream",
     };
 
-    // Get filename for Content-Disposition header
-    let filename = full_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("download");
-
-    let headers = [
-        (header::CONTENT_TYPE, content_type),
-        (
-            header::CONTENT_DISPOSITION,
-            &format!("attachment; filename=\"{}\"", filename),
-        ),
-    ];
-
-    (headers, file_contents).into_response()
+    book_file_response(&full_path, "attachment", &headers).await
 }
 
 pub async fn quick_add_page(State(db): State<AppState>, headers: HeaderMap) -> Response {
@@ -352,9 +887,13 @@ pub async fn quick_add_submit(
     match db
         .create_book(
             &metadata.title,
-            metadata.author.as_deref(),
+            metadata.author().as_deref(),
             metadata.publication_year,
             None,
+            Some(&user.id),
+            BookVisibility::default(),
+            &BookDetails::default(),
+            &[],
         )
         .await
     {
@@ -379,29 +918,61 @@ pub async fn book_edit_page(
 ) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
-    }
+    };
 
-    match db.get_book_by_id(&book_id).await {
-        Ok(Some(book)) => {
-            let template = BookEditTemplate {
-                is_authenticated: true,
-                signups_disabled: signups_disabled(),
-                username: user.map(|u| u.username).unwrap_or_default(),
-                book,
-                error_message: None,
-            };
-            Html(template.render().unwrap()).into_response()
-        }
-        Ok(None) => Redirect::to("/").into_response(),
-        Err(error) => {
-            eprintln!("Error fetching book: {error}");
-            Redirect::to("/").into_response()
-        }
+    match editable_book(&db, &book_id, &user).await {
+        Ok(book) => render_book_edit(&db, user.username, book, None).await,
+        Err(error) => access_error_response(error),
     }
 }
 
+async fn render_book_edit(
+    db: &Database,
+    username: String,
+    book: Book,
+    error_message: Option<String>,
+) -> Response {
+    let shared_with = db
+        .get_book_share_usernames(&book.id)
+        .await
+        .unwrap_or_default()
+        .join(", ");
+    let tags = db
+        .get_book_tags(&book.id)
+        .await
+        .unwrap_or_default()
+        .join(", ");
+    let identifiers =
+        format_identifiers(&db.get_book_identifiers(&book.id).await.unwrap_or_default());
+    let credited = db.get_book_authors(&book.id).await.unwrap_or_default();
+    let names = |role: AuthorRole| {
+        let names: Vec<&str> = credited
+            .iter()
+            .filter(|author| author.role == role)
+            .map(|author| author.name.as_str())
+            .collect();
+        join_author_names(&names)
+    };
+    let translators = names(AuthorRole::Translator);
+    let editors = names(AuthorRole::Editor);
+
+    let template = BookEditTemplate {
+        is_authenticated: true,
+        signups_disabled: signups_disabled(),
+        username,
+        book,
+        tags,
+        identifiers,
+        translators,
+        editors,
+        shared_with,
+        error_message,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
 pub async fn book_edit_submit(
     State(db): State<AppState>,
     headers: HeaderMap,
@@ -414,19 +985,20 @@ pub async fn book_edit_submit(
         return Redirect::to("/login").into_response();
     };
 
+    let book = match editable_book(&db, &book_id, &user).await {
+        Ok(book) => book,
+        Err(error) => return access_error_response(error),
+    };
+
     let title = form.title.trim();
     if title.is_empty() {
-        if let Ok(Some(book)) = db.get_book_by_id(&book_id).await {
-            let template = BookEditTemplate {
-                is_authenticated: true,
-                signups_disabled: signups_disabled(),
-                username: user.username,
-                book,
-                error_message: Some("Title is required".to_string()),
-            };
-            return Html(template.render().unwrap()).into_response();
-        }
-        return Redirect::to("/").into_response();
+        return render_book_edit(
+            &db,
+            user.username,
+            book,
+            Some("Title is required".to_string()),
+        )
+        .await;
     }
 
     let author = if form.author.trim().is_empty() {
@@ -437,55 +1009,141 @@ pub async fn book_edit_submit(
 
     let publication_year = form.publication_year.trim().parse::<i32>().ok();
 
-    match db
-        .update_book(&book_id, title, author, publication_year)
-        .await
-    {
+    let contributors = [
+        (
+            AuthorRole::Translator,
+            split_author_names(&form.translators),
+        ),
+        (AuthorRole::Editor, split_author_names(&form.editors)),
+    ];
+    let result = db
+        .edit_book(
+            &book_id,
+            title,
+            author,
+            publication_year,
+            &form.details.details(),
+            &form.details.identifiers(),
+            &contributors,
+            &parse_tags(&form.tags),
+        )
+        .await;
+
+    match result {
         Ok(_) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
         Err(error) => {
             eprintln!("Book update error: {error}");
-            if let Ok(Some(book)) = db.get_book_by_id(&book_id).await {
-                let template = BookEditTemplate {
-                    is_authenticated: true,
-                    signups_disabled: signups_disabled(),
-                    username: user.username,
-                    book,
-                    error_message: Some("Could not update book. Please try again.".to_string()),
-                };
-                return Html(template.render().unwrap()).into_response();
-            }
-            Redirect::to("/").into_response()
+            render_book_edit(
+                &db,
+                user.username,
+                book,
+                Some("Could not update book. Please try again.".to_string()),
+            )
+            .await
         }
     }
 }
 
-pub async fn book_edit_notes_page(
+pub async fn book_sharing_submit(
     State(db): State<AppState>,
     headers: HeaderMap,
     Path(book_id): Path<String>,
+    Form(form): Form<SharingForm>,
 ) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
+    };
+
+    let book = match editable_book(&db, &book_id, &user).await {
+        Ok(book) => book,
+        Err(error) => return access_error_response(error),
+    };
+
+    let Some(visibility) = BookVisibility::parse(&form.visibility) else {
+        return render_book_edit(
+            &db,
+            user.username,
+            book,
+            Some("Unknown visibility".to_string()),
+        )
+        .await;
+    };
+
+    let usernames: Vec<&str> = form
+        .shared_with
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != user.username)
+        .collect();
+
+    let result = db
+        .update_book_sharing(&book_id, visibility, &usernames)
+        .await;
+
+    match result {
+        Ok(unknown) if unknown.is_empty() => {
+            Redirect::to(&format!("/books/{}", book_id)).into_response()
+        }
+        Ok(unknown) => {
+            let book = db
+                .get_book_by_id(&book_id)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(book);
+            render_book_edit(
+                &db,
+                user.username,
+                book,
+                Some(format!("No such users: {}", unknown.join(", "))),
+            )
+            .await
+        }
+        Err(error) => {
+            eprintln!("Sharing update error: {error}");
+            render_book_edit(
+                &db,
+                user.username,
+                book,
+                Some("Could not update sharing. Please try again.".to_string()),
+            )
+            .await
+        }
     }
+}
 
-    match db.get_book_by_id(&book_id).await {
-        Ok(Some(book)) => {
+pub async fn book_edit_notes_page(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    match editable_book(&db, &book_id, &user).await {
+        Ok(book) => {
+            let preview_html = render_notes(
+                &db,
+                book.notes.as_deref().unwrap_or_default(),
+                Some(&user.id),
+            )
+            .await;
             let template = BookEditNotesTemplate {
                 is_authenticated: true,
                 signups_disabled: signups_disabled(),
-                username: user.map(|u| u.username).unwrap_or_default(),
+                username: user.username,
                 book,
+                preview_html,
                 error_message: None,
             };
             Html(template.render().unwrap()).into_response()
         }
-        Ok(None) => Redirect::to("/").into_response(),
-        Err(error) => {
-            eprintln!("Error fetching book: {error}");
-            Redirect::to("/").into_response()
-        }
+        Err(error) => access_error_response(error),
     }
 }
 
@@ -497,8 +1155,12 @@ pub async fn book_edit_notes_submit(
 ) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
     }
 
     let notes = if form.notes.trim().is_empty() {
@@ -516,6 +1178,26 @@ pub async fn book_edit_notes_submit(
     }
 }
 
+/// Rendered HTML of unsaved notes, for the live preview on the edit-notes page.
+pub async fn book_edit_notes_preview(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+    Form(form): Form<EditNotesForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
+    }
+
+    Html(render_notes(&db, &form.notes, Some(&user.id)).await).into_response()
+}
+
 pub async fn book_edit_chat_page(
     State(db): State<AppState>,
     headers: HeaderMap,
@@ -523,27 +1205,23 @@ pub async fn book_edit_chat_page(
 ) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
-    }
+    };
 
-    match db.get_book_by_id(&book_id).await {
-        Ok(Some(book)) => {
+    match editable_book(&db, &book_id, &user).await {
+        Ok(book) => {
             let template = BookEditChatTemplate {
                 is_authenticated: true,
                 signups_disabled: signups_disabled(),
-                username: user.map(|u| u.username).unwrap_or_default(),
+                username: user.username,
                 book,
                 error_message: None,
                 edit_result: None,
             };
             Html(template.render().unwrap()).into_response()
         }
-        Ok(None) => Redirect::to("/").into_response(),
-        Err(error) => {
-            eprintln!("Error fetching book: {error}");
-            Redirect::to("/").into_response()
-        }
+        Err(error) => access_error_response(error),
     }
 }
 
@@ -559,13 +1237,9 @@ pub async fn book_edit_chat_submit(
         return Redirect::to("/login").into_response();
     };
 
-    let book = match db.get_book_by_id(&book_id).await {
-        Ok(Some(book)) => book,
-        Ok(None) => return Redirect::to("/").into_response(),
-        Err(error) => {
-            eprintln!("Error fetching book: {error}");
-            return Redirect::to("/").into_response();
-        }
+    let book = match editable_book(&db, &book_id, &user).await {
+        Ok(book) => book,
+        Err(error) => return access_error_response(error),
     };
 
     let instruction = form.instruction.trim();
@@ -640,8 +1314,12 @@ pub async fn book_edit_chat_apply(
 ) -> Response {
     let user = current_user(&db, &headers).await;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
     }
 
     let title = form.title.trim();
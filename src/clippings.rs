@@ -0,0 +1,568 @@
+use axum::{
+    extract::{Multipart, State},
+    http::HeaderMap,
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::AppState;
+use crate::auth::current_user;
+use crate::books::{Book, BookDetails, BookVisibility};
+use crate::database::Database;
+use crate::highlights::HighlightInput;
+use crate::imports::{ImportResult, read_upload, render_import};
+
+type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Kindle separates entries in `My Clippings.txt` with this line.
+const CLIPPING_SEPARATOR: &str = "==========";
+
+/// Titles at least this similar (0.0 - 1.0) are taken to be the same book.
+const TITLE_MATCH_THRESHOLD: f64 = 0.85;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClippingKind {
+    Highlight,
+    Note,
+    Bookmark,
+}
+
+/// One entry of a Kindle `My Clippings.txt` file.
+#[derive(Clone, Debug)]
+pub struct Clipping {
+    pub title: String,
+    pub author: Option<String>,
+    pub kind: ClippingKind,
+    pub page: Option<String>,
+    /// Kindle location, either a single number or a range like `180-182`.
+    pub location: Option<String>,
+    /// When the clipping was made, as RFC 3339 if the date could be read.
+    pub added_at: Option<String>,
+    pub text: String,
+}
+
+impl Clipping {
+    /// Page and location in the form shown on highlights, eg. `p. 12, loc 180-182`.
+    pub fn location_label(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            self.page.as_ref().map(|page| format!("p. {page}")),
+            self.location.as_ref().map(|loc| format!("loc {loc}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+
+    fn location_range(&self) -> Option<(u64, u64)> {
+        let location = self.location.as_deref()?;
+        let (start, end) = location.split_once('-').unwrap_or((location, location));
+        let start: u64 = start.trim().parse().ok()?;
+        let end = end.trim();
+        // Accept ranges written with only the trailing digits, eg. 1203-10
+        let end: u64 = if end.len() < start.to_string().len() {
+            let prefix = &start.to_string()[..start.to_string().len() - end.len()];
+            format!("{prefix}{end}").parse().ok()?
+        } else {
+            end.parse().ok()?
+        };
+        Some((start, end.max(start)))
+    }
+}
+
+/// Parse the contents of a Kindle `My Clippings.txt` file. Entries that do not
+/// look like clippings are skipped.
+pub fn parse_clippings(contents: &str) -> Vec<Clipping> {
+    contents
+        .split(CLIPPING_SEPARATOR)
+        .filter_map(parse_clipping)
+        .collect()
+}
+
+fn parse_clipping(entry: &str) -> Option<Clipping> {
+    let mut lines = entry
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim_end_matches('\r'))
+        .skip_while(|line| line.trim().is_empty());
+
+    let (title, author) = split_title_author(lines.next()?.trim());
+    if title.is_empty() {
+        return None;
+    }
+
+    let meta = lines.next()?.trim();
+    if !meta.starts_with('-') {
+        return None;
+    }
+
+    let text = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+
+    let mut kind = ClippingKind::Highlight;
+    let mut page = None;
+    let mut location = None;
+    let mut added_at = None;
+    for (index, part) in meta.trim_start_matches('-').split('|').enumerate() {
+        let part = part.trim();
+        let lower = part.to_lowercase();
+        if index == 0 {
+            if lower.contains("bookmark") {
+                kind = ClippingKind::Bookmark;
+            } else if lower.contains("note") {
+                kind = ClippingKind::Note;
+            }
+        }
+        if let Some(value) = word_after(part, "page") {
+            page = Some(value);
+        }
+        if let Some(value) = word_after(part, "location") {
+            location = Some(value);
+        }
+        if let Some(date) = part.strip_prefix("Added on ") {
+            added_at = parse_added_on(date);
+        }
+    }
+
+    if kind != ClippingKind::Bookmark && text.is_empty() {
+        return None;
+    }
+
+    Some(Clipping {
+        title: title.to_string(),
+        author: author.map(String::from),
+        kind,
+        page,
+        location,
+        added_at,
+        text,
+    })
+}
+
+/// Split `Title (Author)`, taking the last balanced parenthesised group as the author.
+fn split_title_author(line: &str) -> (&str, Option<&str>) {
+    if !line.ends_with(')') {
+        return (line, None);
+    }
+
+    let mut depth = 0;
+    for (index, ch) in line.char_indices().rev() {
+        match ch {
+            ')' => depth += 1,
+            '(' => {
+                depth -= 1;
+                if depth == 0 {
+                    let title = line[..index].trim();
+                    let author = line[index + 1..line.len() - 1].trim();
+                    if title.is_empty() {
+                        return (line, None);
+                    }
+                    return (title, Some(author).filter(|a| !a.is_empty()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (line, None)
+}
+
+/// The word following `keyword` (case-insensitively) in `text`.
+fn word_after(text: &str, keyword: &str) -> Option<String> {
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        if word.eq_ignore_ascii_case(keyword) {
+            return words.next().map(String::from);
+        }
+    }
+    None
+}
+
+fn parse_added_on(date: &str) -> Option<String> {
+    const FORMATS: [&str; 4] = [
+        "%A, %d %B %Y %H:%M:%S",
+        "%A, %B %d, %Y %I:%M:%S %p",
+        "%A, %B %d, %Y %H:%M:%S",
+        "%A, %d %B %Y, %H:%M:%S",
+    ];
+    FORMATS.iter().find_map(|format| {
+        chrono::NaiveDateTime::parse_from_str(date.trim(), format)
+            .ok()
+            .map(|datetime| datetime.and_utc().to_rfc3339())
+    })
+}
+
+/// Lowercase letters and digits only, with single spaces between words.
+pub(crate) fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Title without a subtitle or trailing parenthesised edition note.
+fn short_title(title: &str) -> &str {
+    title
+        .split([':', '(', '['])
+        .next()
+        .unwrap_or(title)
+        .split(" - ")
+        .next()
+        .unwrap_or(title)
+        .trim()
+}
+
+fn title_similarity(a: &str, b: &str) -> f64 {
+    let full = strsim::normalized_levenshtein(&normalize(a), &normalize(b));
+    let short =
+        strsim::normalized_levenshtein(&normalize(short_title(a)), &normalize(short_title(b)));
+    full.max(short)
+}
+
+/// Authors match when either is unknown or they share a name, so that
+/// "Calvino, Italo" and "Italo Calvino" are the same person.
+fn authors_match(a: Option<&str>, b: Option<&str>) -> bool {
+    let (Some(a), Some(b)) = (a, b) else {
+        return true;
+    };
+    let a = normalize(a);
+    let b = normalize(b);
+    a.split(' ')
+        .filter(|word| word.len() > 1)
+        .any(|word| b.split(' ').any(|other| other == word))
+}
+
+/// The book most similar to a clipping's title and author, if any is close enough.
+pub fn best_match<'a>(books: &'a [Book], title: &str, author: Option<&str>) -> Option<&'a Book> {
+    books
+        .iter()
+        .filter(|book| authors_match(book.author.as_deref(), author))
+        .map(|book| (book, title_similarity(&book.title, title)))
+        .filter(|(_, score)| *score >= TITLE_MATCH_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(book, _)| book)
+}
+
+/// Outcome of importing a clippings file.
+#[derive(Default)]
+pub struct ClippingsSummary {
+    pub books_matched: usize,
+    pub books_created: usize,
+    pub highlights_imported: usize,
+    pub duplicates_skipped: usize,
+}
+
+/// A highlight with the note the reader attached to it, if any.
+struct PendingHighlight<'a> {
+    highlight: &'a Clipping,
+    note: Option<&'a str>,
+}
+
+struct ClippingBook<'a> {
+    title: &'a str,
+    author: Option<&'a str>,
+    highlights: Vec<PendingHighlight<'a>>,
+}
+
+/// Group clippings by book. Kindle stores notes as separate entries at the end
+/// of the passage they comment on, so each is attached to that highlight.
+fn group_by_book(clippings: &[Clipping]) -> Vec<ClippingBook<'_>> {
+    let mut books: Vec<ClippingBook> = Vec::new();
+
+    for clipping in clippings {
+        let index = match books
+            .iter()
+            .position(|b| b.title == clipping.title && b.author == clipping.author.as_deref())
+        {
+            Some(index) => index,
+            None => {
+                books.push(ClippingBook {
+                    title: &clipping.title,
+                    author: clipping.author.as_deref(),
+                    highlights: Vec::new(),
+                });
+                books.len() - 1
+            }
+        };
+        let book = &mut books[index];
+
+        match clipping.kind {
+            ClippingKind::Highlight => book.highlights.push(PendingHighlight {
+                highlight: clipping,
+                note: None,
+            }),
+            ClippingKind::Note => {
+                let target = clipping.location_range().and_then(|(note_at, _)| {
+                    book.highlights.iter_mut().rev().find(|pending| {
+                        pending
+                            .highlight
+                            .location_range()
+                            .is_some_and(|(start, end)| (start..=end).contains(&note_at))
+                    })
+                });
+                match target {
+                    Some(pending) => pending.note = Some(&clipping.text),
+                    // A note without a highlight is kept as a quote of its own
+                    None => book.highlights.push(PendingHighlight {
+                        highlight: clipping,
+                        note: None,
+                    }),
+                }
+            }
+            ClippingKind::Bookmark => {}
+        }
+    }
+
+    books.retain(|book| !book.highlights.is_empty());
+    books
+}
+
+/// Import clippings as highlights. Books are matched by fuzzy title and author
+/// among the books `owner_id` may edit, missing ones are created as private
+/// books of theirs, and quotes already on a book are skipped.
+pub async fn import_clippings(
+    db: &Database,
+    clippings: &[Clipping],
+    owner_id: &str,
+) -> Result<ClippingsSummary, DynError> {
+    let mut books = db.get_editable_books(owner_id).await?;
+    let mut summary = ClippingsSummary::default();
+
+    for group in group_by_book(clippings) {
+        let book_id = match best_match(&books, group.title, group.author) {
+            Some(book) => {
+                summary.books_matched += 1;
+                book.id.clone()
+            }
+            None => {
+                let book_id = db
+                    .create_book(
+                        group.title,
+                        group.author,
+                        None,
+                        None,
+                        Some(owner_id),
+                        BookVisibility::Private,
+                        &BookDetails::default(),
+                        &[],
+                    )
+                    .await?;
+                summary.books_created += 1;
+                if let Some(book) = db.get_book_by_id(&book_id).await? {
+                    books.push(book);
+                }
+                book_id
+            }
+        };
+
+        for pending in group.highlights {
+            let clipping = pending.highlight;
+            if db.highlight_exists(&book_id, &clipping.text).await? {
+                summary.duplicates_skipped += 1;
+                continue;
+            }
+
+            let location = clipping.location_label();
+            let input = HighlightInput {
+                quote: &clipping.text,
+                location: location.as_deref(),
+                chapter: None,
+                comment: pending.note,
+            };
+            db.create_highlight_at(&book_id, &input, clipping.added_at.as_deref())
+                .await?;
+            summary.highlights_imported += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+pub async fn clippings_import(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let contents = match read_upload(&mut multipart).await {
+        Ok(mut fields) => fields.remove("file"),
+        Err(error) => return render_import(user, Some(ImportResult::Error(error))),
+    };
+    let Some(contents) = contents else {
+        return render_import(
+            user,
+            Some(ImportResult::Error("Choose a file to import".to_string())),
+        );
+    };
+
+    let clippings = parse_clippings(&contents);
+    if clippings.is_empty() {
+        return render_import(
+            user,
+            Some(ImportResult::Error(
+                "No clippings found, is this a My Clippings.txt file?".to_string(),
+            )),
+        );
+    }
+
+    match import_clippings(&db, &clippings, &user.id).await {
+        Ok(summary) => render_import(user, Some(ImportResult::Clippings(summary))),
+        Err(error) => {
+            eprintln!("Clippings import error: {error}");
+            render_import(
+                user,
+                Some(ImportResult::Error(
+                    "Could not import clippings".to_string(),
+                )),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::{add_book, test_database};
+
+    const CLIPPINGS: &str = "\u{feff}Invisible Cities (Calvino, Italo)\r
+- Your Highlight on page 12 | Location 180-182 | Added on Monday, 3 June 2024 21:14:05\r
+\r
+Cities, like dreams, are made of desires and fears.\r
+==========\r
+Invisible Cities (Calvino, Italo)\r
+- Your Note on page 12 | Location 181 | Added on Monday, 3 June 2024 21:15:00\r
+\r
+Compare with Borges\r
+==========\r
+Invisible Cities (Calvino, Italo)\r
+- Your Bookmark on page 40 | Location 610 | Added on Tuesday, 4 June 2024 08:00:00\r
+\r
+\r
+==========\r
+The Periodic Table (Primo Levi) (Penguin Classics)\r
+- Your Highlight at location 1203-10 | Added on Wednesday, June 5, 2024 7:30:00 PM\r
+\r
+Matter is matter, neither noble nor vile.\r
+==========\r
+not a clipping\r
+==========\r
+";
+
+    #[test]
+    fn parses_highlights_notes_and_bookmarks() {
+        let clippings = parse_clippings(CLIPPINGS);
+        assert_eq!(clippings.len(), 4);
+
+        let highlight = &clippings[0];
+        assert_eq!(highlight.title, "Invisible Cities");
+        assert_eq!(highlight.author.as_deref(), Some("Calvino, Italo"));
+        assert_eq!(highlight.kind, ClippingKind::Highlight);
+        assert_eq!(
+            highlight.location_label().as_deref(),
+            Some("p. 12, loc 180-182")
+        );
+        assert_eq!(
+            highlight.added_at.as_deref(),
+            Some("2024-06-03T21:14:05+00:00")
+        );
+        assert_eq!(
+            highlight.text,
+            "Cities, like dreams, are made of desires and fears."
+        );
+
+        assert_eq!(clippings[1].kind, ClippingKind::Note);
+        assert_eq!(clippings[2].kind, ClippingKind::Bookmark);
+        assert_eq!(clippings[2].text, "");
+
+        let levi = &clippings[3];
+        assert_eq!(levi.title, "The Periodic Table (Primo Levi)");
+        assert_eq!(levi.author.as_deref(), Some("Penguin Classics"));
+        assert_eq!(levi.location_range(), Some((1203, 1210)));
+        assert_eq!(levi.added_at.as_deref(), Some("2024-06-05T19:30:00+00:00"));
+    }
+
+    #[test]
+    fn splits_title_and_author() {
+        assert_eq!(
+            split_title_author("Invisible Cities (Italo Calvino)"),
+            ("Invisible Cities", Some("Italo Calvino"))
+        );
+        assert_eq!(
+            split_title_author("Ficciones (Borges, Jorge Luis (trans.))"),
+            ("Ficciones", Some("Borges, Jorge Luis (trans.)"))
+        );
+        assert_eq!(split_title_author("(Anonymous)"), ("(Anonymous)", None));
+        assert_eq!(split_title_author("No author"), ("No author", None));
+    }
+
+    #[test]
+    fn notes_attach_to_the_highlight_they_end() {
+        let clippings = parse_clippings(CLIPPINGS);
+        let books = group_by_book(&clippings);
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0].highlights.len(), 1);
+        assert_eq!(books[0].highlights[0].note, Some("Compare with Borges"));
+    }
+
+    #[tokio::test]
+    async fn import_matches_books_and_skips_duplicates() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("reader", "secret").await.unwrap();
+        let book_id = add_book(
+            &db,
+            "Invisible cities",
+            Some("Italo Calvino"),
+            Some(&owner),
+            BookVisibility::Private,
+        )
+        .await;
+        let clippings = parse_clippings(CLIPPINGS);
+
+        let summary = import_clippings(&db, &clippings, &owner).await.unwrap();
+        assert_eq!(summary.books_matched, 1);
+        assert_eq!(summary.books_created, 1);
+        assert_eq!(summary.highlights_imported, 2);
+        let highlights = db.get_highlights(&book_id).await.unwrap();
+        assert_eq!(
+            highlights[0].comment.as_deref(),
+            Some("Compare with Borges")
+        );
+
+        let summary = import_clippings(&db, &clippings, &owner).await.unwrap();
+        assert_eq!(summary.books_created, 0);
+        assert_eq!(summary.highlights_imported, 0);
+        assert_eq!(summary.duplicates_skipped, 2);
+    }
+
+    #[tokio::test]
+    async fn import_leaves_other_users_books_alone() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("reader", "secret").await.unwrap();
+        let other = db.create_user("other", "secret").await.unwrap();
+        let book_id = add_book(
+            &db,
+            "Invisible cities",
+            Some("Italo Calvino"),
+            Some(&other),
+            BookVisibility::Private,
+        )
+        .await;
+
+        let summary = import_clippings(&db, &parse_clippings(CLIPPINGS), &owner)
+            .await
+            .unwrap();
+        assert_eq!(summary.books_matched, 0);
+        assert_eq!(summary.books_created, 2);
+        assert!(db.get_highlights(&book_id).await.unwrap().is_empty());
+        for book in db.get_editable_books(&owner).await.unwrap() {
+            assert_eq!(book.visibility, BookVisibility::Private);
+        }
+    }
+}
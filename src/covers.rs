@@ -0,0 +1,262 @@
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use image::ImageFormat;
+use std::env;
+use std::path::PathBuf;
+
+use crate::AppState;
+use crate::auth::current_user;
+use crate::books::{Book, access_error_response, editable_book};
+use crate::files::library_file;
+use crate::metadata::extractor_for;
+
+type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Thumbnails are scaled down to fit this box, keeping their aspect ratio.
+const THUMBNAIL_WIDTH: u32 = 300;
+const THUMBNAIL_HEIGHT: u32 = 450;
+
+/// Cover URLs carry the book's `updated_at`, so a cached cover never goes stale.
+const COVER_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
+/// Directory holding cover thumbnails, from `COVERS_PATH` (default: ./covers).
+pub fn covers_path() -> PathBuf {
+    PathBuf::from(env::var("COVERS_PATH").unwrap_or_else(|_| "./covers".to_string()))
+}
+
+fn thumbnail_file(book_id: &str) -> PathBuf {
+    covers_path().join(format!("{book_id}.jpg"))
+}
+
+/// Left next to the thumbnails when a book file has no cover, so it is not searched again.
+fn no_cover_file(book_id: &str) -> PathBuf {
+    covers_path().join(format!("{book_id}.none"))
+}
+
+/// Whether the stored thumbnail is missing or older than the book file, eg.
+/// because the file was replaced since.
+pub fn cover_outdated(book_id: &str, book_file: &std::path::Path) -> bool {
+    let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|m| m.modified());
+    match (modified(&thumbnail_file(book_id)), modified(book_file)) {
+        (Ok(thumbnail), Ok(file)) => file > thumbnail,
+        (Ok(_), Err(_)) => false,
+        (Err(_), _) => true,
+    }
+}
+
+/// Look for a cover in the book file again, eg. after it was rescanned.
+pub fn forget_missing_cover(book_id: &str) {
+    let _ = std::fs::remove_file(no_cover_file(book_id));
+}
+
+/// Remove the stored thumbnail of a deleted book.
+pub fn remove_cover(book_id: &str) {
+    let _ = std::fs::remove_file(thumbnail_file(book_id));
+    forget_missing_cover(book_id);
+}
+
+/// Resize an image to a JPEG thumbnail.
+pub fn make_thumbnail(image: &[u8]) -> Result<Vec<u8>, image::ImageError> {
+    let thumbnail = image::load_from_memory(image)?
+        .thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+        .into_rgb8();
+    let mut jpeg = std::io::Cursor::new(Vec::new());
+    thumbnail.write_to(&mut jpeg, ImageFormat::Jpeg)?;
+    Ok(jpeg.into_inner())
+}
+
+/// Store a thumbnail of `image` as the book's cover.
+pub fn save_cover(book_id: &str, image: &[u8]) -> Result<(), DynError> {
+    let thumbnail = make_thumbnail(image)?;
+    std::fs::create_dir_all(covers_path())?;
+    std::fs::write(thumbnail_file(book_id), thumbnail)?;
+    forget_missing_cover(book_id);
+    Ok(())
+}
+
+/// The cover image inside a book file, from the extractor for its format.
+pub fn extract_cover(path: &std::path::Path) -> Option<Vec<u8>> {
+    extractor_for(path)?.cover(path)
+}
+
+/// The cached thumbnail of a book, extracting it from the book file the first time.
+fn load_cover(book: &Book) -> Option<Vec<u8>> {
+    if let Ok(thumbnail) = std::fs::read(thumbnail_file(&book.id)) {
+        return Some(thumbnail);
+    }
+    let filepath = book.filepath.as_deref()?;
+    if no_cover_file(&book.id).exists() {
+        return None;
+    }
+
+    match extract_cover(&library_file(filepath)?) {
+        Some(image) => match save_cover(&book.id, &image) {
+            Ok(()) => std::fs::read(thumbnail_file(&book.id)).ok(),
+            Err(error) => {
+                eprintln!("Cover thumbnail error: {error}");
+                None
+            }
+        },
+        None => {
+            if std::fs::create_dir_all(covers_path()).is_ok() {
+                let _ = std::fs::write(no_cover_file(&book.id), "");
+            }
+            None
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A plain cover with the title and author, for books without a cover image.
+fn placeholder_cover(book: &Book) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in book.title.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + word.chars().count() > 16 {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines.truncate(6);
+
+    let mut text = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        text.push_str(&format!(
+            r#"<text x="24" y="{}" font-size="28">{}</text>"#,
+            64 + index * 36,
+            escape_xml(line)
+        ));
+    }
+    if let Some(author) = &book.author {
+        text.push_str(&format!(
+            r#"<text x="24" y="410" font-size="20" fill-opacity="0.7">{}</text>"#,
+            escape_xml(author)
+        ));
+    }
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{THUMBNAIL_WIDTH}" height="{THUMBNAIL_HEIGHT}" viewBox="0 0 {THUMBNAIL_WIDTH} {THUMBNAIL_HEIGHT}"><rect width="100%" height="100%" fill="#eeeae0"/><g font-family="Georgia, serif" fill="#333">{text}</g></svg>"##
+    )
+}
+
+pub async fn book_cover(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let book = match db.get_visible_book(&book_id, viewer_id).await {
+        Ok(Some(book)) => book,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Book not found").into_response(),
+        Err(error) => {
+            eprintln!("Error fetching book: {error}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    };
+
+    // Opening book files and resizing images would block the runtime. A
+    // decoder panicking on a malformed file gets the placeholder.
+    let cover = {
+        let book = book.clone();
+        tokio::task::spawn_blocking(move || load_cover(&book))
+    }
+    .await
+    .unwrap_or_else(|error| {
+        eprintln!("Cover task failed for book {}: {error}", book.id);
+        None
+    });
+
+    match cover {
+        Some(thumbnail) => (
+            [
+                (header::CONTENT_TYPE, "image/jpeg"),
+                (header::CACHE_CONTROL, COVER_CACHE_CONTROL),
+            ],
+            thumbnail,
+        )
+            .into_response(),
+        None => (
+            [
+                (header::CONTENT_TYPE, "image/svg+xml"),
+                (header::CACHE_CONTROL, COVER_CACHE_CONTROL),
+            ],
+            placeholder_cover(&book),
+        )
+            .into_response(),
+    }
+}
+
+/// Replace a book's cover with an uploaded image.
+pub async fn book_cover_upload(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+    mut multipart: Multipart,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
+    }
+
+    let mut image = None;
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("cover") => match field.bytes().await {
+                Ok(bytes) => image = Some(bytes),
+                Err(error) => {
+                    return (StatusCode::BAD_REQUEST, format!("Upload failed: {error}"))
+                        .into_response();
+                }
+            },
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(error) => {
+                return (StatusCode::BAD_REQUEST, format!("Upload failed: {error}"))
+                    .into_response();
+            }
+        }
+    }
+
+    let Some(image) = image.filter(|image| !image.is_empty()) else {
+        return (StatusCode::BAD_REQUEST, "Choose an image to upload").into_response();
+    };
+
+    let saved = {
+        let book_id = book_id.clone();
+        tokio::task::spawn_blocking(move || save_cover(&book_id, &image))
+            .await
+            .unwrap_or_else(|error| Err(error.into()))
+    };
+    if let Err(error) = saved {
+        eprintln!("Cover upload error: {error}");
+        return (StatusCode::BAD_REQUEST, "Could not read that image").into_response();
+    }
+
+    if let Err(error) = db.touch_book(&book_id).await {
+        eprintln!("Error updating book: {error}");
+    }
+
+    Redirect::to(&format!("/books/{}", book_id)).into_response()
+}
@@ -1,15 +1,47 @@
 use argon2::password_hash::SaltString;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use sqlx::{Pool, Row, Sqlite, SqlitePool, migrate::MigrateDatabase};
-use std::{fs, path::Path};
+use sha2::{Digest, Sha256};
+use sqlx::migrate::{Migrate, MigrateDatabase, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow};
+use sqlx::{Pool, Row, Sqlite, SqlitePool};
+
+use crate::authors::AuthorRole;
+use crate::metadata::{Identifier, join_author_names, split_author_names};
 
 pub struct Database {
     pub pool: Pool<Sqlite>,
 }
 
+/// The files under `migrations/`, embedded at compile time. Each version has an
+/// `.up.sql` script and a `.down.sql` script that reverts it.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// What a backup holds, as found by `Database::check_backup`.
+pub struct BackupInfo {
+    pub migrations: usize,
+    pub users: i64,
+    pub books: i64,
+}
+
+/// A migration as listed by `alayaserver migrate status`.
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    /// When it was applied, None while pending.
+    pub applied_at: Option<String>,
+    /// The file changed after it was applied.
+    pub modified: bool,
+}
+
 type DynError = Box<dyn std::error::Error + Send + Sync>;
 
+/// How long a session stays valid without being used.
+pub const SESSION_TTL: chrono::Duration = chrono::Duration::days(7);
+
+/// Minimum time between two expiry renewals of the same session.
+const SESSION_TOUCH_INTERVAL: chrono::Duration = chrono::Duration::minutes(5);
+
 impl Database {
     pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
         // Create database if it doesn't exist
@@ -34,91 +66,228 @@ impl Database {
         Ok(Database { pool })
     }
 
+    /// Open an existing database file without writing to it, eg. a backup to check.
+    pub async fn open_read_only(path: &std::path::Path) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        Ok(Database { pool })
+    }
+
+    /// Apply pending migrations. Refuses to run when an applied migration file
+    /// has been edited since, as its checksum no longer matches.
     pub async fn run_migrations(&self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Running database migrations...");
+        let pending = self
+            .migration_status()
+            .await?
+            .into_iter()
+            .filter(|migration| migration.applied_at.is_none())
+            .count();
 
-        // Create migrations table if it doesn't exist
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS _migrations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                filename TEXT NOT NULL UNIQUE,
-                executed_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+        let mut conn = self.pool.acquire().await?;
+        without_foreign_keys(&mut conn, async |conn| MIGRATOR.run(conn).await).await?;
 
-        // Get all migration files
-        let migrations_dir = Path::new("migrations");
-        if !migrations_dir.exists() {
-            println!("Migrations directory not found");
-            return Ok(());
+        if pending > 0 {
+            println!("Applied {} migration(s)", pending);
         }
+        Ok(())
+    }
 
-        let mut entries: Vec<_> = fs::read_dir(migrations_dir)?
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| {
-                entry
-                    .path()
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| ext == "sql")
-                    .unwrap_or(false)
-            })
-            .collect();
-
-        entries.sort_by_key(|entry| entry.file_name());
+    /// Every known migration and whether it has been applied.
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+        self.adopt_legacy_migrations().await?;
 
-        for entry in entries {
-            let filename = entry.file_name().to_string_lossy().to_string();
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        let applied = conn.list_applied_migrations().await?;
 
-            // Check if migration has already been executed
-            let executed = sqlx::query("SELECT filename FROM _migrations WHERE filename = ?")
-                .bind(&filename)
-                .fetch_optional(&self.pool)
+        let installed: std::collections::HashMap<i64, String> =
+            sqlx::query("SELECT version, installed_on FROM _sqlx_migrations")
+                .fetch_all(&mut *conn)
                 .await?
-                .is_some();
+                .iter()
+                .map(|row| (row.get("version"), row.get("installed_on")))
+                .collect();
+
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| {
+                let record = applied.iter().find(|a| a.version == migration.version);
+                MigrationStatus {
+                    version: migration.version,
+                    description: migration.description.to_string(),
+                    applied_at: record.map(|_| {
+                        installed
+                            .get(&migration.version)
+                            .cloned()
+                            .unwrap_or_default()
+                    }),
+                    modified: record.is_some_and(|a| a.checksum != migration.checksum),
+                }
+            })
+            .collect())
+    }
 
-            if executed {
-                println!("Migration {} already executed, skipping", filename);
-                continue;
-            }
+    /// Revert the most recently applied migration, returning it.
+    pub async fn revert_last_migration(&self) -> Result<Option<MigrationStatus>, sqlx::Error> {
+        let mut applied: Vec<MigrationStatus> = self
+            .migration_status()
+            .await?
+            .into_iter()
+            .filter(|migration| migration.applied_at.is_some())
+            .collect();
+        if applied.is_empty() {
+            return Ok(None);
+        }
+        let target = applied
+            .iter()
+            .rev()
+            .nth(1)
+            .map(|migration| migration.version)
+            .unwrap_or(0);
+
+        let mut conn = self.pool.acquire().await?;
+        without_foreign_keys(&mut conn, async |conn| MIGRATOR.undo(conn, target).await).await?;
+        Ok(applied.pop())
+    }
 
-            println!("Executing migration: {}", filename);
+    /// Databases set up before migrations were embedded list the files they ran
+    /// in `_migrations`. Record those in sqlx's table once and drop the old one.
+    async fn adopt_legacy_migrations(&self) -> Result<(), sqlx::Error> {
+        let legacy = sqlx::query(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = '_migrations'",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if legacy.is_none() {
+            return Ok(());
+        }
 
-            // Read and execute migration file
-            let migration_sql = fs::read_to_string(entry.path())?;
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
 
-            // Execute the migration in a transaction
-            let mut tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query("SELECT filename, executed_at FROM _migrations")
+            .fetch_all(&mut *tx)
+            .await?;
 
-            // Split by semicolons and execute each statement
-            for statement in migration_sql.split(';') {
-                let statement = statement.trim();
-                if !statement.is_empty() {
-                    sqlx::query(statement).execute(&mut *tx).await?;
-                }
-            }
+        for row in rows {
+            let filename: String = row.get("filename");
+            let version = filename
+                .split('_')
+                .next()
+                .and_then(|prefix| prefix.parse::<i64>().ok());
+            let Some(migration) = MIGRATOR.iter().find(|migration| {
+                Some(migration.version) == version && !migration.migration_type.is_down_migration()
+            }) else {
+                continue;
+            };
 
-            // Record the migration as executed
             sqlx::query(
-                "INSERT INTO _migrations (filename, executed_at) VALUES (?, datetime('now'))",
+                "INSERT OR IGNORE INTO _sqlx_migrations (version, description, installed_on, success, checksum, execution_time)
+                 VALUES (?, ?, ?, TRUE, ?, 0)",
             )
-            .bind(&filename)
+            .bind(migration.version)
+            .bind(migration.description.as_ref())
+            .bind(row.get::<String, _>("executed_at"))
+            .bind(migration.checksum.as_ref())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("DROP TABLE _migrations")
             .execute(&mut *tx)
             .await?;
+        tx.commit().await?;
 
-            tx.commit().await?;
+        Ok(())
+    }
 
-            println!("Successfully executed migration: {}", filename);
-        }
+    // Backup methods
 
-        println!("All migrations completed");
+    /// Write a consistent copy of the database to `path`, which must not exist yet.
+    pub async fn backup_to(&self, path: &std::path::Path) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().as_ref())
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
+    /// Check that this database is intact and was written by a version of alaya
+    /// whose migrations this binary knows, without changing it.
+    pub async fn check_backup(&self) -> Result<BackupInfo, DynError> {
+        let integrity: String = sqlx::query("PRAGMA integrity_check")
+            .fetch_one(&self.pool)
+            .await?
+            .get(0);
+        if integrity != "ok" {
+            return Err(format!("integrity check failed: {integrity}").into());
+        }
+
+        let tables: Vec<String> =
+            sqlx::query("SELECT name FROM sqlite_master WHERE type = 'table'")
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|row| row.get("name"))
+                .collect();
+        let has_table = |name: &str| tables.iter().any(|table| table == name);
+
+        let mut migrations = 0;
+        if has_table("_sqlx_migrations") {
+            let rows = sqlx::query("SELECT version, checksum FROM _sqlx_migrations")
+                .fetch_all(&self.pool)
+                .await?;
+            for row in &rows {
+                let version: i64 = row.get("version");
+                let checksum: Vec<u8> = row.get("checksum");
+                let known = MIGRATOR.iter().any(|migration| {
+                    migration.version == version
+                        && !migration.migration_type.is_down_migration()
+                        && migration.checksum.as_ref() == checksum.as_slice()
+                });
+                if !known {
+                    return Err(format!(
+                        "migration {version} is unknown to this alaya or was modified"
+                    )
+                    .into());
+                }
+            }
+            migrations = rows.len();
+        } else if has_table("_migrations") {
+            let row = sqlx::query("SELECT COUNT(*) AS count FROM _migrations")
+                .fetch_one(&self.pool)
+                .await?;
+            migrations = row.get::<i64, _>("count") as usize;
+        }
+
+        if !has_table("users") || !has_table("books") {
+            return Err("not an alaya database".into());
+        }
+
+        let count = |table: &str| {
+            let sql = format!("SELECT COUNT(*) AS count FROM {table}");
+            let pool = &self.pool;
+            async move {
+                sqlx::query(&sql)
+                    .fetch_one(pool)
+                    .await
+                    .map(|row| row.get::<i64, _>("count"))
+            }
+        };
+
+        Ok(BackupInfo {
+            migrations,
+            users: count("users").await?,
+            books: count("books").await?,
+        })
+    }
+
     // User-related database methods
     pub async fn get_all_users(&self) -> Result<Vec<crate::auth::User>, sqlx::Error> {
         let rows = sqlx::query(
@@ -224,6 +393,33 @@ impl Database {
             .is_ok())
     }
 
+    pub async fn get_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<crate::auth::User>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT id, username, password_hash, created_at FROM users WHERE username = ?",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| crate::auth::User {
+            id: row.get("id"),
+            username: row.get("username"),
+            password_hash: row.get("password_hash"),
+            created_at: row.get("created_at"),
+        }))
+    }
+
+    pub async fn get_username(&self, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+        let row = sqlx::query("SELECT username FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get("username")))
+    }
+
     pub async fn update_password(&self, user_id: &str, new_password: &str) -> Result<(), DynError> {
         let password_hash = self.hash_password(new_password)?;
         let now = chrono::Utc::now().to_rfc3339();
@@ -239,20 +435,28 @@ impl Database {
     }
 
     // Session management methods
-    pub async fn create_session(&self, user_id: &str) -> Result<String, DynError> {
+    pub async fn create_session(
+        &self,
+        user_id: &str,
+        user_agent: Option<&str>,
+    ) -> Result<String, DynError> {
         // Generate a simple session token (UUID)
         let token = uuid::Uuid::new_v4().to_string();
-        let now = chrono::Utc::now().to_rfc3339();
+        let now = chrono::Utc::now();
         let session_id = uuid::Uuid::new_v4().to_string();
 
-        // Insert session into database (no expiration)
-        sqlx::query("INSERT INTO sessions (id, user_id, token, created_at) VALUES (?, ?, ?, ?)")
-            .bind(&session_id)
-            .bind(user_id)
-            .bind(&token)
-            .bind(&now)
-            .execute(&self.pool)
-            .await?;
+        sqlx::query(
+            "INSERT INTO sessions (id, user_id, token, created_at, last_seen_at, expires_at, user_agent) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&session_id)
+        .bind(user_id)
+        .bind(&token)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind((now + SESSION_TTL).to_rfc3339())
+        .bind(user_agent)
+        .execute(&self.pool)
+        .await?;
 
         Ok(token)
     }
@@ -261,13 +465,15 @@ impl Database {
         &self,
         token: &str,
     ) -> Result<Option<crate::auth::User>, DynError> {
+        let now = chrono::Utc::now().to_rfc3339();
         let session_row = sqlx::query(
             "SELECT s.user_id, u.username, u.password_hash, u.created_at
              FROM sessions s
              JOIN users u ON s.user_id = u.id
-             WHERE s.token = ?",
+             WHERE s.token = ? AND s.expires_at > ?",
         )
         .bind(token)
+        .bind(&now)
         .fetch_optional(&self.pool)
         .await?;
 
@@ -284,6 +490,71 @@ impl Database {
         }
     }
 
+    /// Slide a live session's expiry forward. Writes at most once per
+    /// `SESSION_TOUCH_INTERVAL`; returns true when the session was renewed.
+    pub async fn touch_session(&self, token: &str) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let result = sqlx::query(
+            "UPDATE sessions SET last_seen_at = ?, expires_at = ?
+             WHERE token = ? AND expires_at > ? AND last_seen_at < ?",
+        )
+        .bind(now.to_rfc3339())
+        .bind((now + SESSION_TTL).to_rfc3339())
+        .bind(token)
+        .bind(now.to_rfc3339())
+        .bind((now - SESSION_TOUCH_INTERVAL).to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Give a session a fresh token, keeping its id and history.
+    pub async fn rotate_session(&self, token: &str) -> Result<Option<String>, sqlx::Error> {
+        let new_token = uuid::Uuid::new_v4().to_string();
+        let result = sqlx::query("UPDATE sessions SET token = ? WHERE token = ?")
+            .bind(&new_token)
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok((result.rows_affected() > 0).then_some(new_token))
+    }
+
+    /// Active sessions of a user, most recently used first.
+    /// The session identified by `current_token` is flagged as current.
+    pub async fn get_sessions(
+        &self,
+        user_id: &str,
+        current_token: Option<&str>,
+    ) -> Result<Vec<crate::auth::Session>, sqlx::Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let rows = sqlx::query(
+            "SELECT id, created_at, last_seen_at, user_agent, token = ? AS is_current
+             FROM sessions
+             WHERE user_id = ? AND expires_at > ?
+             ORDER BY last_seen_at DESC",
+        )
+        .bind(current_token)
+        .bind(user_id)
+        .bind(&now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let sessions = rows
+            .into_iter()
+            .map(|row| crate::auth::Session {
+                id: row.get("id"),
+                created_at: row.get("created_at"),
+                last_seen_at: row.get("last_seen_at"),
+                user_agent: row.get("user_agent"),
+                is_current: row.get::<Option<bool>, _>("is_current").unwrap_or(false),
+            })
+            .collect();
+
+        Ok(sessions)
+    }
+
     pub async fn delete_session(&self, token: &str) -> Result<(), DynError> {
         sqlx::query("DELETE FROM sessions WHERE token = ?")
             .bind(token)
@@ -293,165 +564,635 @@ impl Database {
         Ok(())
     }
 
+    /// Revoke a session by id. Only deletes it if it belongs to the given user.
+    pub async fn delete_session_by_id(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sessions WHERE id = ? AND user_id = ?")
+            .bind(session_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete every session of a user, except the one with `keep_token` if given.
+    pub async fn delete_user_sessions(
+        &self,
+        user_id: &str,
+        keep_token: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sessions WHERE user_id = ? AND token IS NOT ?")
+            .bind(user_id)
+            .bind(keep_token)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let result =
+            sqlx::query("DELETE FROM sessions WHERE expires_at IS NULL OR expires_at <= ?")
+                .bind(&now)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected())
+    }
+
+    // API token methods
+    /// Create a named API token for a user and return the plaintext token.
+    /// Only a hash is stored, so the token cannot be shown again later.
+    pub async fn create_api_token(&self, user_id: &str, name: &str) -> Result<String, DynError> {
+        let token = format!(
+            "alaya_{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        let token_id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query(
+            "INSERT INTO api_tokens (id, user_id, name, token_hash, created_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&token_id)
+        .bind(user_id)
+        .bind(name)
+        .bind(hash_api_token(&token))
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    pub async fn get_api_tokens(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<crate::auth::ApiToken>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, name, created_at, last_used_at FROM api_tokens WHERE user_id = ? ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let tokens = rows
+            .into_iter()
+            .map(|row| crate::auth::ApiToken {
+                id: row.get("id"),
+                name: row.get("name"),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+            })
+            .collect();
+
+        Ok(tokens)
+    }
+
+    pub async fn validate_api_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<crate::auth::User>, DynError> {
+        let token_hash = hash_api_token(token);
+
+        let row = sqlx::query(
+            "SELECT t.id AS token_id, t.user_id, u.username, u.password_hash, u.created_at
+             FROM api_tokens t
+             JOIN users u ON t.user_id = u.id
+             WHERE t.token_hash = ?",
+        )
+        .bind(&token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let token_id: String = row.get("token_id");
+        let now = chrono::Utc::now().to_rfc3339();
+        sqlx::query("UPDATE api_tokens SET last_used_at = ? WHERE id = ?")
+            .bind(&now)
+            .bind(&token_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(Some(crate::auth::User {
+            id: row.get("user_id"),
+            username: row.get("username"),
+            password_hash: row.get("password_hash"),
+            created_at: row.get("created_at"),
+        }))
+    }
+
+    /// Revoke a token. Only deletes it if it belongs to the given user.
+    pub async fn delete_api_token(&self, user_id: &str, token_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM api_tokens WHERE id = ? AND user_id = ?")
+            .bind(token_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     // Book-related database methods
+    /// Create a book with its details and identifiers in one transaction.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_book(
         &self,
         title: &str,
         author: Option<&str>,
         publication_year: Option<i32>,
         notes: Option<&str>,
+        owner_id: Option<&str>,
+        visibility: crate::books::BookVisibility,
+        details: &crate::books::BookDetails,
+        identifiers: &[Identifier],
     ) -> Result<String, DynError> {
         let book_id = uuid::Uuid::new_v4().to_string();
         let now = chrono::Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
 
         sqlx::query(
-            "INSERT INTO books (id, title, author, publication_year, notes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO books (id, title, author, publication_year, notes, user_id, visibility, publisher, language, description, page_count, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&book_id)
         .bind(title)
         .bind(author)
         .bind(publication_year)
         .bind(notes)
+        .bind(owner_id)
+        .bind(visibility.as_str())
+        .bind(&details.publisher)
+        .bind(&details.language)
+        .bind(&details.description)
+        .bind(details.page_count)
         .bind(&now)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        insert_book_identifiers(&mut tx, &book_id, identifiers).await?;
+        sync_book_authors(&mut tx, &book_id).await?;
+        replace_book_fts(&mut tx, &book_id).await?;
+        tx.commit().await?;
+
         Ok(book_id)
     }
 
     /// Create or update a book by filepath (upsert).
     /// If a book with the given filepath exists, it will be updated.
     /// Otherwise, a new book will be created.
+    /// Details and identifiers missing from the file are left as they were, so
+    /// rescanning keeps what was filled in by hand. New books get `owner_id` and
+    /// `visibility`; existing ones keep theirs, unless they had no owner yet.
+    #[allow(clippy::too_many_arguments)]
     pub async fn upsert_book_by_filepath(
         &self,
         filepath: &str,
         title: &str,
         author: Option<&str>,
         publication_year: Option<i32>,
+        details: &crate::books::BookDetails,
+        identifiers: &[Identifier],
+        owner_id: Option<&str>,
+        visibility: crate::books::BookVisibility,
     ) -> Result<String, DynError> {
         let now = chrono::Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
 
         // Check if book with this filepath already exists
         let existing = sqlx::query("SELECT id FROM books WHERE filepath = ?")
             .bind(filepath)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *tx)
             .await?;
 
-        if let Some(row) = existing {
+        let book_id = if let Some(row) = existing {
             // Update existing book
             let book_id: String = row.get("id");
             sqlx::query(
-                "UPDATE books SET title = ?, author = ?, publication_year = ?, updated_at = ? WHERE id = ?",
+                "UPDATE books SET title = ?, author = ?, publication_year = ?,
+                 publisher = COALESCE(?, publisher), language = COALESCE(?, language),
+                 description = COALESCE(?, description), page_count = COALESCE(?, page_count),
+                 user_id = COALESCE(user_id, ?), updated_at = ? WHERE id = ?",
             )
             .bind(title)
             .bind(author)
             .bind(publication_year)
+            .bind(&details.publisher)
+            .bind(&details.language)
+            .bind(&details.description)
+            .bind(details.page_count)
+            .bind(owner_id)
             .bind(&now)
             .bind(&book_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
-
-            Ok(book_id)
+            book_id
         } else {
             // Create new book
             let book_id = uuid::Uuid::new_v4().to_string();
             sqlx::query(
-                "INSERT INTO books (id, title, author, publication_year, filepath, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                "INSERT INTO books (id, title, author, publication_year, publisher, language, description, page_count, filepath, user_id, visibility, created_at, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             )
             .bind(&book_id)
             .bind(title)
             .bind(author)
             .bind(publication_year)
+            .bind(&details.publisher)
+            .bind(&details.language)
+            .bind(&details.description)
+            .bind(details.page_count)
             .bind(filepath)
+            .bind(owner_id)
+            .bind(visibility.as_str())
             .bind(&now)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
+            book_id
+        };
 
-            Ok(book_id)
-        }
+        insert_book_identifiers(&mut tx, &book_id, identifiers).await?;
+        sync_book_authors(&mut tx, &book_id).await?;
+        replace_book_fts(&mut tx, &book_id).await?;
+        tx.commit().await?;
+
+        // The file may have been replaced by one with a cover
+        crate::covers::forget_missing_cover(&book_id);
+
+        Ok(book_id)
     }
 
     pub async fn get_all_books(&self) -> Result<Vec<crate::books::Book>, sqlx::Error> {
-        let rows = sqlx::query(
-            "SELECT id, title, author, publication_year, filepath, notes, created_at FROM books ORDER BY created_at DESC",
-        )
+        let rows = sqlx::query(&format!(
+            "SELECT {BOOK_COLUMNS} FROM books ORDER BY created_at DESC"
+        ))
         .fetch_all(&self.pool)
         .await?;
 
-        let books = rows
-            .into_iter()
-            .map(|row| crate::books::Book {
-                id: row.get("id"),
-                title: row.get("title"),
-                author: row.get("author"),
-                publication_year: row.get("publication_year"),
-                filepath: row.get("filepath"),
-                notes: row.get("notes"),
-                created_at: row.get("created_at"),
-            })
-            .collect();
-
-        Ok(books)
+        Ok(rows.iter().map(book_from_row).collect())
     }
 
-    pub async fn get_book_by_id(
+    /// Every book the viewer (or an anonymous visitor) may see, oldest first.
+    pub async fn get_visible_books(
         &self,
-        book_id: &str,
-    ) -> Result<Option<crate::books::Book>, sqlx::Error> {
-        let row = sqlx::query(
-            "SELECT id, title, author, publication_year, filepath, notes, created_at FROM books WHERE id = ?",
-        )
-        .bind(book_id)
-        .fetch_optional(&self.pool)
+        viewer_id: Option<&str>,
+    ) -> Result<Vec<crate::books::Book>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {BOOK_COLUMNS} FROM books WHERE {} ORDER BY created_at, id",
+            visibility_condition("books")
+        ))
+        .bind(viewer_id)
+        .bind(viewer_id)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(row.map(|row| crate::books::Book {
-            id: row.get("id"),
-            title: row.get("title"),
-            author: row.get("author"),
-            publication_year: row.get("publication_year"),
-            filepath: row.get("filepath"),
-            notes: row.get("notes"),
-            created_at: row.get("created_at"),
-        }))
+        Ok(rows.iter().map(book_from_row).collect())
     }
 
-    pub async fn get_book_count(&self) -> Result<i64, sqlx::Error> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM books")
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(row.get("count"))
-    }
+    /// Books a user may change, which are the ones they own.
+    pub async fn get_editable_books(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<crate::books::Book>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {BOOK_COLUMNS} FROM books WHERE user_id = ? ORDER BY created_at DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
 
-    pub async fn delete_book(&self, book_id: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM books WHERE id = ?")
-            .bind(book_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        Ok(rows.iter().map(book_from_row).collect())
     }
 
-    pub async fn update_book(
+    /// Fetch one page of books using keyset pagination on the requested sort.
+    /// A cursor naming a book that no longer exists starts over at the first page.
+    pub async fn get_books_page(
         &self,
-        book_id: &str,
-        title: &str,
-        author: Option<&str>,
-        publication_year: Option<i32>,
-    ) -> Result<(), sqlx::Error> {
-        let now = chrono::Utc::now().to_rfc3339();
-        sqlx::query(
-            "UPDATE books SET title = ?, author = ?, publication_year = ?, updated_at = ? WHERE id = ?",
-        )
-        .bind(title)
-        .bind(author)
-        .bind(publication_year)
-        .bind(&now)
+        request: &crate::books::BookPageRequest,
+    ) -> Result<crate::books::BookPage, sqlx::Error> {
+        use crate::books::PageCursor;
+
+        let expr = request.sort.sql_expr();
+
+        let mut cursor = request.cursor.as_ref();
+        if let Some(PageCursor::After(id) | PageCursor::Before(id)) = cursor {
+            let exists = sqlx::query("SELECT 1 FROM books WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?
+                .is_some();
+            if !exists {
+                cursor = None;
+            }
+        }
+
+        // Paging backwards walks the list in reverse and flips the rows afterwards
+        let backwards = matches!(cursor, Some(PageCursor::Before(_)));
+        let descending = request.descending != backwards;
+        let (direction, comparison) = if descending {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+
+        // Every condition pushes the values for its placeholders, in order
+        let viewer_id = request.viewer_id.as_deref();
+        let mut conditions = vec![visibility_condition("books")];
+        let mut binds = vec![viewer_id, viewer_id];
+        if request.owned_only {
+            conditions.push("user_id = ?".to_string());
+            binds.push(viewer_id);
+        }
+        if request.notes_only {
+            conditions.push("notes IS NOT NULL".to_string());
+        }
+        if let Some(tag) = &request.tag {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM book_tags bt JOIN tags t ON t.id = bt.tag_id WHERE bt.book_id = books.id AND t.name = ?)"
+                    .to_string(),
+            );
+            binds.push(Some(tag.as_str()));
+        }
+        if let Some(status) = request.status {
+            conditions.push(
+                "(SELECT r.status FROM readings r WHERE r.book_id = books.id AND r.user_id = ? ORDER BY r.created_at DESC, r.rowid DESC LIMIT 1) = ?"
+                    .to_string(),
+            );
+            binds.push(viewer_id);
+            binds.push(Some(status.as_str()));
+        }
+        if let Some(PageCursor::After(id) | PageCursor::Before(id)) = cursor {
+            conditions.push(format!(
+                "({expr}, id) {comparison} ((SELECT {expr} FROM books WHERE id = ?), ?)"
+            ));
+            binds.push(Some(id.as_str()));
+            binds.push(Some(id.as_str()));
+        }
+        let where_clause = conditions.join(" AND ");
+
+        let sql = format!(
+            "SELECT {BOOK_COLUMNS} FROM books WHERE {where_clause} ORDER BY {expr} {direction}, id {direction} LIMIT ?"
+        );
+
+        let mut query = sqlx::query(&sql);
+        for value in binds {
+            query = query.bind(value);
+        }
+        let rows = query.bind(request.limit + 1).fetch_all(&self.pool).await?;
+
+        let has_more = rows.len() as i64 > request.limit;
+        let mut books: Vec<crate::books::Book> = rows
+            .iter()
+            .take(request.limit as usize)
+            .map(book_from_row)
+            .collect();
+
+        if backwards {
+            books.reverse();
+        }
+
+        let (has_prev, has_next) = match cursor {
+            None => (false, has_more),
+            Some(PageCursor::After(_)) => (true, has_more),
+            Some(PageCursor::Before(_)) => (has_more, true),
+        };
+
+        Ok(crate::books::BookPage {
+            books,
+            has_prev,
+            has_next,
+        })
+    }
+
+    pub async fn get_book_by_id(
+        &self,
+        book_id: &str,
+    ) -> Result<Option<crate::books::Book>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {BOOK_COLUMNS} FROM books WHERE id = ?"))
+            .bind(book_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(book_from_row))
+    }
+
+    /// Fetch a book only if the viewer (or an anonymous visitor) may see it.
+    pub async fn get_visible_book(
+        &self,
+        book_id: &str,
+        viewer_id: Option<&str>,
+    ) -> Result<Option<crate::books::Book>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "SELECT {BOOK_COLUMNS} FROM books WHERE id = ? AND {}",
+            visibility_condition("books")
+        ))
         .bind(book_id)
-        .execute(&self.pool)
+        .bind(viewer_id)
+        .bind(viewer_id)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(())
+
+        Ok(row.as_ref().map(book_from_row))
+    }
+
+    /// Ids of visible books by title, matched case-insensitively and keyed by the
+    /// title as given. When titles collide the oldest book wins.
+    pub async fn get_visible_book_ids_by_title(
+        &self,
+        titles: &[String],
+        viewer_id: Option<&str>,
+    ) -> Result<std::collections::HashMap<String, String>, sqlx::Error> {
+        let sql = format!(
+            "SELECT id FROM books WHERE title = ? COLLATE NOCASE AND {} ORDER BY created_at LIMIT 1",
+            visibility_condition("books")
+        );
+
+        let mut book_ids = std::collections::HashMap::new();
+        for title in titles {
+            let row = sqlx::query(&sql)
+                .bind(title.trim())
+                .bind(viewer_id)
+                .bind(viewer_id)
+                .fetch_optional(&self.pool)
+                .await?;
+            if let Some(row) = row {
+                book_ids.insert(title.clone(), row.get("id"));
+            }
+        }
+
+        Ok(book_ids)
+    }
+
+    pub async fn get_owned_book_count(&self, user_id: &str) -> Result<i64, sqlx::Error> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM books WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    /// Set a book's visibility and replace who it is shared with in one
+    /// transaction. Returns the usernames that don't exist.
+    pub async fn update_book_sharing(
+        &self,
+        book_id: &str,
+        visibility: crate::books::BookVisibility,
+        usernames: &[&str],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE books SET visibility = ?, updated_at = ? WHERE id = ?")
+            .bind(visibility.as_str())
+            .bind(&now)
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        let unknown = replace_book_shares(&mut tx, book_id, usernames).await?;
+        tx.commit().await?;
+        Ok(unknown)
+    }
+
+    /// Usernames the book is shared with, alphabetically.
+    pub async fn get_book_share_usernames(
+        &self,
+        book_id: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT u.username FROM book_shares s JOIN users u ON s.user_id = u.id WHERE s.book_id = ? ORDER BY u.username",
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("username")).collect())
+    }
+
+    /// Replace the list of users a book is shared with, keeping its visibility.
+    /// Returns the usernames that do not belong to any account.
+    #[cfg(test)]
+    pub async fn set_book_shares(
+        &self,
+        book_id: &str,
+        usernames: &[&str],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let unknown = replace_book_shares(&mut tx, book_id, usernames).await?;
+        tx.commit().await?;
+        Ok(unknown)
+    }
+
+    pub async fn delete_book(&self, book_id: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM books WHERE id = ?")
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM books_fts WHERE book_id = ?")
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    pub async fn update_book(
+        &self,
+        book_id: &str,
+        title: &str,
+        author: Option<&str>,
+        publication_year: Option<i32>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        update_book_fields(&mut tx, book_id, title, author, publication_year).await?;
+        sync_book_authors(&mut tx, book_id).await?;
+        replace_book_fts(&mut tx, book_id).await?;
+        tx.commit().await
+    }
+
+    /// Save the edit form of a book in one transaction: its fields and details,
+    /// and its identifiers, translators, editors and tags, which replace the
+    /// ones it had.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn edit_book(
+        &self,
+        book_id: &str,
+        title: &str,
+        author: Option<&str>,
+        publication_year: Option<i32>,
+        details: &crate::books::BookDetails,
+        identifiers: &[Identifier],
+        contributors: &[(AuthorRole, Vec<String>)],
+        tags: &[&str],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        update_book_fields(&mut tx, book_id, title, author, publication_year).await?;
+        sqlx::query(
+            "UPDATE books SET publisher = ?, language = ?, description = ?, page_count = ? WHERE id = ?",
+        )
+        .bind(&details.publisher)
+        .bind(&details.language)
+        .bind(&details.description)
+        .bind(details.page_count)
+        .bind(book_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM book_identifiers WHERE book_id = ?")
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_book_identifiers(&mut tx, book_id, identifiers).await?;
+
+        sqlx::query("DELETE FROM book_tags WHERE book_id = ?")
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_book_tags(&mut tx, book_id, tags).await?;
+
+        link_authors_from_field(&mut tx, book_id).await?;
+        for (role, names) in contributors {
+            link_book_authors(&mut tx, book_id, *role, names).await?;
+        }
+        remove_unlinked_authors(&mut tx).await?;
+        replace_book_fts(&mut tx, book_id).await?;
+        tx.commit().await
+    }
+
+    /// Replace a book's fields, notes and visibility in one transaction, keeping
+    /// its identifiers, tags and shares.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn replace_book(
+        &self,
+        book_id: &str,
+        title: &str,
+        author: Option<&str>,
+        publication_year: Option<i32>,
+        notes: Option<&str>,
+        visibility: crate::books::BookVisibility,
+        details: &crate::books::BookDetails,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        update_book_fields(&mut tx, book_id, title, author, publication_year).await?;
+        sqlx::query(
+            "UPDATE books SET notes = ?, visibility = ?, publisher = ?, language = ?, description = ?, page_count = ? WHERE id = ?",
+        )
+        .bind(notes)
+        .bind(visibility.as_str())
+        .bind(&details.publisher)
+        .bind(&details.language)
+        .bind(&details.description)
+        .bind(details.page_count)
+        .bind(book_id)
+        .execute(&mut *tx)
+        .await?;
+        sync_book_authors(&mut tx, book_id).await?;
+        replace_book_fts(&mut tx, book_id).await?;
+        tx.commit().await
     }
 
     pub async fn update_book_notes(
@@ -460,12 +1201,1981 @@ impl Database {
         notes: Option<&str>,
     ) -> Result<(), sqlx::Error> {
         let now = chrono::Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
         sqlx::query("UPDATE books SET notes = ?, updated_at = ? WHERE id = ?")
             .bind(notes)
             .bind(&now)
             .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        replace_book_fts(&mut tx, book_id).await?;
+        tx.commit().await
+    }
+
+    /// Mark a book as changed, eg. after its cover was replaced.
+    pub async fn touch_book(&self, book_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE books SET updated_at = ? WHERE id = ?")
+            .bind(chrono::Utc::now().to_rfc3339())
+            .bind(book_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
+
+    // Tag methods
+    /// Tag names of a book, alphabetically.
+    pub async fn get_book_tags(&self, book_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT t.name FROM book_tags bt JOIN tags t ON t.id = bt.tag_id WHERE bt.book_id = ? ORDER BY t.name",
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("name")).collect())
+    }
+
+    /// Replace the tags of a book, creating tags that don't exist yet.
+    pub async fn set_book_tags(&self, book_id: &str, names: &[&str]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM book_tags WHERE book_id = ?")
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_book_tags(&mut tx, book_id, names).await?;
+
+        tx.commit().await
+    }
+
+    /// Add tags to a book, keeping the ones it already has.
+    pub async fn add_book_tags(&self, book_id: &str, names: &[&str]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        insert_book_tags(&mut tx, book_id, names).await?;
+        tx.commit().await
+    }
+
+    // Identifier methods
+
+    pub async fn get_book_identifiers(
+        &self,
+        book_id: &str,
+    ) -> Result<Vec<Identifier>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT scheme, value FROM book_identifiers WHERE book_id = ? ORDER BY scheme, value",
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Identifier {
+                scheme: row.get("scheme"),
+                value: row.get("value"),
+            })
+            .collect())
+    }
+
+    /// All tags with the number of books the viewer can see, by name.
+    pub async fn get_tag_counts(
+        &self,
+        viewer_id: Option<&str>,
+    ) -> Result<Vec<crate::books::TagCount>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT t.name, COUNT(*) AS count
+             FROM tags t
+             JOIN book_tags bt ON bt.tag_id = t.id
+             JOIN books ON books.id = bt.book_id
+             WHERE {}
+             GROUP BY t.id
+             ORDER BY t.name COLLATE NOCASE",
+            visibility_condition("books")
+        ))
+        .bind(viewer_id)
+        .bind(viewer_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let tags = rows
+            .into_iter()
+            .map(|row| crate::books::TagCount {
+                name: row.get("name"),
+                count: row.get("count"),
+            })
+            .collect();
+
+        Ok(tags)
+    }
+
+    // Reading log methods
+
+    /// A user's read-throughs of a book, the current one first.
+    pub async fn get_readings(
+        &self,
+        user_id: &str,
+        book_id: &str,
+    ) -> Result<Vec<crate::readings::Reading>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, book_id, status, started_on, finished_on, created_at
+             FROM readings
+             WHERE user_id = ? AND book_id = ?
+             ORDER BY created_at DESC, rowid DESC",
+        )
+        .bind(user_id)
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let readings = rows
+            .into_iter()
+            .map(|row| crate::readings::Reading {
+                id: row.get("id"),
+                book_id: row.get("book_id"),
+                status: row.get("status"),
+                started_on: row.get("started_on"),
+                finished_on: row.get("finished_on"),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        Ok(readings)
+    }
+
+    /// Read-throughs of a book by every reader, or only by `user_id` when given,
+    /// oldest first, for exports.
+    pub async fn get_exported_readings(
+        &self,
+        book_id: &str,
+        user_id: Option<&str>,
+    ) -> Result<Vec<crate::export::ExportedReading>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT r.id, u.username, r.status, r.started_on, r.finished_on, r.created_at
+             FROM readings r JOIN users u ON r.user_id = u.id
+             WHERE r.book_id = ? AND (? IS NULL OR r.user_id = ?)
+             ORDER BY r.created_at, r.rowid",
+        )
+        .bind(book_id)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let readings = rows
+            .into_iter()
+            .map(|row| crate::export::ExportedReading {
+                id: row.get("id"),
+                reader: row.get("username"),
+                status: row.get("status"),
+                started_on: row.get("started_on"),
+                finished_on: row.get("finished_on"),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        Ok(readings)
+    }
+
+    pub async fn create_reading(
+        &self,
+        user_id: &str,
+        book_id: &str,
+        status: crate::readings::ReadingStatus,
+        started_on: Option<&str>,
+        finished_on: Option<&str>,
+    ) -> Result<String, sqlx::Error> {
+        let reading_id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query(
+            "INSERT INTO readings (id, user_id, book_id, status, started_on, finished_on, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&reading_id)
+        .bind(user_id)
+        .bind(book_id)
+        .bind(status)
+        .bind(started_on)
+        .bind(finished_on)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(reading_id)
+    }
+
+    /// Returns false when the reading does not exist or belongs to someone else.
+    pub async fn update_reading(
+        &self,
+        user_id: &str,
+        book_id: &str,
+        reading_id: &str,
+        status: crate::readings::ReadingStatus,
+        started_on: Option<&str>,
+        finished_on: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            "UPDATE readings SET status = ?, started_on = ?, finished_on = ?, updated_at = ?
+             WHERE id = ? AND user_id = ? AND book_id = ?",
+        )
+        .bind(status)
+        .bind(started_on)
+        .bind(finished_on)
+        .bind(&now)
+        .bind(reading_id)
+        .bind(user_id)
+        .bind(book_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_reading(
+        &self,
+        user_id: &str,
+        book_id: &str,
+        reading_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM readings WHERE id = ? AND user_id = ? AND book_id = ?")
+                .bind(reading_id)
+                .bind(user_id)
+                .bind(book_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Finished read-throughs of a user, most recent first, undated ones last.
+    pub async fn get_finished_books(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<crate::readings::FinishedBook>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT books.id, books.title, books.author, r.finished_on
+             FROM readings r
+             JOIN books ON books.id = r.book_id
+             WHERE r.user_id = ? AND r.status = 'finished' AND {}
+             ORDER BY r.finished_on IS NULL, r.finished_on DESC, r.created_at DESC",
+            visibility_condition("books")
+        ))
+        .bind(user_id)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let books = rows
+            .into_iter()
+            .map(|row| crate::readings::FinishedBook {
+                book_id: row.get("id"),
+                title: row.get("title"),
+                author: row.get("author"),
+                finished_on: row.get("finished_on"),
+            })
+            .collect();
+
+        Ok(books)
+    }
+
+    // Reading position methods
+
+    /// Where a user left off in the reader.
+    pub async fn get_reading_position(
+        &self,
+        user_id: &str,
+        book_id: &str,
+    ) -> Result<Option<crate::reader::ReadingPosition>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT chapter, progress FROM reading_positions WHERE user_id = ? AND book_id = ?",
+        )
+        .bind(user_id)
+        .bind(book_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| crate::reader::ReadingPosition {
+            chapter: row.get::<i64, _>("chapter").max(0) as usize,
+            progress: row.get("progress"),
+        }))
+    }
+
+    pub async fn save_reading_position(
+        &self,
+        user_id: &str,
+        book_id: &str,
+        position: &crate::reader::ReadingPosition,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO reading_positions (user_id, book_id, chapter, progress, updated_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (user_id, book_id) DO UPDATE SET
+                chapter = excluded.chapter,
+                progress = excluded.progress,
+                updated_at = excluded.updated_at",
+        )
+        .bind(user_id)
+        .bind(book_id)
+        .bind(position.chapter as i64)
+        .bind(position.progress)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Highlight methods
+
+    /// Highlights of a book in their user-defined order.
+    pub async fn get_highlights(
+        &self,
+        book_id: &str,
+    ) -> Result<Vec<crate::highlights::Highlight>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, book_id, quote, location, chapter, comment, position, created_at
+             FROM highlights WHERE book_id = ? ORDER BY position, created_at",
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(highlight_from_row).collect())
+    }
+
+    pub async fn get_highlight(
+        &self,
+        book_id: &str,
+        highlight_id: &str,
+    ) -> Result<Option<crate::highlights::Highlight>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT id, book_id, quote, location, chapter, comment, position, created_at
+             FROM highlights WHERE id = ? AND book_id = ?",
+        )
+        .bind(highlight_id)
+        .bind(book_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(highlight_from_row))
+    }
+
+    /// Append a highlight to the end of a book's list.
+    pub async fn create_highlight(
+        &self,
+        book_id: &str,
+        input: &crate::highlights::HighlightInput<'_>,
+    ) -> Result<String, sqlx::Error> {
+        self.create_highlight_at(book_id, input, None).await
+    }
+
+    /// Append a highlight, dated `created_at` when it was made elsewhere, eg. on a Kindle.
+    pub async fn create_highlight_at(
+        &self,
+        book_id: &str,
+        input: &crate::highlights::HighlightInput<'_>,
+        created_at: Option<&str>,
+    ) -> Result<String, sqlx::Error> {
+        let highlight_id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query(
+            "INSERT INTO highlights (id, book_id, quote, location, chapter, comment, position, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(position), 0) + 1 FROM highlights WHERE book_id = ?), ?, ?)",
+        )
+        .bind(&highlight_id)
+        .bind(book_id)
+        .bind(input.quote)
+        .bind(input.location)
+        .bind(input.chapter)
+        .bind(input.comment)
+        .bind(book_id)
+        .bind(created_at.unwrap_or(&now))
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(highlight_id)
+    }
+
+    /// Whether a book already has a highlight with exactly this quote.
+    pub async fn highlight_exists(&self, book_id: &str, quote: &str) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query("SELECT 1 FROM highlights WHERE book_id = ? AND quote = ? LIMIT 1")
+            .bind(book_id)
+            .bind(quote.trim())
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn update_highlight(
+        &self,
+        book_id: &str,
+        highlight_id: &str,
+        input: &crate::highlights::HighlightInput<'_>,
+    ) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query(
+            "UPDATE highlights SET quote = ?, location = ?, chapter = ?, comment = ?, updated_at = ?
+             WHERE id = ? AND book_id = ?",
+        )
+        .bind(input.quote)
+        .bind(input.location)
+        .bind(input.chapter)
+        .bind(input.comment)
+        .bind(&now)
+        .bind(highlight_id)
+        .bind(book_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_highlight(
+        &self,
+        book_id: &str,
+        highlight_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM highlights WHERE id = ? AND book_id = ?")
+            .bind(highlight_id)
+            .bind(book_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Swap a highlight's position with the previous (`up`) or next one.
+    pub async fn move_highlight(
+        &self,
+        book_id: &str,
+        highlight_id: &str,
+        up: bool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(current) =
+            sqlx::query("SELECT position FROM highlights WHERE id = ? AND book_id = ?")
+                .bind(highlight_id)
+                .bind(book_id)
+                .fetch_optional(&mut *tx)
+                .await?
+        else {
+            return Ok(());
+        };
+        let position: i64 = current.get("position");
+
+        let neighbour_sql = if up {
+            "SELECT id, position FROM highlights WHERE book_id = ? AND position < ? ORDER BY position DESC LIMIT 1"
+        } else {
+            "SELECT id, position FROM highlights WHERE book_id = ? AND position > ? ORDER BY position ASC LIMIT 1"
+        };
+        let Some(neighbour) = sqlx::query(neighbour_sql)
+            .bind(book_id)
+            .bind(position)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(());
+        };
+        let neighbour_id: String = neighbour.get("id");
+        let neighbour_position: i64 = neighbour.get("position");
+
+        sqlx::query("UPDATE highlights SET position = ? WHERE id = ?")
+            .bind(neighbour_position)
+            .bind(highlight_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE highlights SET position = ? WHERE id = ?")
+            .bind(position)
+            .bind(&neighbour_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    // Restore methods
+
+    /// Write restored books, their tags, shares, highlights and reading log in
+    /// one transaction. A book already here, matched by id or else by file path,
+    /// is kept or replaced according to `policy`, and keeps its owner. With
+    /// `editor_id` set, books that user may not edit are always kept, and file
+    /// paths are neither matched nor restored: new books get none and replaced
+    /// books keep theirs, so a crafted export cannot serve any file, eg. the
+    /// database.
+    /// Highlights and readings whose id is already taken by another book are
+    /// skipped, so a crafted export cannot move them off that book.
+    pub async fn restore_books(
+        &self,
+        books: &[crate::export::RestoredBook<'_>],
+        policy: crate::export::ConflictPolicy,
+        editor_id: Option<&str>,
+    ) -> Result<crate::export::RestoreSummary, sqlx::Error> {
+        use crate::export::ConflictPolicy;
+
+        let mut summary = crate::export::RestoreSummary::default();
+        let mut tx = self.pool.begin().await?;
+
+        for restored in books {
+            let book = restored.book;
+            let filepath = book.filepath.as_deref().filter(|_| editor_id.is_none());
+
+            let mut existing =
+                sqlx::query("SELECT id, user_id, updated_at FROM books WHERE id = ?")
+                    .bind(&book.id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if existing.is_none()
+                && let Some(filepath) = filepath
+            {
+                existing =
+                    sqlx::query("SELECT id, user_id, updated_at FROM books WHERE filepath = ?")
+                        .bind(filepath)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+            }
+
+            let book_id = match existing {
+                None => {
+                    sqlx::query(
+                        "INSERT INTO books (id, title, author, publication_year, publisher, language, description, page_count, filepath, notes, user_id, visibility, created_at, updated_at)
+                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind(&book.id)
+                    .bind(&book.title)
+                    .bind(&book.author)
+                    .bind(book.publication_year)
+                    .bind(&book.publisher)
+                    .bind(&book.language)
+                    .bind(&book.description)
+                    .bind(book.page_count)
+                    .bind(filepath)
+                    .bind(&book.notes)
+                    .bind(&restored.owner_id)
+                    .bind(book.visibility.as_str())
+                    .bind(&book.created_at)
+                    .bind(&book.updated_at)
+                    .execute(&mut *tx)
+                    .await?;
+                    summary.created += 1;
+                    book.id.clone()
+                }
+                Some(row) => {
+                    let book_id: String = row.get("id");
+                    let owner_id: Option<String> = row.get("user_id");
+                    let updated_at: String = row.get("updated_at");
+
+                    let editable =
+                        editor_id.is_none_or(|editor_id| owner_id.as_deref() == Some(editor_id));
+                    let replace = editable
+                        && match policy {
+                            ConflictPolicy::Skip => false,
+                            ConflictPolicy::Overwrite => true,
+                            ConflictPolicy::Newest => is_newer(&book.updated_at, &updated_at),
+                        };
+                    if !replace {
+                        summary.skipped += 1;
+                        continue;
+                    }
+
+                    sqlx::query(
+                        "UPDATE books SET title = ?, author = ?, publication_year = ?, publisher = ?, language = ?, description = ?, page_count = ?,
+                         filepath = CASE WHEN ? THEN ? ELSE filepath END, notes = ?, user_id = COALESCE(user_id, ?),
+                         visibility = ?, created_at = ?, updated_at = ?
+                         WHERE id = ?",
+                    )
+                    .bind(&book.title)
+                    .bind(&book.author)
+                    .bind(book.publication_year)
+                    .bind(&book.publisher)
+                    .bind(&book.language)
+                    .bind(&book.description)
+                    .bind(book.page_count)
+                    .bind(editor_id.is_none())
+                    .bind(filepath)
+                    .bind(&book.notes)
+                    .bind(&restored.owner_id)
+                    .bind(book.visibility.as_str())
+                    .bind(&book.created_at)
+                    .bind(&book.updated_at)
+                    .bind(&book_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    for table in [
+                        "book_tags",
+                        "book_identifiers",
+                        "book_authors",
+                        "book_shares",
+                        "highlights",
+                    ] {
+                        sqlx::query(&format!("DELETE FROM {table} WHERE book_id = ?"))
+                            .bind(&book_id)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    summary.updated += 1;
+                    book_id
+                }
+            };
+
+            let tags: Vec<&str> = book.tags.iter().map(String::as_str).collect();
+            insert_book_tags(&mut tx, &book_id, &tags).await?;
+            insert_book_identifiers(&mut tx, &book_id, &book.identifiers).await?;
+
+            let now = chrono::Utc::now().to_rfc3339();
+            for user_id in &restored.shared_with {
+                sqlx::query(
+                    "INSERT OR IGNORE INTO book_shares (book_id, user_id, created_at) VALUES (?, ?, ?)",
+                )
+                .bind(&book_id)
+                .bind(user_id)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            for highlight in &book.highlights {
+                let result = sqlx::query(
+                    "INSERT INTO highlights (id, book_id, quote, location, chapter, comment, position, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                     ON CONFLICT(id) DO UPDATE SET quote = excluded.quote, location = excluded.location,
+                         chapter = excluded.chapter, comment = excluded.comment, position = excluded.position,
+                         created_at = excluded.created_at, updated_at = excluded.updated_at
+                     WHERE highlights.book_id = excluded.book_id",
+                )
+                .bind(&highlight.id)
+                .bind(&book_id)
+                .bind(&highlight.quote)
+                .bind(&highlight.location)
+                .bind(&highlight.chapter)
+                .bind(&highlight.comment)
+                .bind(highlight.position)
+                .bind(&highlight.created_at)
+                .bind(&highlight.created_at)
+                .execute(&mut *tx)
+                .await?;
+                summary.highlights += result.rows_affected() as usize;
+            }
+
+            for (user_id, reading) in &restored.readings {
+                let result = sqlx::query(
+                    "INSERT INTO readings (id, user_id, book_id, status, started_on, finished_on, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                     ON CONFLICT(id) DO UPDATE SET status = excluded.status, started_on = excluded.started_on,
+                         finished_on = excluded.finished_on, created_at = excluded.created_at, updated_at = excluded.updated_at
+                     WHERE readings.book_id = excluded.book_id AND readings.user_id = excluded.user_id",
+                )
+                .bind(&reading.id)
+                .bind(user_id)
+                .bind(&book_id)
+                .bind(reading.status)
+                .bind(&reading.started_on)
+                .bind(&reading.finished_on)
+                .bind(&reading.created_at)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+                summary.readings += result.rows_affected() as usize;
+            }
+
+            link_authors_from_field(&mut tx, &book_id).await?;
+            for (role, names) in [
+                (AuthorRole::Translator, &book.translators),
+                (AuthorRole::Editor, &book.editors),
+            ] {
+                link_book_authors(&mut tx, &book_id, role, names).await?;
+            }
+            replace_book_fts(&mut tx, &book_id).await?;
+        }
+
+        remove_unlinked_authors(&mut tx).await?;
+        tx.commit().await?;
+        Ok(summary)
+    }
+
+    // Author methods
+
+    /// Replace who is credited with a book in a role other than author, eg.
+    /// its translators. Authors come from the book's author field.
+    pub async fn set_book_contributors(
+        &self,
+        book_id: &str,
+        role: AuthorRole,
+        names: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        link_book_authors(&mut tx, book_id, role, names).await?;
+        remove_unlinked_authors(&mut tx).await?;
+        tx.commit().await
+    }
+
+    /// Everyone credited with a book: authors first, in the order given, then
+    /// translators and editors.
+    pub async fn get_book_authors(
+        &self,
+        book_id: &str,
+    ) -> Result<Vec<crate::authors::BookAuthor>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT a.id, a.name, ba.role
+             FROM book_authors ba
+             JOIN authors a ON a.id = ba.author_id
+             WHERE ba.book_id = ?
+             ORDER BY CASE ba.role WHEN 'author' THEN 0 WHEN 'translator' THEN 1 ELSE 2 END, ba.position",
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| crate::authors::BookAuthor {
+                id: row.get("id"),
+                name: row.get("name"),
+                role: row.get("role"),
+            })
+            .collect())
+    }
+
+    pub async fn get_author(
+        &self,
+        author_id: &str,
+    ) -> Result<Option<crate::authors::Author>, sqlx::Error> {
+        let row = sqlx::query("SELECT id, name, created_at FROM authors WHERE id = ?")
+            .bind(author_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| crate::authors::Author {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+        }))
+    }
+
+    /// Authors with the number of books the viewer can see, by name. Authors
+    /// of hidden books only are left out.
+    pub async fn get_author_counts(
+        &self,
+        viewer_id: Option<&str>,
+    ) -> Result<Vec<crate::authors::AuthorCount>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT a.id, a.name, COUNT(DISTINCT ba.book_id) AS count
+             FROM authors a
+             JOIN book_authors ba ON ba.author_id = a.id
+             JOIN books ON books.id = ba.book_id
+             WHERE {}
+             GROUP BY a.id
+             ORDER BY a.name COLLATE NOCASE",
+            visibility_condition("books")
+        ))
+        .bind(viewer_id)
+        .bind(viewer_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| crate::authors::AuthorCount {
+                id: row.get("id"),
+                name: row.get("name"),
+                count: row.get("count"),
+            })
+            .collect())
+    }
+
+    /// The books of an author that the viewer can see, with the author's role in each.
+    pub async fn get_author_books(
+        &self,
+        author_id: &str,
+        viewer_id: Option<&str>,
+    ) -> Result<Vec<crate::authors::AuthorBook>, sqlx::Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {BOOK_COLUMNS}, ba.role
+             FROM book_authors ba
+             JOIN books ON books.id = ba.book_id
+             WHERE ba.author_id = ? AND {}
+             ORDER BY COALESCE(books.publication_year, 9999), books.title COLLATE NOCASE",
+            visibility_condition("books")
+        ))
+        .bind(author_id)
+        .bind(viewer_id)
+        .bind(viewer_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| crate::authors::AuthorBook {
+                book: book_from_row(row),
+                role: row.get("role"),
+            })
+            .collect())
+    }
+
+    /// Whether an author is credited with a book the user may not change,
+    /// including books without an owner.
+    pub async fn author_has_books_of_others(
+        &self,
+        author_id: &str,
+        user_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT EXISTS (
+                 SELECT 1 FROM book_authors ba
+                 JOIN books ON books.id = ba.book_id
+                 WHERE ba.author_id = ? AND books.user_id IS NOT ?
+             ) AS found",
+        )
+        .bind(author_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get("found"))
+    }
+
+    /// Merge a duplicate author into another. Their books are credited to the
+    /// remaining author, whose name replaces the duplicate's in author fields.
+    /// With `keep_alias` the duplicate's name, and the aliases it had, are kept
+    /// for the remaining author so rescanned files naming it link there too.
+    /// Aliases apply to every user's books, so otherwise they are dropped.
+    pub async fn merge_authors(
+        &self,
+        from_id: &str,
+        into_id: &str,
+        keep_alias: bool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let book_ids: Vec<String> =
+            sqlx::query("SELECT DISTINCT book_id FROM book_authors WHERE author_id = ?")
+                .bind(from_id)
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|row| row.get("book_id"))
+                .collect();
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO book_authors (book_id, author_id, role, position)
+             SELECT book_id, ?, role, position FROM book_authors WHERE author_id = ?",
+        )
+        .bind(into_id)
+        .bind(from_id)
+        .execute(&mut *tx)
+        .await?;
+        if keep_alias {
+            sqlx::query("UPDATE author_aliases SET author_id = ? WHERE author_id = ?")
+                .bind(into_id)
+                .bind(from_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT OR REPLACE INTO author_aliases (name, author_id) SELECT name, ? FROM authors WHERE id = ?",
+            )
+            .bind(into_id)
+            .bind(from_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("DELETE FROM authors WHERE id = ?")
+            .bind(from_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let now = chrono::Utc::now().to_rfc3339();
+        for book_id in &book_ids {
+            let names: Vec<String> = sqlx::query(
+                "SELECT a.name FROM book_authors ba JOIN authors a ON a.id = ba.author_id
+                 WHERE ba.book_id = ? AND ba.role = 'author' ORDER BY ba.position",
+            )
+            .bind(book_id)
+            .fetch_all(&mut *tx)
+            .await?
+            .iter()
+            .map(|row| row.get("name"))
+            .collect();
+            if names.is_empty() {
+                continue;
+            }
+
+            sqlx::query("UPDATE books SET author = ?, updated_at = ? WHERE id = ?")
+                .bind(join_author_names(&names))
+                .bind(&now)
+                .bind(book_id)
+                .execute(&mut *tx)
+                .await?;
+            replace_book_fts(&mut tx, book_id).await?;
+        }
+
+        tx.commit().await
+    }
+
+    // Full-text search methods
+
+    /// Search titles, authors and notes, best matches first.
+    /// Matched terms are wrapped in `HIGHLIGHT_START`/`HIGHLIGHT_END` markers.
+    pub async fn search_books(
+        &self,
+        query: &str,
+        viewer_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<crate::books::SearchResult>, sqlx::Error> {
+        let Some(match_query) = fts_match_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let rows = sqlx::query(&format!(
+            "SELECT {BOOK_COLUMNS}, f.title_highlight, f.author_highlight, f.notes_snippet
+             FROM (
+                 SELECT book_id,
+                        highlight(books_fts, 1, ?, ?) AS title_highlight,
+                        highlight(books_fts, 2, ?, ?) AS author_highlight,
+                        snippet(books_fts, 3, ?, ?, '…', 24) AS notes_snippet,
+                        bm25(books_fts, 0.0, 10.0, 5.0, 1.0) AS score
+                 FROM books_fts
+                 WHERE books_fts MATCH ?
+             ) f
+             JOIN books ON books.id = f.book_id
+             WHERE {}
+             ORDER BY f.score
+             LIMIT ?",
+            visibility_condition("books")
+        ))
+        .bind(HIGHLIGHT_START)
+        .bind(HIGHLIGHT_END)
+        .bind(HIGHLIGHT_START)
+        .bind(HIGHLIGHT_END)
+        .bind(HIGHLIGHT_START)
+        .bind(HIGHLIGHT_END)
+        .bind(&match_query)
+        .bind(viewer_id)
+        .bind(viewer_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let results = rows
+            .iter()
+            .map(|row| crate::books::SearchResult {
+                title_highlight: row.get("title_highlight"),
+                author_highlight: row.get("author_highlight"),
+                notes_snippet: row.get("notes_snippet"),
+                book: book_from_row(row),
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+/// Run migrations with foreign keys off, as SQLite requires when a migration
+/// rebuilds a table: dropping the old table would otherwise delete the rows
+/// referencing it. Foreign keys can only be switched outside a transaction,
+/// so this is done on the connection the migrations run on.
+async fn without_foreign_keys<T>(
+    conn: &mut sqlx::pool::PoolConnection<Sqlite>,
+    migrate: impl AsyncFnOnce(&mut sqlx::SqliteConnection) -> Result<T, sqlx::migrate::MigrateError>,
+) -> Result<T, sqlx::migrate::MigrateError> {
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut **conn)
+        .await?;
+    let result = migrate(&mut **conn).await;
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut **conn)
+        .await?;
+    result
+}
+
+/// Replace who a book is shared with, by username. Returns the usernames that
+/// don't exist.
+async fn replace_book_shares(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+    usernames: &[&str],
+) -> Result<Vec<String>, sqlx::Error> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut unknown = Vec::new();
+
+    sqlx::query("DELETE FROM book_shares WHERE book_id = ?")
+        .bind(book_id)
+        .execute(&mut **tx)
+        .await?;
+
+    for username in usernames {
+        let user = sqlx::query("SELECT id FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        let Some(user) = user else {
+            unknown.push(username.to_string());
+            continue;
+        };
+
+        let user_id: String = user.get("id");
+        sqlx::query(
+            "INSERT OR IGNORE INTO book_shares (book_id, user_id, created_at) VALUES (?, ?, ?)",
+        )
+        .bind(book_id)
+        .bind(&user_id)
+        .bind(&now)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(unknown)
+}
+
+/// Set a book's title, author and year, and mark it as changed.
+async fn update_book_fields(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+    title: &str,
+    author: Option<&str>,
+    publication_year: Option<i32>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE books SET title = ?, author = ?, publication_year = ?, updated_at = ? WHERE id = ?",
+    )
+    .bind(title)
+    .bind(author)
+    .bind(publication_year)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .bind(book_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Replace the full-text index entry of a book with its current row, in the
+/// transaction that changed the row.
+async fn replace_book_fts(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM books_fts WHERE book_id = ?")
+        .bind(book_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO books_fts (book_id, title, author, notes) SELECT id, title, COALESCE(author, ''), COALESCE(notes, '') FROM books WHERE id = ?",
+    )
+    .bind(book_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Whether RFC 3339 timestamp `a` is later than `b`, comparing the text when either does not parse.
+fn is_newer(a: &str, b: &str) -> bool {
+    match (
+        chrono::DateTime::parse_from_rfc3339(a),
+        chrono::DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a > b,
+        _ => a > b,
+    }
+}
+
+async fn insert_book_tags(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+    names: &[&str],
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().to_rfc3339();
+
+    for name in names {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO tags (id, name, created_at) VALUES (?, ?, ?)")
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(name)
+            .bind(&now)
+            .execute(&mut **tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO book_tags (book_id, tag_id) SELECT ?, id FROM tags WHERE name = ?",
+        )
+        .bind(book_id)
+        .bind(name)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Link a book to the authors named in its author field, forgetting authors
+/// it no longer names.
+async fn sync_book_authors(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+) -> Result<(), sqlx::Error> {
+    link_authors_from_field(tx, book_id).await?;
+    remove_unlinked_authors(tx).await
+}
+
+/// Link a book to the authors named in its author field.
+async fn link_authors_from_field(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+) -> Result<(), sqlx::Error> {
+    let author: Option<String> = sqlx::query("SELECT author FROM books WHERE id = ?")
+        .bind(book_id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .and_then(|row| row.get("author"));
+    let names = author
+        .as_deref()
+        .map(split_author_names)
+        .unwrap_or_default();
+    link_book_authors(tx, book_id, AuthorRole::Author, &names).await
+}
+
+/// Replace the authors credited with a book in one role, by name. Names are
+/// matched without regard to case, and through the aliases left by merges;
+/// unknown names become new authors.
+async fn link_book_authors(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+    role: AuthorRole,
+    names: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM book_authors WHERE book_id = ? AND role = ?")
+        .bind(book_id)
+        .bind(role.as_str())
+        .execute(&mut **tx)
+        .await?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    for (position, name) in names.iter().enumerate() {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        let known = sqlx::query(
+            "SELECT author_id AS id FROM author_aliases WHERE name = ?
+             UNION ALL SELECT id FROM authors WHERE name = ?
+             LIMIT 1",
+        )
+        .bind(name)
+        .bind(name)
+        .fetch_optional(&mut **tx)
+        .await?;
+        let author_id: String = match known {
+            Some(row) => row.get("id"),
+            None => {
+                let author_id = uuid::Uuid::new_v4().to_string();
+                sqlx::query("INSERT INTO authors (id, name, created_at) VALUES (?, ?, ?)")
+                    .bind(&author_id)
+                    .bind(name)
+                    .bind(&now)
+                    .execute(&mut **tx)
+                    .await?;
+                author_id
+            }
+        };
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO book_authors (book_id, author_id, role, position) VALUES (?, ?, ?, ?)",
+        )
+        .bind(book_id)
+        .bind(&author_id)
+        .bind(role.as_str())
+        .bind(position as i64)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Forget authors no longer credited with any book, unless a merge left an
+/// alias pointing at them.
+async fn remove_unlinked_authors(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM authors
+         WHERE NOT EXISTS (SELECT 1 FROM book_authors WHERE author_id = authors.id)
+           AND NOT EXISTS (SELECT 1 FROM author_aliases WHERE author_id = authors.id)",
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn insert_book_identifiers(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    book_id: &str,
+    identifiers: &[Identifier],
+) -> Result<(), sqlx::Error> {
+    for identifier in identifiers {
+        if identifier.scheme.is_empty() || identifier.value.is_empty() {
+            continue;
+        }
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO book_identifiers (book_id, scheme, value) VALUES (?, ?, ?)",
+        )
+        .bind(book_id)
+        .bind(&identifier.scheme)
+        .bind(&identifier.value)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Columns selected for every `Book`, in the order `book_from_row` expects.
+const BOOK_COLUMNS: &str = "books.id, books.title, books.author, books.publication_year, books.publisher, books.language, books.description, books.page_count, books.filepath, books.notes, books.user_id, books.visibility, books.created_at, books.updated_at";
+
+fn book_from_row(row: &SqliteRow) -> crate::books::Book {
+    crate::books::Book {
+        id: row.get("id"),
+        title: row.get("title"),
+        author: row.get("author"),
+        publication_year: row.get("publication_year"),
+        publisher: row.get("publisher"),
+        language: row.get("language"),
+        description: row.get("description"),
+        page_count: row.get("page_count"),
+        filepath: row.get("filepath"),
+        notes: row.get("notes"),
+        user_id: row.get("user_id"),
+        visibility: row.get("visibility"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn highlight_from_row(row: &SqliteRow) -> crate::highlights::Highlight {
+    crate::highlights::Highlight {
+        id: row.get("id"),
+        book_id: row.get("book_id"),
+        quote: row.get("quote"),
+        location: row.get("location"),
+        chapter: row.get("chapter"),
+        comment: row.get("comment"),
+        position: row.get("position"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// SQL condition restricting `table` to books the viewer may see.
+/// Binds the viewer id twice. Owners see their own books whatever their
+/// visibility; a NULL viewer only sees public books.
+fn visibility_condition(table: &str) -> String {
+    format!(
+        "({table}.visibility = 'public' OR {table}.user_id = ? \
+         OR ({table}.visibility = 'shared' AND EXISTS \
+         (SELECT 1 FROM book_shares s WHERE s.book_id = {table}.id AND s.user_id = ?)))"
+    )
+}
+
+/// API tokens are long random strings, so a plain SHA-256 is enough to
+/// avoid storing them in the clear while keeping lookups cheap.
+fn hash_api_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Marker placed before a matched term in search highlights and snippets.
+pub const HIGHLIGHT_START: &str = "\u{2}";
+/// Marker placed after a matched term in search highlights and snippets.
+pub const HIGHLIGHT_END: &str = "\u{3}";
+
+/// Turn free user input into an FTS5 query: every word is quoted (so FTS
+/// syntax characters are taken literally) and matched as a prefix.
+fn fts_match_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{term}\"*"))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::books::{BookDetails, BookVisibility};
+
+    /// A migrated database in a temporary directory, deleted with the directory.
+    pub(crate) async fn test_database() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}", dir.path().join("alaya.db").display());
+        let db = Database::new(&url).await.unwrap();
+        db.run_migrations().await.unwrap();
+        (dir, db)
+    }
+
+    pub(crate) async fn add_book(
+        db: &Database,
+        title: &str,
+        author: Option<&str>,
+        owner_id: Option<&str>,
+        visibility: BookVisibility,
+    ) -> String {
+        db.create_book(
+            title,
+            author,
+            None,
+            None,
+            owner_id,
+            visibility,
+            &BookDetails::default(),
+            &[],
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Revert migrations until `version` is the last one applied.
+    pub(crate) async fn revert_to(db: &Database, version: i64) {
+        while let Some(last) = db
+            .migration_status()
+            .await
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|migration| migration.applied_at.is_some())
+        {
+            if last.version <= version {
+                break;
+            }
+            db.revert_last_migration().await.unwrap();
+        }
+    }
+
+    async fn has_table(db: &Database, name: &str) -> bool {
+        sqlx::query("SELECT 1 FROM sqlite_master WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&db.pool)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    async fn search_titles(db: &Database, query: &str) -> Vec<String> {
+        db.search_books(query, None, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|result| result.book.title)
+            .collect()
+    }
+
+    #[test]
+    fn fts_match_query_quotes_terms() {
+        assert_eq!(
+            fts_match_query("invisible \"cit-ies"),
+            Some("\"invisible\"* \"cit-ies\"*".to_string())
+        );
+        assert_eq!(fts_match_query(" \" "), None);
+    }
+
+    #[tokio::test]
+    async fn search_follows_book_changes() {
+        let (_dir, db) = test_database().await;
+        let book_id = add_book(
+            &db,
+            "Invisible Cities",
+            Some("Italo Calvino"),
+            None,
+            BookVisibility::Public,
+        )
+        .await;
+        db.update_book_notes(&book_id, Some("Marco Polo talks to Kublai Khan"))
+            .await
+            .unwrap();
+
+        assert_eq!(search_titles(&db, "invis").await, ["Invisible Cities"]);
+        assert_eq!(search_titles(&db, "calvino").await, ["Invisible Cities"]);
+        let results = db.search_books("kublai", None, 10).await.unwrap();
+        assert!(results[0].notes_matched());
+
+        db.update_book(&book_id, "Le città invisibili", Some("Italo Calvino"), None)
+            .await
+            .unwrap();
+        db.update_book_notes(&book_id, None).await.unwrap();
+        assert!(search_titles(&db, "kublai").await.is_empty());
+        assert_eq!(search_titles(&db, "citta").await, ["Le città invisibili"]);
+
+        db.delete_book(&book_id).await.unwrap();
+        assert!(search_titles(&db, "calvino").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fts_migration_round_trip() {
+        let (_dir, db) = test_database().await;
+        add_book(
+            &db,
+            "Invisible Cities",
+            Some("Italo Calvino"),
+            None,
+            BookVisibility::Public,
+        )
+        .await;
+
+        revert_to(&db, 7).await;
+        assert!(!has_table(&db, "books_fts").await);
+
+        db.run_migrations().await.unwrap();
+        assert_eq!(search_titles(&db, "calvino").await, ["Invisible Cities"]);
+    }
+
+    async fn author_names(db: &Database, book_id: &str) -> Vec<String> {
+        db.get_book_authors(book_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|author| author.name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn authors_migration_splits_like_split_author_names() {
+        let (_dir, db) = test_database().await;
+        let mut books = Vec::new();
+        for author in [
+            "de la Cruz, Juana Inés",
+            "Calvino, Italo",
+            "Italo Calvino, William Weaver",
+            "Italo Calvino, italo calvino, William Weaver",
+        ] {
+            let book_id = add_book(&db, author, Some(author), None, BookVisibility::Public).await;
+            books.push((book_id, split_author_names(author)));
+        }
+
+        revert_to(&db, 17).await;
+        assert!(!has_table(&db, "authors").await);
+
+        db.run_migrations().await.unwrap();
+        for (book_id, names) in books {
+            assert_eq!(author_names(&db, &book_id).await, names);
+        }
+    }
+
+    fn page_request(
+        descending: bool,
+        cursor: Option<crate::books::PageCursor>,
+    ) -> crate::books::BookPageRequest {
+        crate::books::BookPageRequest {
+            viewer_id: None,
+            sort: crate::books::BookSort::Title,
+            descending,
+            owned_only: false,
+            notes_only: false,
+            tag: None,
+            status: None,
+            cursor,
+            limit: 2,
+        }
+    }
+
+    /// Titles of a page, with whether it links to a previous and a next page.
+    async fn page_titles(
+        db: &Database,
+        request: &crate::books::BookPageRequest,
+    ) -> (Vec<String>, bool, bool) {
+        let page = db.get_books_page(request).await.unwrap();
+        let titles = page.books.into_iter().map(|book| book.title).collect();
+        (titles, page.has_prev, page.has_next)
+    }
+
+    /// Add public books titled A to E, returning their ids by title.
+    async fn add_lettered_books(db: &Database) -> std::collections::HashMap<String, String> {
+        let mut ids = std::collections::HashMap::new();
+        for title in ["C", "a", "E", "b", "D"] {
+            let id = add_book(db, title, None, None, BookVisibility::Public).await;
+            ids.insert(title.to_uppercase(), id);
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn books_page_ascending() {
+        use crate::books::PageCursor::{After, Before};
+
+        let (_dir, db) = test_database().await;
+        let ids = add_lettered_books(&db).await;
+
+        let first = page_titles(&db, &page_request(false, None)).await;
+        assert_eq!(first, (vec!["a".into(), "b".into()], false, true));
+        let second = page_request(false, Some(After(ids["B"].clone())));
+        assert_eq!(
+            page_titles(&db, &second).await,
+            (vec!["C".into(), "D".into()], true, true)
+        );
+        let last = page_request(false, Some(After(ids["D"].clone())));
+        assert_eq!(
+            page_titles(&db, &last).await,
+            (vec!["E".into()], true, false)
+        );
+        let back = page_request(false, Some(Before(ids["C"].clone())));
+        assert_eq!(
+            page_titles(&db, &back).await,
+            (vec!["a".into(), "b".into()], false, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn books_page_descending() {
+        use crate::books::PageCursor::{After, Before};
+
+        let (_dir, db) = test_database().await;
+        let ids = add_lettered_books(&db).await;
+
+        let first = page_titles(&db, &page_request(true, None)).await;
+        assert_eq!(first, (vec!["E".into(), "D".into()], false, true));
+        let second = page_request(true, Some(After(ids["D"].clone())));
+        assert_eq!(
+            page_titles(&db, &second).await,
+            (vec!["C".into(), "b".into()], true, true)
+        );
+        let last = page_request(true, Some(After(ids["B"].clone())));
+        assert_eq!(
+            page_titles(&db, &last).await,
+            (vec!["a".into()], true, false)
+        );
+        let back = page_request(true, Some(Before(ids["C"].clone())));
+        assert_eq!(
+            page_titles(&db, &back).await,
+            (vec!["E".into(), "D".into()], false, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn tags_are_trimmed_and_shared_without_regard_to_case() {
+        let (_dir, db) = test_database().await;
+        let first = add_book(&db, "Ficciones", None, None, BookVisibility::Public).await;
+        let second = add_book(&db, "Aleph", None, None, BookVisibility::Public).await;
+
+        db.add_book_tags(&first, &[" Fiction ", "fiction", "", "Argentina"])
+            .await
+            .unwrap();
+        db.add_book_tags(&first, &["ARGENTINA", "Stories"])
+            .await
+            .unwrap();
+        db.add_book_tags(&second, &["FICTION"]).await.unwrap();
+
+        assert_eq!(
+            db.get_book_tags(&first).await.unwrap(),
+            ["Argentina", "Fiction", "Stories"]
+        );
+        assert_eq!(db.get_book_tags(&second).await.unwrap(), ["Fiction"]);
+
+        db.set_book_tags(&first, &["stories"]).await.unwrap();
+        assert_eq!(db.get_book_tags(&first).await.unwrap(), ["Stories"]);
+    }
+
+    #[tokio::test]
+    async fn books_page_filtered_by_tag() {
+        use crate::books::PageCursor::{After, Before};
+
+        let (_dir, db) = test_database().await;
+        let ids = add_lettered_books(&db).await;
+        for title in ["A", "C", "D", "E"] {
+            db.add_book_tags(&ids[title], &["Fiction"]).await.unwrap();
+        }
+        let tagged = |cursor| crate::books::BookPageRequest {
+            tag: Some("fiction".to_string()),
+            ..page_request(false, cursor)
+        };
+
+        assert_eq!(
+            page_titles(&db, &tagged(None)).await,
+            (vec!["a".into(), "C".into()], false, true)
+        );
+        assert_eq!(
+            page_titles(&db, &tagged(Some(After(ids["C"].clone())))).await,
+            (vec!["D".into(), "E".into()], true, false)
+        );
+        assert_eq!(
+            page_titles(&db, &tagged(Some(Before(ids["D"].clone())))).await,
+            (vec!["a".into(), "C".into()], false, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn books_page_with_deleted_cursor_starts_over() {
+        use crate::books::PageCursor::{After, Before};
+
+        let (_dir, db) = test_database().await;
+        let ids = add_lettered_books(&db).await;
+        db.delete_book(&ids["C"]).await.unwrap();
+
+        for cursor in [After(ids["C"].clone()), Before(ids["C"].clone())] {
+            assert_eq!(
+                page_titles(&db, &page_request(false, Some(cursor))).await,
+                (vec!["a".into(), "b".into()], false, true)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn api_tokens_are_stored_hashed_and_revoked_by_their_owner() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("owner", "secret").await.unwrap();
+        let other = db.create_user("other", "secret").await.unwrap();
+        let token = db.create_api_token(&owner, "script").await.unwrap();
+
+        let stored: String = sqlx::query("SELECT token_hash FROM api_tokens")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+            .get("token_hash");
+        assert_ne!(stored, token);
+        assert_eq!(stored, hash_api_token(&token));
+
+        let user = db.validate_api_token(&token).await.unwrap().unwrap();
+        assert_eq!(user.id, owner);
+        assert!(
+            db.validate_api_token("alaya_unknown")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let token_id = db.get_api_tokens(&owner).await.unwrap()[0].id.clone();
+        db.delete_api_token(&other, &token_id).await.unwrap();
+        assert!(db.validate_api_token(&token).await.unwrap().is_some());
+        db.delete_api_token(&owner, &token_id).await.unwrap();
+        assert!(db.validate_api_token(&token).await.unwrap().is_none());
+    }
+
+    async fn visible_titles(db: &Database, viewer_id: Option<&str>) -> Vec<String> {
+        let mut titles: Vec<String> = db
+            .get_visible_books(viewer_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|book| book.title)
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    #[tokio::test]
+    async fn books_are_visible_by_owner_share_and_visibility() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("owner", "secret").await.unwrap();
+        let friend = db.create_user("friend", "secret").await.unwrap();
+        let stranger = db.create_user("stranger", "secret").await.unwrap();
+
+        let private = add_book(&db, "Private", None, Some(&owner), BookVisibility::Private).await;
+        let shared = add_book(&db, "Shared", None, Some(&owner), BookVisibility::Shared).await;
+        add_book(&db, "Public", None, Some(&owner), BookVisibility::Public).await;
+        add_book(&db, "Unowned", None, None, BookVisibility::Private).await;
+        db.set_book_shares(&shared, &["friend"]).await.unwrap();
+
+        assert_eq!(visible_titles(&db, None).await, ["Public"]);
+        assert_eq!(
+            visible_titles(&db, Some(&owner)).await,
+            ["Private", "Public", "Shared"]
+        );
+        assert_eq!(
+            visible_titles(&db, Some(&friend)).await,
+            ["Public", "Shared"]
+        );
+        assert_eq!(visible_titles(&db, Some(&stranger)).await, ["Public"]);
+
+        assert!(
+            db.get_visible_book(&private, Some(&friend))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            db.get_visible_book(&shared, Some(&stranger))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            db.search_books("private", Some(&friend), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            db.search_books("shared", Some(&friend), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn only_owners_may_edit_books() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("owner", "secret").await.unwrap();
+        let friend = db.create_user("friend", "secret").await.unwrap();
+
+        let shared = add_book(&db, "Shared", None, Some(&owner), BookVisibility::Shared).await;
+        let unowned = add_book(&db, "Unowned", None, None, BookVisibility::Public).await;
+        db.set_book_shares(&shared, &["friend"]).await.unwrap();
+
+        let editable: Vec<String> = db
+            .get_editable_books(&owner)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|book| book.id)
+            .collect();
+        assert_eq!(editable, [shared.as_str()]);
+        assert!(db.get_editable_books(&friend).await.unwrap().is_empty());
+
+        let shared = db.get_book_by_id(&shared).await.unwrap().unwrap();
+        assert!(shared.can_edit(&owner));
+        assert!(!shared.can_edit(&friend));
+        let unowned = db.get_book_by_id(&unowned).await.unwrap().unwrap();
+        assert!(!unowned.can_edit(&owner));
+    }
+
+    #[tokio::test]
+    async fn book_edits_are_saved_together() {
+        let (_dir, db) = test_database().await;
+        let book_id = add_book(
+            &db,
+            "Invisible Cities",
+            Some("Italo Calvino"),
+            None,
+            BookVisibility::Public,
+        )
+        .await;
+        let details = BookDetails {
+            publisher: Some("Einaudi".to_string()),
+            ..BookDetails::default()
+        };
+        let contributors = [(AuthorRole::Translator, vec!["William Weaver".to_string()])];
+
+        db.edit_book(
+            &book_id,
+            "Le città invisibili",
+            Some("Italo Calvino"),
+            Some(1972),
+            &details,
+            &[Identifier::new("isbn", "9788806123456")],
+            &contributors,
+            &["fiction"],
+        )
+        .await
+        .unwrap();
+        let book = db.get_book_by_id(&book_id).await.unwrap().unwrap();
+        assert_eq!(book.title, "Le città invisibili");
+        assert_eq!(book.publisher.as_deref(), Some("Einaudi"));
+        assert_eq!(db.get_book_tags(&book_id).await.unwrap(), ["fiction"]);
+        assert_eq!(db.get_book_identifiers(&book_id).await.unwrap().len(), 1);
+        let names: Vec<String> = db
+            .get_book_authors(&book_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|author| author.name)
+            .collect();
+        assert_eq!(names, ["Italo Calvino", "William Weaver"]);
+
+        // Failing at the tags must undo the fields and identifiers written before them
+        sqlx::query("DROP TABLE book_tags")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let edited = db
+            .edit_book(
+                &book_id,
+                "Invisible Cities",
+                None,
+                None,
+                &BookDetails::default(),
+                &[],
+                &[(AuthorRole::Translator, Vec::new())],
+                &["fiction"],
+            )
+            .await;
+        assert!(edited.is_err());
+        let book = db.get_book_by_id(&book_id).await.unwrap().unwrap();
+        assert_eq!(book.title, "Le città invisibili");
+        assert_eq!(book.publisher.as_deref(), Some("Einaudi"));
+        assert_eq!(db.get_book_identifiers(&book_id).await.unwrap().len(), 1);
+        assert_eq!(db.get_book_authors(&book_id).await.unwrap().len(), 2);
+    }
+
+    async fn set_session_times(db: &Database, token: &str, last_seen_at: &str, expires_at: &str) {
+        sqlx::query("UPDATE sessions SET last_seen_at = ?, expires_at = ? WHERE token = ?")
+            .bind(last_seen_at)
+            .bind(expires_at)
+            .bind(token)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sessions_expire_slide_and_rotate() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let token = db.create_session(&user_id, Some("test")).await.unwrap();
+        assert!(db.validate_session(&token).await.unwrap().is_some());
+
+        // Renewed at most once per interval
+        assert!(!db.touch_session(&token).await.unwrap());
+        let hour_ago = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+        let soon = (chrono::Utc::now() + chrono::Duration::minutes(1)).to_rfc3339();
+        set_session_times(&db, &token, &hour_ago, &soon).await;
+        assert!(db.touch_session(&token).await.unwrap());
+
+        let rotated = db.rotate_session(&token).await.unwrap().unwrap();
+        assert!(db.validate_session(&token).await.unwrap().is_none());
+        assert!(db.validate_session(&rotated).await.unwrap().is_some());
+        assert!(db.rotate_session(&token).await.unwrap().is_none());
+
+        set_session_times(&db, &rotated, &hour_ago, &hour_ago).await;
+        assert!(db.validate_session(&rotated).await.unwrap().is_none());
+        assert!(!db.touch_session(&rotated).await.unwrap());
+        assert_eq!(db.delete_expired_sessions().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn log_out_everywhere_keeps_the_current_session() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let other_id = db.create_user("other", "secret").await.unwrap();
+        let current = db.create_session(&user_id, None).await.unwrap();
+        let elsewhere = db.create_session(&user_id, None).await.unwrap();
+        let others = db.create_session(&other_id, None).await.unwrap();
+
+        let sessions = db.get_sessions(&user_id, Some(&current)).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.iter().filter(|s| s.is_current).count(), 1);
+
+        db.delete_user_sessions(&user_id, Some(&current))
+            .await
+            .unwrap();
+        assert!(db.validate_session(&current).await.unwrap().is_some());
+        assert!(db.validate_session(&elsewhere).await.unwrap().is_none());
+        assert!(db.validate_session(&others).await.unwrap().is_some());
+
+        db.delete_user_sessions(&user_id, None).await.unwrap();
+        assert!(db.validate_session(&current).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn session_expiry_migration_round_trip() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let token = db.create_session(&user_id, None).await.unwrap();
+
+        revert_to(&db, 11).await;
+        let columns: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info('sessions')")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get("name"))
+            .collect();
+        assert!(!columns.iter().any(|column| column == "expires_at"));
+
+        // Sessions from before expiry was tracked get a fresh week
+        db.run_migrations().await.unwrap();
+        assert!(db.validate_session(&token).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn every_migration_reverts_and_reapplies() {
+        let (_dir, db) = test_database().await;
+        let status = db.migration_status().await.unwrap();
+        assert!(status.iter().all(|m| m.applied_at.is_some() && !m.modified));
+
+        revert_to(&db, 0).await;
+        let tables: Vec<String> = sqlx::query(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.get("name"))
+        .collect();
+        assert_eq!(tables, ["_sqlx_migrations"]);
+        assert!(
+            db.migration_status()
+                .await
+                .unwrap()
+                .iter()
+                .all(|m| m.applied_at.is_none())
+        );
+
+        db.run_migrations().await.unwrap();
+        assert_eq!(db.migration_status().await.unwrap().len(), status.len());
+        add_book(&db, "Invisible Cities", None, None, BookVisibility::Public).await;
+    }
+
+    #[tokio::test]
+    async fn edited_migrations_are_refused() {
+        let (_dir, db) = test_database().await;
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 1")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let status = db.migration_status().await.unwrap();
+        assert!(status[0].modified);
+        assert!(!status[1].modified);
+        assert!(db.run_migrations().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn legacy_migration_table_is_adopted() {
+        let (_dir, db) = test_database().await;
+        revert_to(&db, 2).await;
+        sqlx::query("DELETE FROM _sqlx_migrations")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE _migrations (filename TEXT, executed_at TEXT)")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        for filename in [
+            "001_create_users_table.sql",
+            "002_create_sessions_table.sql",
+        ] {
+            sqlx::query("INSERT INTO _migrations VALUES (?, '2024-01-01T00:00:00+00:00')")
+                .bind(filename)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        db.run_migrations().await.unwrap();
+        assert!(!has_table(&db, "_migrations").await);
+        let status = db.migration_status().await.unwrap();
+        assert_eq!(
+            status[0].applied_at.as_deref(),
+            Some("2024-01-01T00:00:00+00:00")
+        );
+        assert!(status.iter().all(|m| m.applied_at.is_some()));
+    }
 }
@@ -0,0 +1,713 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use crate::AppState;
+use crate::auth::{User, current_user};
+use crate::authors::AuthorRole;
+use crate::books::BookVisibility;
+use crate::database::Database;
+use crate::highlights::Highlight;
+use crate::metadata::Identifier;
+use crate::readings::ReadingStatus;
+
+type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Bumped whenever the JSON layout changes in a way older readers cannot follow.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// File names in the Markdown zip are cut to this many characters before the extension.
+const MAX_FILENAME_CHARS: usize = 100;
+
+/// A whole library as written by the JSON export, and read back by the restore.
+#[derive(Serialize, Deserialize)]
+pub struct LibraryExport {
+    pub version: u32,
+    pub exported_at: String,
+    pub books: Vec<ExportedBook>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedBook {
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub publication_year: Option<i32>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub page_count: Option<i64>,
+    #[serde(default)]
+    pub identifiers: Vec<Identifier>,
+    #[serde(default)]
+    pub translators: Vec<String>,
+    #[serde(default)]
+    pub editors: Vec<String>,
+    pub filepath: Option<String>,
+    pub notes: Option<String>,
+    pub visibility: BookVisibility,
+    /// Username of the owner; None for books that belong to everyone.
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub shared_with: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub highlights: Vec<ExportedHighlight>,
+    #[serde(default)]
+    pub readings: Vec<ExportedReading>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedHighlight {
+    pub id: String,
+    pub quote: String,
+    pub location: Option<String>,
+    pub chapter: Option<String>,
+    pub comment: Option<String>,
+    pub position: i64,
+    pub created_at: String,
+}
+
+impl From<Highlight> for ExportedHighlight {
+    fn from(highlight: Highlight) -> Self {
+        ExportedHighlight {
+            id: highlight.id,
+            quote: highlight.quote,
+            location: highlight.location,
+            chapter: highlight.chapter,
+            comment: highlight.comment,
+            position: highlight.position,
+            created_at: highlight.created_at,
+        }
+    }
+}
+
+/// One read-through, with the reader named by username.
+#[derive(Serialize, Deserialize)]
+pub struct ExportedReading {
+    pub id: String,
+    pub reader: String,
+    pub status: ReadingStatus,
+    pub started_on: Option<String>,
+    pub finished_on: Option<String>,
+    pub created_at: String,
+}
+
+/// Output formats offered by the web export and `alayaserver export`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            "markdown" | "md" | "zip" => Some(ExportFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "zip",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Markdown => "application/zip",
+        }
+    }
+}
+
+/// Collect the library for export. A viewer gets the books they can see, only
+/// their own reading log and who their own books are shared with; without one
+/// every book, reader and share is included.
+pub async fn build_export(db: &Database, viewer: Option<&User>) -> Result<LibraryExport, DynError> {
+    let viewer_id = viewer.map(|user| user.id.as_str());
+    let books = match viewer_id {
+        Some(viewer_id) => db.get_visible_books(Some(viewer_id)).await?,
+        None => {
+            let mut books = db.get_all_books().await?;
+            books.reverse();
+            books
+        }
+    };
+    let usernames: HashMap<String, String> = db
+        .get_all_users()
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user.username))
+        .collect();
+
+    let mut exported = Vec::with_capacity(books.len());
+    for book in books {
+        let tags = db.get_book_tags(&book.id).await?;
+        let identifiers = db.get_book_identifiers(&book.id).await?;
+        let credited = db.get_book_authors(&book.id).await?;
+        let names = |role: AuthorRole| -> Vec<String> {
+            credited
+                .iter()
+                .filter(|author| author.role == role)
+                .map(|author| author.name.clone())
+                .collect()
+        };
+        let shared_with = match viewer_id {
+            Some(viewer_id) if book.user_id.as_deref() != Some(viewer_id) => Vec::new(),
+            _ => db.get_book_share_usernames(&book.id).await?,
+        };
+        let highlights = db.get_highlights(&book.id).await?;
+        let readings = db.get_exported_readings(&book.id, viewer_id).await?;
+
+        exported.push(ExportedBook {
+            owner: book
+                .user_id
+                .as_ref()
+                .and_then(|user_id| usernames.get(user_id).cloned()),
+            id: book.id,
+            title: book.title,
+            author: book.author,
+            publication_year: book.publication_year,
+            publisher: book.publisher,
+            language: book.language,
+            description: book.description,
+            page_count: book.page_count,
+            identifiers,
+            translators: names(AuthorRole::Translator),
+            editors: names(AuthorRole::Editor),
+            filepath: book.filepath,
+            notes: book.notes,
+            visibility: book.visibility,
+            shared_with,
+            tags,
+            highlights: highlights
+                .into_iter()
+                .map(ExportedHighlight::from)
+                .collect(),
+            readings,
+            created_at: book.created_at,
+            updated_at: book.updated_at,
+        });
+    }
+
+    Ok(LibraryExport {
+        version: EXPORT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        books: exported,
+    })
+}
+
+/// Write an export in the given format.
+pub fn render_export(export: &LibraryExport, format: ExportFormat) -> Result<Vec<u8>, DynError> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_vec_pretty(export)?),
+        ExportFormat::Csv => to_csv(export),
+        ExportFormat::Markdown => to_markdown_zip(export),
+    }
+}
+
+/// One row per book. Tags are joined with commas and of the identifiers only
+/// the ISBN is given; descriptions, highlights and readings are only in the
+/// JSON export.
+fn to_csv(export: &LibraryExport) -> Result<Vec<u8>, DynError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "id",
+        "title",
+        "author",
+        "publication_year",
+        "publisher",
+        "language",
+        "page_count",
+        "isbn",
+        "filepath",
+        "visibility",
+        "owner",
+        "tags",
+        "notes",
+        "created_at",
+        "updated_at",
+    ])?;
+
+    for book in &export.books {
+        let year = book
+            .publication_year
+            .map(|year| year.to_string())
+            .unwrap_or_default();
+        let page_count = book
+            .page_count
+            .map(|pages| pages.to_string())
+            .unwrap_or_default();
+        let isbn = book
+            .identifiers
+            .iter()
+            .find(|identifier| identifier.scheme == "isbn")
+            .map(|identifier| identifier.value.as_str())
+            .unwrap_or_default();
+        writer.write_record([
+            book.id.as_str(),
+            book.title.as_str(),
+            book.author.as_deref().unwrap_or(""),
+            year.as_str(),
+            book.publisher.as_deref().unwrap_or(""),
+            book.language.as_deref().unwrap_or(""),
+            page_count.as_str(),
+            isbn,
+            book.filepath.as_deref().unwrap_or(""),
+            book.visibility.as_str(),
+            book.owner.as_deref().unwrap_or(""),
+            book.tags.join(", ").as_str(),
+            book.notes.as_deref().unwrap_or(""),
+            book.created_at.as_str(),
+            book.updated_at.as_str(),
+        ])?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+/// A zip with one Markdown file per book, metadata in YAML front matter.
+fn to_markdown_zip(export: &LibraryExport) -> Result<Vec<u8>, DynError> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    let mut used = HashSet::new();
+
+    for book in &export.books {
+        zip.start_file(markdown_filename(book, &mut used), options)?;
+        zip.write_all(book_markdown(book).as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// A file name from the book title, unique within the zip.
+fn markdown_filename(book: &ExportedBook, used: &mut HashSet<String>) -> String {
+    let cleaned: String = book
+        .title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(MAX_FILENAME_CHARS)
+        .collect();
+    let stem = cleaned.trim().trim_start_matches('.').trim();
+    let stem = if stem.is_empty() { &book.id } else { stem };
+
+    let mut name = format!("{stem}.md");
+    let mut counter = 2;
+    while !used.insert(name.to_lowercase()) {
+        name = format!("{stem} ({counter}).md");
+        counter += 1;
+    }
+    name
+}
+
+/// JSON strings and numbers are valid YAML scalars, so values are quoted with serde_json.
+fn yaml_value<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+fn book_markdown(book: &ExportedBook) -> String {
+    let mut front = vec![
+        format!("id: {}", yaml_value(&book.id)),
+        format!("title: {}", yaml_value(&book.title)),
+    ];
+    if let Some(author) = &book.author {
+        front.push(format!("author: {}", yaml_value(author)));
+    }
+    if !book.translators.is_empty() {
+        front.push(format!("translators: {}", yaml_value(&book.translators)));
+    }
+    if !book.editors.is_empty() {
+        front.push(format!("editors: {}", yaml_value(&book.editors)));
+    }
+    if let Some(year) = book.publication_year {
+        front.push(format!("publication_year: {year}"));
+    }
+    if let Some(publisher) = &book.publisher {
+        front.push(format!("publisher: {}", yaml_value(publisher)));
+    }
+    if let Some(language) = &book.language {
+        front.push(format!("language: {}", yaml_value(language)));
+    }
+    if let Some(pages) = book.page_count {
+        front.push(format!("page_count: {pages}"));
+    }
+    if !book.identifiers.is_empty() {
+        let identifiers: Vec<String> = book
+            .identifiers
+            .iter()
+            .map(|identifier| format!("{}:{}", identifier.scheme, identifier.value))
+            .collect();
+        front.push(format!("identifiers: {}", yaml_value(&identifiers)));
+    }
+    if let Some(description) = &book.description {
+        front.push(format!("description: {}", yaml_value(description)));
+    }
+    if !book.tags.is_empty() {
+        front.push(format!("tags: {}", yaml_value(&book.tags)));
+    }
+    front.push(format!("visibility: {}", book.visibility.as_str()));
+    if let Some(owner) = &book.owner {
+        front.push(format!("owner: {}", yaml_value(owner)));
+    }
+    if let Some(filepath) = &book.filepath {
+        front.push(format!("filepath: {}", yaml_value(filepath)));
+    }
+    front.push(format!("created_at: {}", yaml_value(&book.created_at)));
+    front.push(format!("updated_at: {}", yaml_value(&book.updated_at)));
+
+    let mut markdown = format!("---\n{}\n---\n\n# {}\n", front.join("\n"), book.title);
+
+    if let Some(notes) = book
+        .notes
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        markdown.push('\n');
+        markdown.push_str(notes);
+        markdown.push('\n');
+    }
+
+    if !book.highlights.is_empty() {
+        markdown.push_str("\n## Highlights\n");
+        for highlight in &book.highlights {
+            markdown.push('\n');
+            for line in highlight.quote.lines() {
+                markdown.push_str(&format!("> {line}\n"));
+            }
+            let source: Vec<&str> = [highlight.chapter.as_deref(), highlight.location.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect();
+            if !source.is_empty() {
+                markdown.push_str(&format!("\n— {}\n", source.join(", ")));
+            }
+            if let Some(comment) = &highlight.comment {
+                markdown.push_str(&format!("\n{comment}\n"));
+            }
+        }
+    }
+
+    markdown
+}
+
+/// What to do when a restored book already exists, matched by id or file path.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    /// Keep whichever copy was updated most recently.
+    Newest,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 3] = [
+        ConflictPolicy::Skip,
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::Newest,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Newest => "newest",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "keep existing books",
+            ConflictPolicy::Overwrite => "overwrite existing books",
+            ConflictPolicy::Newest => "keep the most recently updated",
+        }
+    }
+}
+
+/// Outcome of restoring a JSON export.
+#[derive(Default)]
+pub struct RestoreSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub highlights: usize,
+    pub readings: usize,
+}
+
+/// Who a restore runs as, which decides how owners and readers are mapped.
+pub enum RestoreAs<'a> {
+    /// A user on the web: new books become theirs, only their own reading log
+    /// is kept, only books they may edit can be replaced, and file paths are
+    /// left out.
+    User(&'a User),
+    /// The command line: owners and readers are matched by username, and books
+    /// whose owner has no account here go to `fallback`.
+    Admin { fallback: Option<&'a User> },
+}
+
+/// A book from an export with its owner, shares and readers resolved to local user ids.
+pub struct RestoredBook<'a> {
+    pub book: &'a ExportedBook,
+    pub owner_id: Option<String>,
+    pub shared_with: Vec<String>,
+    pub readings: Vec<(String, &'a ExportedReading)>,
+}
+
+/// Read a JSON export, refusing ones written by a newer alaya.
+pub fn parse_export(contents: &str) -> Result<LibraryExport, String> {
+    let export: LibraryExport =
+        serde_json::from_str(contents).map_err(|e| format!("Not an alaya JSON export: {e}"))?;
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "Export version {} is newer than this alaya understands ({EXPORT_VERSION})",
+            export.version
+        ));
+    }
+    Ok(export)
+}
+
+/// Restore an export in one transaction, keeping book ids and timestamps.
+pub async fn restore_library(
+    db: &Database,
+    export: &LibraryExport,
+    policy: ConflictPolicy,
+    restore_as: RestoreAs<'_>,
+) -> Result<RestoreSummary, DynError> {
+    let user_ids: HashMap<String, String> = db
+        .get_all_users()
+        .await?
+        .into_iter()
+        .map(|user| (user.username, user.id))
+        .collect();
+
+    let mut missing_owners: Vec<&str> = Vec::new();
+    let mut books = Vec::with_capacity(export.books.len());
+    for book in &export.books {
+        let owner_id = match (&restore_as, &book.owner) {
+            (RestoreAs::User(user), _) => Some(user.id.clone()),
+            (RestoreAs::Admin { .. }, None) => None,
+            (RestoreAs::Admin { fallback }, Some(owner)) => match (user_ids.get(owner), fallback) {
+                (Some(user_id), _) => Some(user_id.clone()),
+                (None, Some(fallback)) => Some(fallback.id.clone()),
+                (None, None) => {
+                    if !missing_owners.contains(&owner.as_str()) {
+                        missing_owners.push(owner);
+                    }
+                    None
+                }
+            },
+        };
+
+        let readings = book
+            .readings
+            .iter()
+            .filter_map(|reading| {
+                let user_id = match &restore_as {
+                    RestoreAs::User(user) => {
+                        Some(&user.id).filter(|_| reading.reader == user.username)
+                    }
+                    RestoreAs::Admin { .. } => user_ids.get(&reading.reader),
+                };
+                user_id.map(|user_id| (user_id.clone(), reading))
+            })
+            .collect();
+
+        books.push(RestoredBook {
+            book,
+            owner_id,
+            shared_with: book
+                .shared_with
+                .iter()
+                .filter_map(|username| user_ids.get(username).cloned())
+                .collect(),
+            readings,
+        });
+    }
+
+    if !missing_owners.is_empty() {
+        return Err(format!(
+            "No account for book owner(s): {}. Sign them up first or name a fallback user.",
+            missing_owners.join(", ")
+        )
+        .into());
+    }
+
+    let editor_id = match restore_as {
+        RestoreAs::User(user) => Some(user.id.as_str()),
+        RestoreAs::Admin { .. } => None,
+    };
+    Ok(db.restore_books(&books, policy, editor_id).await?)
+}
+
+async fn export_response(db: &Database, headers: &HeaderMap, format: ExportFormat) -> Response {
+    let user = current_user(db, headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let body = match build_export(db, Some(&user)).await {
+        Ok(export) => render_export(&export, format),
+        Err(error) => Err(error),
+    };
+
+    match body {
+        Ok(body) => {
+            let filename = format!(
+                "alaya-{}.{}",
+                chrono::Utc::now().format("%Y-%m-%d"),
+                format.extension()
+            );
+            let headers = [
+                (header::CONTENT_TYPE, format.content_type().to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ];
+            (headers, body).into_response()
+        }
+        Err(error) => {
+            eprintln!("Export error: {error}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not export library",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn export_json(State(db): State<AppState>, headers: HeaderMap) -> Response {
+    export_response(&db, &headers, ExportFormat::Json).await
+}
+
+pub async fn export_csv(State(db): State<AppState>, headers: HeaderMap) -> Response {
+    export_response(&db, &headers, ExportFormat::Csv).await
+}
+
+pub async fn export_markdown(State(db): State<AppState>, headers: HeaderMap) -> Response {
+    export_response(&db, &headers, ExportFormat::Markdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::test_database;
+
+    fn export(books: &[(&str, &str, &str)]) -> LibraryExport {
+        let books: Vec<serde_json::Value> = books
+            .iter()
+            .map(|(id, title, filepath)| {
+                serde_json::json!({
+                    "id": id,
+                    "title": title,
+                    "author": null,
+                    "publication_year": null,
+                    "filepath": filepath,
+                    "notes": null,
+                    "visibility": "private",
+                    "owner": null,
+                    "created_at": "2024-06-03T21:14:05+00:00",
+                    "updated_at": "2024-06-03T21:14:05+00:00",
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "version": EXPORT_VERSION,
+            "exported_at": "2024-06-03T21:14:05+00:00",
+            "books": books,
+        });
+        parse_export(&json.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn user_restore_cannot_set_or_change_file_paths() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let user = db.get_user_by_username("reader").await.unwrap().unwrap();
+        let own_id = db
+            .upsert_book_by_filepath(
+                "calvino/cities.epub",
+                "Invisible Cities",
+                None,
+                None,
+                &crate::books::BookDetails::default(),
+                &[],
+                Some(&user_id),
+                BookVisibility::Private,
+            )
+            .await
+            .unwrap();
+
+        let crafted = export(&[
+            ("new-book", "The Database", "alaya.db"),
+            (&own_id, "Invisible Cities", "../alaya.db"),
+            ("other-id", "Copy", "calvino/cities.epub"),
+        ]);
+        let summary = restore_library(
+            &db,
+            &crafted,
+            ConflictPolicy::Overwrite,
+            RestoreAs::User(&user),
+        )
+        .await
+        .unwrap();
+        assert_eq!((summary.created, summary.updated), (2, 1));
+
+        let filepath = async |id: &str| db.get_book_by_id(id).await.unwrap().unwrap().filepath;
+        assert_eq!(filepath("new-book").await, None);
+        assert_eq!(filepath("other-id").await, None);
+        assert_eq!(
+            filepath(&own_id).await.as_deref(),
+            Some("calvino/cities.epub")
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_restore_keeps_file_paths() {
+        let (_dir, db) = test_database().await;
+        let library = export(&[("scanned", "Invisible Cities", "calvino/cities.epub")]);
+        restore_library(
+            &db,
+            &library,
+            ConflictPolicy::Skip,
+            RestoreAs::Admin { fallback: None },
+        )
+        .await
+        .unwrap();
+
+        let book = db.get_book_by_id("scanned").await.unwrap().unwrap();
+        assert_eq!(book.filepath.as_deref(), Some("calvino/cities.epub"));
+    }
+}
@@ -0,0 +1,435 @@
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::env;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// The library directory from `LIBRARY_PATH` (default: the current directory).
+fn library_root() -> PathBuf {
+    PathBuf::from(env::var("LIBRARY_PATH").unwrap_or_else(|_| ".".to_string()))
+}
+
+/// Where a book's stored file path points under the library directory. `None`
+/// when the file does not exist, or when it resolves outside the library, eg.
+/// through `..` or a symlink.
+pub(crate) fn library_file(filepath: &str) -> Option<PathBuf> {
+    file_under(&library_root(), filepath)
+}
+
+/// Where `filepath` points under `root`, if it exists there.
+fn file_under(root: &Path, filepath: &str) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let full_path = root.join(filepath).canonicalize().ok()?;
+    if !full_path.starts_with(&root) {
+        eprintln!("Refusing file outside the library: {filepath}");
+        return None;
+    }
+    Some(full_path)
+}
+
+/// Content type of a book file, by extension.
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("epub") => "application/epub+zip",
+        Some("mobi") => "application/x-mobipocket-ebook",
+        Some("azw") | Some("azw3") => "application/vnd.amazon.ebook",
+        Some("txt") => "text/plain",
+        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Some("fb2") => "application/x-fictionbook+xml",
+        Some("cbz") => "application/vnd.comicbook+zip",
+        Some("cbr") => "application/vnd.comicbook-rar",
+        Some("djvu") | Some("djv") => "image/vnd.djvu",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A byte range of a file, both ends inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+/// Parse a `Range` header against a file of `len` bytes. `None` means the
+/// header should be ignored and the whole file sent; `Some(Err(()))` that the
+/// range cannot be satisfied. Multiple ranges are answered with the whole file,
+/// which the spec allows.
+fn parse_range(value: &str, len: u64) -> Option<Result<ByteRange, ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // The last `end` bytes
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(Err(()));
+        }
+        ByteRange {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        }
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end: u64 = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            end.parse().ok()?
+        };
+        if start >= len {
+            return Some(Err(()));
+        }
+        if end < start {
+            return None;
+        }
+        ByteRange {
+            start,
+            end: end.min(len - 1),
+        }
+    };
+    Some(Ok(range))
+}
+
+/// Stream a book file from the library, with `disposition` either `attachment`
+/// for downloads or `inline` for the browser to display. Answers conditional
+/// and range requests so large files can be resumed and seeked.
+pub(crate) async fn book_file_response(
+    full_path: &Path,
+    disposition: &str,
+    request_headers: &HeaderMap,
+) -> Response {
+    let mut file = match tokio::fs::File::open(full_path).await {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("Error opening file {}: {error}", full_path.display());
+            return (StatusCode::NOT_FOUND, "File not found on disk").into_response();
+        }
+    };
+    let metadata = match file.metadata().await {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            eprintln!("Error reading file: {error}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Could not read file").into_response();
+        }
+    };
+    let len = metadata.len();
+
+    // HTTP dates have whole seconds, so compare modification times at that precision
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| UNIX_EPOCH + std::time::Duration::from_secs(since_epoch.as_secs()));
+    let etag = format!(
+        "\"{:x}-{:x}\"",
+        len,
+        modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or_default()
+    );
+    let last_modified = modified.map(httpdate::fmt_http_date);
+
+    let filename = full_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("download")
+        .replace('"', "");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(content_type(full_path)),
+    );
+    if let Ok(value) = HeaderValue::from_str(&format!("{disposition}; filename=\"{filename}\"")) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Some(value) = last_modified
+        .as_deref()
+        .and_then(|date| HeaderValue::from_str(date).ok())
+    {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
+
+    if not_modified(request_headers, &etag, modified) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    let range = request_headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| if_range_matches(request_headers, &etag, last_modified.as_deref()))
+        .and_then(|value| parse_range(value, len));
+
+    let (status, range) = match range {
+        None => (StatusCode::OK, None),
+        Some(Ok(range)) => (StatusCode::PARTIAL_CONTENT, Some(range)),
+        Some(Err(())) => {
+            headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{len}")).expect("valid header"),
+            );
+            return (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response();
+        }
+    };
+
+    let (start, body_len) = match range {
+        Some(range) => {
+            headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", range.start, range.end, len))
+                    .expect("valid header"),
+            );
+            (range.start, range.end - range.start + 1)
+        }
+        None => (0, len),
+    };
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+
+    if start > 0
+        && let Err(error) = file.seek(SeekFrom::Start(start)).await
+    {
+        eprintln!("Error reading file: {error}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Could not read file").into_response();
+    }
+    let body = Body::from_stream(ReaderStream::new(file.take(body_len)));
+
+    (status, headers, body).into_response()
+}
+
+/// Whether `If-None-Match`, or failing that `If-Modified-Since`, says the
+/// client's copy is current.
+fn not_modified(request_headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(value) = request_headers.get(header::IF_NONE_MATCH) {
+        let value = value.to_str().unwrap_or_default();
+        return value.trim() == "*"
+            || value
+                .split(',')
+                .any(|tag| tag.trim().trim_start_matches("W/") == etag);
+    }
+
+    let since = request_headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// A range is only honoured when `If-Range`, if sent, names the current file.
+fn if_range_matches(request_headers: &HeaderMap, etag: &str, last_modified: Option<&str>) -> bool {
+    match request_headers
+        .get(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+    {
+        None => true,
+        Some(value) => value == etag || Some(value) == last_modified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETAG: &str = "\"1f4-5f5e100\"";
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn range(start: u64, end: u64) -> Option<Result<ByteRange, ()>> {
+        Some(Ok(ByteRange { start, end }))
+    }
+
+    #[test]
+    fn parse_range_bounded() {
+        assert_eq!(parse_range("bytes=0-99", 500), range(0, 99));
+        assert_eq!(parse_range(" bytes=10 - 20 ", 500), range(10, 20));
+        assert_eq!(parse_range("bytes=400-999", 500), range(400, 499));
+    }
+
+    #[test]
+    fn parse_range_open_ended() {
+        assert_eq!(parse_range("bytes=100-", 500), range(100, 499));
+        assert_eq!(parse_range("bytes=499-", 500), range(499, 499));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(parse_range("bytes=-100", 500), range(400, 499));
+        assert_eq!(parse_range("bytes=-1000", 500), range(0, 499));
+        assert_eq!(parse_range("bytes=-0", 500), Some(Err(())));
+        assert_eq!(parse_range("bytes=-10", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn parse_range_out_of_bounds() {
+        assert_eq!(parse_range("bytes=500-", 500), Some(Err(())));
+        assert_eq!(parse_range("bytes=600-700", 500), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn parse_range_ignored() {
+        assert_eq!(parse_range("bytes=20-10", 500), None);
+        assert_eq!(parse_range("bytes=0-10,20-30", 500), None);
+        assert_eq!(parse_range("items=0-10", 500), None);
+        assert_eq!(parse_range("bytes=a-b", 500), None);
+        assert_eq!(parse_range("bytes=10", 500), None);
+    }
+
+    #[test]
+    fn not_modified_etags() {
+        let modified = Some(UNIX_EPOCH);
+        assert!(not_modified(
+            &headers(header::IF_NONE_MATCH, ETAG),
+            ETAG,
+            modified
+        ));
+        assert!(not_modified(
+            &headers(header::IF_NONE_MATCH, "*"),
+            ETAG,
+            modified
+        ));
+        assert!(not_modified(
+            &headers(header::IF_NONE_MATCH, &format!("W/{ETAG}")),
+            ETAG,
+            modified
+        ));
+        assert!(not_modified(
+            &headers(header::IF_NONE_MATCH, &format!("\"other\", {ETAG}")),
+            ETAG,
+            modified
+        ));
+        assert!(!not_modified(
+            &headers(header::IF_NONE_MATCH, "\"other\""),
+            ETAG,
+            modified
+        ));
+        assert!(!not_modified(&HeaderMap::new(), ETAG, modified));
+    }
+
+    #[test]
+    fn not_modified_dates() {
+        let modified = UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        let date = httpdate::fmt_http_date(modified);
+        assert!(not_modified(
+            &headers(header::IF_MODIFIED_SINCE, &date),
+            ETAG,
+            Some(modified)
+        ));
+
+        let earlier = httpdate::fmt_http_date(modified - std::time::Duration::from_secs(1));
+        assert!(!not_modified(
+            &headers(header::IF_MODIFIED_SINCE, &earlier),
+            ETAG,
+            Some(modified)
+        ));
+        assert!(!not_modified(
+            &headers(header::IF_MODIFIED_SINCE, &date),
+            ETAG,
+            None
+        ));
+        assert!(!not_modified(
+            &headers(header::IF_MODIFIED_SINCE, "yesterday"),
+            ETAG,
+            Some(modified)
+        ));
+
+        // If-None-Match wins over If-Modified-Since
+        let mut both = headers(header::IF_NONE_MATCH, "\"other\"");
+        both.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_str(&date).unwrap(),
+        );
+        assert!(!not_modified(&both, ETAG, Some(modified)));
+    }
+
+    #[test]
+    fn if_range_needs_a_strong_match() {
+        let date = "Mon, 12 Jan 1970 13:46:40 GMT";
+        assert!(if_range_matches(&HeaderMap::new(), ETAG, Some(date)));
+        assert!(if_range_matches(
+            &headers(header::IF_RANGE, ETAG),
+            ETAG,
+            Some(date)
+        ));
+        assert!(if_range_matches(
+            &headers(header::IF_RANGE, date),
+            ETAG,
+            Some(date)
+        ));
+        assert!(!if_range_matches(
+            &headers(header::IF_RANGE, &format!("W/{ETAG}")),
+            ETAG,
+            Some(date)
+        ));
+        assert!(!if_range_matches(
+            &headers(header::IF_RANGE, "\"other\""),
+            ETAG,
+            Some(date)
+        ));
+        assert!(!if_range_matches(
+            &headers(header::IF_RANGE, date),
+            ETAG,
+            None
+        ));
+    }
+
+    #[test]
+    fn library_files_stay_inside_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("library");
+        std::fs::create_dir_all(root.join("Fiction")).unwrap();
+        std::fs::write(root.join("Fiction/book.epub"), "book").unwrap();
+        std::fs::write(dir.path().join("alaya.db"), "database").unwrap();
+
+        assert_eq!(
+            file_under(&root, "Fiction/book.epub"),
+            Some(root.join("Fiction/book.epub").canonicalize().unwrap())
+        );
+        assert_eq!(
+            file_under(&root, "Fiction/../Fiction/book.epub"),
+            Some(root.join("Fiction/book.epub").canonicalize().unwrap())
+        );
+        assert_eq!(file_under(&root, "Fiction/missing.epub"), None);
+        assert_eq!(file_under(&root, "../alaya.db"), None);
+        assert_eq!(file_under(&root, "Fiction/../../alaya.db"), None);
+
+        let outside = dir.path().join("alaya.db");
+        assert_eq!(file_under(&root, outside.to_str().unwrap()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn library_files_do_not_follow_symlinks_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("library");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(dir.path().join("alaya.db"), "database").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("alaya.db"), root.join("book.epub")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("parent")).unwrap();
+
+        assert_eq!(file_under(&root, "book.epub"), None);
+        assert_eq!(file_under(&root, "parent/alaya.db"), None);
+    }
+}
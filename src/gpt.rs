@@ -1,7 +1,9 @@
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::{env, error::Error, fmt};
 
+use crate::metadata::{join_author_names, split_author_names};
+
 const OPENAI_CHAT_COMPLETIONS_URL: &str = "https://api.openai.com/v1/chat/completions";
 const DEFAULT_MODEL: &str = "gpt-5-nano";
 const USER_AGENT: &str = "alayascan/0.1.0";
@@ -74,7 +76,7 @@ impl GptClient {
             "Identify this book: \"{query}\"\n\n\
             Return the information as JSON with these fields:\n\
             - title: the correct title (omit the subtitle if it exists)\n\
-            - author: the author name (if multiple authors, separate with commas)\n\
+            - authors: a JSON array of the authors' full names, first name first, one string per person (leave out translators and editors; [] if unknown)\n\
             - publication_year: the original publication year if known, otherwise null\n\n\
             Return ONLY valid JSON, no other text."
         );
@@ -121,7 +123,11 @@ impl GptClient {
         instruction: &str,
         model: &str,
     ) -> Result<BookEditResult, GptError> {
-        let author_str = current_author.unwrap_or("unknown");
+        let author_str = match current_author {
+            Some(author) => serde_json::to_string(&split_author_names(author))
+                .unwrap_or_else(|_| author.to_string()),
+            None => "unknown".to_string(),
+        };
         let year_str = current_publication_year
             .map(|y| y.to_string())
             .unwrap_or_else(|| "unknown".to_string());
@@ -129,13 +135,13 @@ impl GptClient {
         let prompt = format!(
             "I have a book with these current details:\n\
             - Title: {current_title}\n\
-            - Author: {author_str}\n\
+            - Authors: {author_str}\n\
             - Publication Year: {year_str}\n\n\
             User instruction: \"{instruction}\"\n\n\
             Apply the user's instruction to update the book details. \
             Return the updated information as JSON with these fields:\n\
             - title: the updated title (or keep original if not changing)\n\
-            - author: the author name (if multiple authors, separate with commas; or null if unknown)\n\
+            - authors: a JSON array of the authors' full names, first name first, one string per person ([] if unknown)\n\
             - publication_year: the updated publication year as a number (or null if unknown)\n\n\
             Return ONLY valid JSON, no other text."
         );
@@ -242,17 +248,53 @@ pub enum GptError {
 #[derive(Debug, Deserialize)]
 pub struct BookMetadata {
     pub title: String,
-    pub author: Option<String>,
+    #[serde(default, deserialize_with = "names")]
+    pub authors: Vec<String>,
     pub publication_year: Option<i32>,
 }
 
+impl BookMetadata {
+    /// Authors joined into the single author field books have.
+    pub fn author(&self) -> Option<String> {
+        joined_names(&self.authors)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct BookEditResult {
     pub title: String,
-    pub author: Option<String>,
+    #[serde(default, deserialize_with = "names")]
+    pub authors: Vec<String>,
     pub publication_year: Option<i32>,
 }
 
+impl BookEditResult {
+    /// Authors joined into the single author field books have.
+    pub fn author(&self) -> Option<String> {
+        joined_names(&self.authors)
+    }
+}
+
+/// A list of names, taking null as none and leaving out blank ones. Each name
+/// is kept whole, commas and all.
+fn names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let names: Option<Vec<String>> = Option::deserialize(deserializer)?;
+    Ok(names
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect())
+}
+
+fn joined_names(names: &[String]) -> Option<String> {
+    if names.is_empty() {
+        None
+    } else {
+        Some(join_author_names(names))
+    }
+}
+
 impl fmt::Display for GptError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -311,3 +353,34 @@ pub struct ChatCompletionResponse {
 pub struct ChatChoice {
     pub message: ChatMessage,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn author_lists_keep_commas_in_names() {
+        let metadata: BookMetadata = serde_json::from_str(
+            r#"{"title": "The Dispossessed", "authors": ["Le Guin, Ursula K."], "publication_year": 1974}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.authors, ["Le Guin, Ursula K."]);
+        assert_eq!(
+            split_author_names(&metadata.author().unwrap()),
+            ["Le Guin, Ursula K."]
+        );
+
+        let result: BookEditResult = serde_json::from_str(
+            r#"{"title": "Good Omens", "authors": ["Terry Pratchett", " Neil Gaiman "], "publication_year": null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            result.author().as_deref(),
+            Some("Terry Pratchett, Neil Gaiman")
+        );
+
+        let result: BookEditResult =
+            serde_json::from_str(r#"{"title": "Beowulf", "authors": null}"#).unwrap();
+        assert_eq!(result.author(), None);
+    }
+}
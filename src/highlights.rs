@@ -0,0 +1,416 @@
+use askama::Template;
+use axum::{
+    extract::{Form, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use crate::auth::{User, current_user, signups_disabled};
+use crate::books::{Book, access_error_response, editable_book};
+use crate::templates::HighlightFormTemplate;
+
+/// A quoted passage of a book, kept apart from the free-form notes.
+#[derive(Serialize, Clone)]
+pub struct Highlight {
+    pub id: String,
+    pub book_id: String,
+    pub quote: String,
+    /// Page number or e-reader location, as written by the reader.
+    pub location: Option<String>,
+    pub chapter: Option<String>,
+    pub comment: Option<String>,
+    pub position: i64,
+    pub created_at: String,
+}
+
+impl Highlight {
+    pub fn created_date(&self) -> &str {
+        self.created_at
+            .split('T')
+            .next()
+            .unwrap_or(&self.created_at)
+    }
+}
+
+/// Fields of a highlight as entered, with blank values already dropped.
+pub struct HighlightInput<'a> {
+    pub quote: &'a str,
+    pub location: Option<&'a str>,
+    pub chapter: Option<&'a str>,
+    pub comment: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+pub struct HighlightForm {
+    pub quote: String,
+    pub location: String,
+    pub chapter: String,
+    pub comment: String,
+}
+
+/// Trim a form field and treat blank values as missing.
+fn optional(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|v| !v.is_empty())
+}
+
+impl HighlightForm {
+    fn input(&self) -> HighlightInput<'_> {
+        HighlightInput {
+            quote: self.quote.trim(),
+            location: optional(&self.location),
+            chapter: optional(&self.chapter),
+            comment: optional(&self.comment),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MoveHighlightForm {
+    pub direction: String,
+}
+
+fn render_highlight_form(
+    user: User,
+    book: Book,
+    highlight: Option<Highlight>,
+    error_message: Option<String>,
+) -> Response {
+    let template = HighlightFormTemplate {
+        is_authenticated: true,
+        signups_disabled: signups_disabled(),
+        username: user.username,
+        book,
+        highlight,
+        error_message,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+pub async fn highlight_new_page(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    match editable_book(&db, &book_id, &user).await {
+        Ok(book) => render_highlight_form(user, book, None, None),
+        Err(error) => access_error_response(error),
+    }
+}
+
+pub async fn highlight_create(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+    Form(form): Form<HighlightForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let book = match editable_book(&db, &book_id, &user).await {
+        Ok(book) => book,
+        Err(error) => return access_error_response(error),
+    };
+
+    let input = form.input();
+    if input.quote.is_empty() {
+        return render_highlight_form(user, book, None, Some("Quote is required".to_string()));
+    }
+
+    match db.create_highlight(&book_id, &input).await {
+        Ok(_) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Err(error) => {
+            eprintln!("Highlight creation error: {error}");
+            render_highlight_form(
+                user,
+                book,
+                None,
+                Some("Could not save highlight".to_string()),
+            )
+        }
+    }
+}
+
+pub async fn highlight_edit_page(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, highlight_id)): Path<(String, String)>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let book = match editable_book(&db, &book_id, &user).await {
+        Ok(book) => book,
+        Err(error) => return access_error_response(error),
+    };
+
+    match db.get_highlight(&book_id, &highlight_id).await {
+        Ok(Some(highlight)) => render_highlight_form(user, book, Some(highlight), None),
+        Ok(None) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Err(error) => {
+            eprintln!("Error fetching highlight: {error}");
+            Redirect::to(&format!("/books/{}", book_id)).into_response()
+        }
+    }
+}
+
+pub async fn highlight_edit_submit(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, highlight_id)): Path<(String, String)>,
+    Form(form): Form<HighlightForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let book = match editable_book(&db, &book_id, &user).await {
+        Ok(book) => book,
+        Err(error) => return access_error_response(error),
+    };
+
+    let highlight = match db.get_highlight(&book_id, &highlight_id).await {
+        Ok(Some(highlight)) => highlight,
+        Ok(None) => return Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Err(error) => {
+            eprintln!("Error fetching highlight: {error}");
+            return Redirect::to(&format!("/books/{}", book_id)).into_response();
+        }
+    };
+
+    let input = form.input();
+    if input.quote.is_empty() {
+        return render_highlight_form(
+            user,
+            book,
+            Some(highlight),
+            Some("Quote is required".to_string()),
+        );
+    }
+
+    match db.update_highlight(&book_id, &highlight_id, &input).await {
+        Ok(_) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Err(error) => {
+            eprintln!("Highlight update error: {error}");
+            render_highlight_form(
+                user,
+                book,
+                Some(highlight),
+                Some("Could not save highlight".to_string()),
+            )
+        }
+    }
+}
+
+pub async fn highlight_delete(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, highlight_id)): Path<(String, String)>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
+    }
+
+    match db.delete_highlight(&book_id, &highlight_id).await {
+        Ok(_) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Err(error) => {
+            eprintln!("Highlight delete error: {error}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not delete highlight",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Swap a highlight with its neighbour above or below.
+pub async fn highlight_move(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, highlight_id)): Path<(String, String)>,
+    Form(form): Form<MoveHighlightForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    if let Err(error) = editable_book(&db, &book_id, &user).await {
+        return access_error_response(error);
+    }
+
+    let up = match form.direction.as_str() {
+        "up" => true,
+        "down" => false,
+        _ => return (StatusCode::BAD_REQUEST, "Unknown direction").into_response(),
+    };
+
+    if let Err(error) = db.move_highlight(&book_id, &highlight_id, up).await {
+        eprintln!("Highlight move error: {error}");
+    }
+
+    Redirect::to(&format!("/books/{}#highlight-{}", book_id, highlight_id)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::books::BookVisibility;
+    use crate::database::Database;
+    use crate::database::tests::{add_book, test_database};
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    fn quote(quote: &str) -> HighlightInput<'_> {
+        HighlightInput {
+            quote,
+            location: None,
+            chapter: None,
+            comment: None,
+        }
+    }
+
+    async fn quotes(db: &Database, book_id: &str) -> Vec<String> {
+        db.get_highlights(book_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|highlight| highlight.quote)
+            .collect()
+    }
+
+    async fn post(app: &axum::Router, uri: &str, token: &str, form: &str) -> StatusCode {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(form.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn highlights_append_and_move() {
+        let (_dir, db) = test_database().await;
+        let book_id = add_book(&db, "Ficciones", None, None, BookVisibility::Public).await;
+        let mut ids = Vec::new();
+        for text in ["one", "two", "three"] {
+            ids.push(db.create_highlight(&book_id, &quote(text)).await.unwrap());
+        }
+        assert_eq!(quotes(&db, &book_id).await, ["one", "two", "three"]);
+
+        db.move_highlight(&book_id, &ids[2], true).await.unwrap();
+        assert_eq!(quotes(&db, &book_id).await, ["one", "three", "two"]);
+        db.move_highlight(&book_id, &ids[0], false).await.unwrap();
+        assert_eq!(quotes(&db, &book_id).await, ["three", "one", "two"]);
+
+        // Nothing above the first or below the last
+        db.move_highlight(&book_id, &ids[2], true).await.unwrap();
+        db.move_highlight(&book_id, &ids[1], false).await.unwrap();
+        assert_eq!(quotes(&db, &book_id).await, ["three", "one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn highlights_of_another_book_are_left_alone() {
+        let (_dir, db) = test_database().await;
+        let book_id = add_book(&db, "Ficciones", None, None, BookVisibility::Public).await;
+        let other_id = add_book(&db, "Aleph", None, None, BookVisibility::Public).await;
+        db.create_highlight(&book_id, &quote("mine")).await.unwrap();
+        let first = db
+            .create_highlight(&other_id, &quote("first"))
+            .await
+            .unwrap();
+        let second = db
+            .create_highlight(&other_id, &quote("second"))
+            .await
+            .unwrap();
+
+        assert!(db.get_highlight(&book_id, &second).await.unwrap().is_none());
+        db.move_highlight(&book_id, &second, true).await.unwrap();
+        db.move_highlight(&book_id, &first, false).await.unwrap();
+        db.update_highlight(&book_id, &first, &quote("changed"))
+            .await
+            .unwrap();
+        db.delete_highlight(&book_id, &second).await.unwrap();
+
+        assert_eq!(quotes(&db, &other_id).await, ["first", "second"]);
+        assert_eq!(quotes(&db, &book_id).await, ["mine"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_owner_changes_highlights() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("owner", "secret").await.unwrap();
+        let reader = db.create_user("reader", "secret").await.unwrap();
+        let owner_token = db.create_api_token(&owner, "test").await.unwrap();
+        let reader_token = db.create_api_token(&reader, "test").await.unwrap();
+        let book_id = add_book(&db, "Ficciones", None, Some(&owner), BookVisibility::Public).await;
+        let first = db
+            .create_highlight(&book_id, &quote("first"))
+            .await
+            .unwrap();
+        db.create_highlight(&book_id, &quote("second"))
+            .await
+            .unwrap();
+        let db = std::sync::Arc::new(db);
+        let app = crate::create_app(db.clone());
+
+        let base = format!("/books/{book_id}/highlights");
+        let form = "quote=changed&location=&chapter=&comment=";
+        for (uri, form) in [
+            (base.clone(), form),
+            (format!("{base}/{first}/edit"), form),
+            (format!("{base}/{first}/delete"), ""),
+            (format!("{base}/{first}/move"), "direction=down"),
+        ] {
+            assert_eq!(
+                post(&app, &uri, &reader_token, form).await,
+                StatusCode::FORBIDDEN,
+                "{uri}"
+            );
+        }
+        assert_eq!(quotes(&db, &book_id).await, ["first", "second"]);
+
+        let status = post(
+            &app,
+            &format!("{base}/{first}/move"),
+            &owner_token,
+            "direction=down",
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let status = post(
+            &app,
+            &base,
+            &owner_token,
+            "quote=third&location=&chapter=&comment=",
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(quotes(&db, &book_id).await, ["second", "first", "third"]);
+    }
+}
@@ -0,0 +1,785 @@
+use askama::Template;
+use axum::{
+    extract::{Multipart, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use std::collections::HashMap;
+
+use crate::AppState;
+use crate::auth::{User, current_user, signups_disabled};
+use crate::books::{Book, BookDetails, BookVisibility};
+use crate::clippings::{ClippingsSummary, best_match, normalize};
+use crate::database::Database;
+use crate::export::{ConflictPolicy, RestoreAs, RestoreSummary, parse_export, restore_library};
+use crate::readings::{Reading, ReadingStatus};
+use crate::templates::{ImportPreviewTemplate, ImportTemplate};
+
+type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Shelves that Goodreads and StoryGraph use for reading status rather than as tags.
+const STATUS_SHELVES: &[&str] = &[
+    "read",
+    "currently-reading",
+    "to-read",
+    "did-not-finish",
+    "paused",
+];
+
+/// Which service a CSV export came from, told apart by its header row.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CsvFormat {
+    Goodreads,
+    StoryGraph,
+}
+
+impl CsvFormat {
+    fn detect(headers: &csv::StringRecord) -> Option<Self> {
+        if headers.iter().any(|h| h == "Exclusive Shelf") {
+            Some(CsvFormat::Goodreads)
+        } else if headers.iter().any(|h| h == "Read Status") {
+            Some(CsvFormat::StoryGraph)
+        } else {
+            None
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CsvFormat::Goodreads => "Goodreads",
+            CsvFormat::StoryGraph => "StoryGraph",
+        }
+    }
+}
+
+/// A read-through taken from an export. Dates are `YYYY-MM-DD`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedReading {
+    pub status: ReadingStatus,
+    pub started_on: Option<String>,
+    pub finished_on: Option<String>,
+}
+
+impl ImportedReading {
+    /// Whether the reader already has this read-through, so importing twice adds nothing.
+    fn is_known(&self, readings: &[Reading]) -> bool {
+        if self.status == ReadingStatus::WantToRead {
+            return !readings.is_empty();
+        }
+        readings.iter().any(|reading| {
+            reading.status == self.status
+                && reading.finished_on == self.finished_on
+                && (reading.started_on.is_none()
+                    || self.started_on.is_none()
+                    || reading.started_on == self.started_on)
+        })
+    }
+
+    fn describe(&self) -> String {
+        match (&self.started_on, &self.finished_on) {
+            (_, Some(finished)) => format!("{} {}", self.status.label(), finished),
+            (Some(started), None) => format!("{} since {}", self.status.label(), started),
+            (None, None) => self.status.label().to_string(),
+        }
+    }
+}
+
+/// One row of an export mapped onto alaya's fields.
+#[derive(Clone, Debug)]
+pub struct ImportedBook {
+    pub title: String,
+    pub author: Option<String>,
+    pub publication_year: Option<i32>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+    pub readings: Vec<ImportedReading>,
+}
+
+/// Parse a Goodreads or StoryGraph CSV export.
+pub fn parse_csv(contents: &str) -> Result<(CsvFormat, Vec<ImportedBook>), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Could not read CSV header: {e}"))?
+        .clone();
+    let format = CsvFormat::detect(&headers)
+        .ok_or("Unrecognised CSV, expected a Goodreads or StoryGraph export")?;
+    let columns: HashMap<&str, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (name.trim(), index))
+        .collect();
+
+    let mut books = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Could not read CSV row {}: {e}", line + 2))?;
+        let field = |name: &str| {
+            columns
+                .get(name)
+                .and_then(|&index| record.get(index))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let book = match format {
+            CsvFormat::Goodreads => goodreads_book(field),
+            CsvFormat::StoryGraph => storygraph_book(field),
+        };
+        if let Some(book) = book {
+            books.push(book);
+        }
+    }
+
+    Ok((format, books))
+}
+
+fn goodreads_book<'a>(field: impl Fn(&str) -> Option<&'a str>) -> Option<ImportedBook> {
+    let title = field("Title")?.to_string();
+
+    let publication_year = field("Original Publication Year")
+        .or_else(|| field("Year Published"))
+        .and_then(|year| year.parse().ok());
+
+    let notes = join_notes(&[
+        field("My Review").map(html_to_text),
+        field("Private Notes").map(String::from),
+    ]);
+
+    let shelf = field("Exclusive Shelf").unwrap_or("read");
+    let status = match shelf {
+        "to-read" => ReadingStatus::WantToRead,
+        "currently-reading" => ReadingStatus::Reading,
+        _ => ReadingStatus::Finished,
+    };
+    let finished_on = field("Date Read").and_then(parse_date);
+    let readings = if status == ReadingStatus::Finished && shelf != "read" && finished_on.is_none()
+    {
+        // A custom exclusive shelf without a read date says nothing about reading
+        Vec::new()
+    } else {
+        vec![ImportedReading {
+            status,
+            started_on: None,
+            finished_on: finished_on.filter(|_| status == ReadingStatus::Finished),
+        }]
+    };
+
+    Some(ImportedBook {
+        title,
+        author: field("Author").map(String::from),
+        publication_year,
+        notes,
+        tags: split_tags(field("Bookshelves")),
+        readings,
+    })
+}
+
+fn storygraph_book<'a>(field: impl Fn(&str) -> Option<&'a str>) -> Option<ImportedBook> {
+    let title = field("Title")?.to_string();
+
+    let status = match field("Read Status").unwrap_or("read") {
+        "to-read" => ReadingStatus::WantToRead,
+        "currently-reading" | "paused" => ReadingStatus::Reading,
+        "did-not-finish" => ReadingStatus::Abandoned,
+        _ => ReadingStatus::Finished,
+    };
+
+    // "Dates Read" lists every read-through as start-end pairs, oldest first
+    let mut readings: Vec<ImportedReading> = field("Dates Read")
+        .map(|dates| {
+            dates
+                .split(',')
+                .filter_map(|range| {
+                    let (start, end) = range.split_once('-').unwrap_or(("", range));
+                    let started_on = parse_date(start);
+                    let finished_on = parse_date(end);
+                    (started_on.is_some() || finished_on.is_some()).then_some(ImportedReading {
+                        status: ReadingStatus::Finished,
+                        started_on,
+                        finished_on,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    readings.sort_by(|a, b| a.finished_on.cmp(&b.finished_on));
+
+    match readings.last_mut() {
+        // The latest read-through carries the current status
+        Some(last) if status.is_closed() => last.status = status,
+        Some(_) if status == ReadingStatus::WantToRead => {}
+        _ => readings.push(ImportedReading {
+            status,
+            started_on: None,
+            finished_on: field("Last Date Read")
+                .and_then(parse_date)
+                .filter(|_| status.is_closed()),
+        }),
+    }
+
+    Some(ImportedBook {
+        title,
+        author: field("Authors").map(String::from),
+        publication_year: None,
+        notes: field("Review").map(html_to_text),
+        tags: split_tags(field("Tags")),
+        readings,
+    })
+}
+
+fn parse_date(value: &str) -> Option<String> {
+    ["%Y/%m/%d", "%Y-%m-%d", "%m/%d/%Y"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(value.trim(), format).ok())
+        .map(|date| date.to_string())
+}
+
+fn split_tags(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty() && !STATUS_SHELVES.contains(tag))
+        .map(String::from)
+        .collect()
+}
+
+/// Reviews are exported with HTML line breaks.
+fn html_to_text(value: &str) -> String {
+    value
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .trim()
+        .to_string()
+}
+
+fn join_notes(parts: &[Option<String>]) -> Option<String> {
+    let parts: Vec<&str> = parts
+        .iter()
+        .flatten()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("\n\n"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportAction {
+    Create,
+    Update,
+    Duplicate,
+}
+
+impl ImportAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ImportAction::Create => "create",
+            ImportAction::Update => "update",
+            ImportAction::Duplicate => "skip",
+        }
+    }
+}
+
+/// What importing one row would do.
+pub struct PlannedImport {
+    pub book: ImportedBook,
+    pub action: ImportAction,
+    pub existing: Option<Book>,
+    pub new_tags: Vec<String>,
+    pub new_readings: Vec<ImportedReading>,
+    /// Human readable list of the fields the import sets.
+    pub changes: Vec<String>,
+}
+
+/// Work out, without writing anything, what importing `books` for a user would do.
+/// Rows are matched to the user's books the same way as Kindle clippings, and only
+/// fill in what is missing, so importing the same file again changes nothing.
+pub async fn plan_import(
+    db: &Database,
+    books: Vec<ImportedBook>,
+    user_id: &str,
+) -> Result<Vec<PlannedImport>, DynError> {
+    let candidates = db.get_editable_books(user_id).await?;
+    let mut plan = Vec::new();
+
+    for book in merge_duplicate_rows(books) {
+        let Some(existing) = best_match(&candidates, &book.title, book.author.as_deref()) else {
+            let mut changes = Vec::new();
+            if let Some(year) = book.publication_year {
+                changes.push(format!("pub year {year}"));
+            }
+            if book.notes.is_some() {
+                changes.push("notes".to_string());
+            }
+            if !book.tags.is_empty() {
+                changes.push(format!("tags: {}", book.tags.join(", ")));
+            }
+            changes.extend(book.readings.iter().map(ImportedReading::describe));
+            plan.push(PlannedImport {
+                new_tags: book.tags.clone(),
+                new_readings: book.readings.clone(),
+                book,
+                action: ImportAction::Create,
+                existing: None,
+                changes,
+            });
+            continue;
+        };
+
+        let tags = db.get_book_tags(&existing.id).await?;
+        let readings = db.get_readings(user_id, &existing.id).await?;
+
+        let mut changes = Vec::new();
+        if existing.publication_year.is_none()
+            && let Some(year) = book.publication_year
+        {
+            changes.push(format!("pub year {year}"));
+        }
+        if existing.notes.is_none() && book.notes.is_some() {
+            changes.push("notes".to_string());
+        }
+        let new_tags: Vec<String> = book
+            .tags
+            .iter()
+            .filter(|tag| !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            .cloned()
+            .collect();
+        if !new_tags.is_empty() {
+            changes.push(format!("tags: {}", new_tags.join(", ")));
+        }
+        let new_readings: Vec<ImportedReading> = book
+            .readings
+            .iter()
+            .filter(|reading| !reading.is_known(&readings))
+            .cloned()
+            .collect();
+        changes.extend(new_readings.iter().map(ImportedReading::describe));
+
+        plan.push(PlannedImport {
+            action: if changes.is_empty() {
+                ImportAction::Duplicate
+            } else {
+                ImportAction::Update
+            },
+            existing: Some(existing.clone()),
+            new_tags,
+            new_readings,
+            book,
+            changes,
+        });
+    }
+
+    Ok(plan)
+}
+
+/// Fold rows naming the same book, eg. one listed on two shelves, into the first
+/// of them so it is created once. Later rows add their tags and read-throughs and
+/// fill in what the first left empty.
+fn merge_duplicate_rows(books: Vec<ImportedBook>) -> Vec<ImportedBook> {
+    let key = |book: &ImportedBook| {
+        (
+            normalize(&book.title),
+            normalize(book.author.as_deref().unwrap_or_default()),
+        )
+    };
+
+    let mut merged: Vec<ImportedBook> = Vec::new();
+    for book in books {
+        let Some(first) = merged.iter_mut().find(|first| key(first) == key(&book)) else {
+            merged.push(book);
+            continue;
+        };
+
+        first.publication_year = first.publication_year.or(book.publication_year);
+        if first.notes.is_none() {
+            first.notes = book.notes;
+        }
+        for tag in book.tags {
+            if !first.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                first.tags.push(tag);
+            }
+        }
+        for reading in book.readings {
+            if !first.readings.contains(&reading) {
+                first.readings.push(reading);
+            }
+        }
+    }
+
+    merged
+}
+
+/// Outcome of a CSV import.
+#[derive(Default)]
+pub struct CsvImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// Carry out a plan from `plan_import`.
+pub async fn apply_import(
+    db: &Database,
+    plan: &[PlannedImport],
+    user_id: &str,
+) -> Result<CsvImportSummary, DynError> {
+    let mut summary = CsvImportSummary::default();
+
+    for planned in plan {
+        let book = &planned.book;
+        let book_id = match (&planned.action, &planned.existing) {
+            (ImportAction::Duplicate, _) => {
+                summary.skipped += 1;
+                continue;
+            }
+            (ImportAction::Update, Some(existing)) => {
+                if existing.publication_year.is_none() && book.publication_year.is_some() {
+                    db.update_book(
+                        &existing.id,
+                        &existing.title,
+                        existing.author.as_deref(),
+                        book.publication_year,
+                    )
+                    .await?;
+                }
+                if existing.notes.is_none() && book.notes.is_some() {
+                    db.update_book_notes(&existing.id, book.notes.as_deref())
+                        .await?;
+                }
+                summary.updated += 1;
+                existing.id.clone()
+            }
+            _ => {
+                let book_id = db
+                    .create_book(
+                        &book.title,
+                        book.author.as_deref(),
+                        book.publication_year,
+                        book.notes.as_deref(),
+                        Some(user_id),
+                        BookVisibility::Private,
+                        &BookDetails::default(),
+                        &[],
+                    )
+                    .await?;
+                summary.created += 1;
+                book_id
+            }
+        };
+
+        let tags: Vec<&str> = planned.new_tags.iter().map(String::as_str).collect();
+        db.add_book_tags(&book_id, &tags).await?;
+
+        for reading in &planned.new_readings {
+            db.create_reading(
+                user_id,
+                &book_id,
+                reading.status,
+                reading.started_on.as_deref(),
+                reading.finished_on.as_deref(),
+            )
+            .await?;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Text fields and file contents of a multipart upload, by field name.
+pub(crate) async fn read_upload(
+    multipart: &mut Multipart,
+) -> Result<HashMap<String, String>, String> {
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| format!("Upload failed: {e}"))?
+    {
+        let Some(name) = field.name().map(String::from) else {
+            continue;
+        };
+        let bytes = field
+            .bytes()
+            .await
+            .map_err(|e| format!("Upload failed: {e}"))?;
+        fields.insert(name, String::from_utf8_lossy(&bytes).into_owned());
+    }
+    Ok(fields)
+}
+
+/// What the import page reports after an upload.
+pub(crate) enum ImportResult {
+    Clippings(ClippingsSummary),
+    Csv(CsvImportSummary),
+    Restore(RestoreSummary),
+    Error(String),
+}
+
+pub(crate) fn render_import(user: User, result: Option<ImportResult>) -> Response {
+    let mut template = ImportTemplate {
+        is_authenticated: true,
+        signups_disabled: signups_disabled(),
+        username: user.username,
+        clippings: None,
+        csv: None,
+        restore: None,
+        error_message: None,
+    };
+    match result {
+        Some(ImportResult::Clippings(summary)) => template.clippings = Some(summary),
+        Some(ImportResult::Csv(summary)) => template.csv = Some(summary),
+        Some(ImportResult::Restore(summary)) => template.restore = Some(summary),
+        Some(ImportResult::Error(error)) => template.error_message = Some(error),
+        None => {}
+    }
+    Html(template.render().unwrap()).into_response()
+}
+
+pub async fn import_page(State(db): State<AppState>, headers: HeaderMap) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    render_import(user, None)
+}
+
+/// Preview a CSV upload, or import it once the preview is confirmed.
+pub async fn csv_import(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let mut fields = match read_upload(&mut multipart).await {
+        Ok(fields) => fields,
+        Err(error) => return render_import(user, Some(ImportResult::Error(error))),
+    };
+
+    // The preview page sends the CSV back as text rather than as a file
+    let contents = fields
+        .remove("csv")
+        .or_else(|| fields.remove("file"))
+        .filter(|contents| !contents.trim().is_empty());
+    let Some(contents) = contents else {
+        return render_import(
+            user,
+            Some(ImportResult::Error("Choose a file to import".to_string())),
+        );
+    };
+
+    let (format, books) = match parse_csv(&contents) {
+        Ok(parsed) => parsed,
+        Err(error) => return render_import(user, Some(ImportResult::Error(error))),
+    };
+
+    let plan = match plan_import(&db, books, &user.id).await {
+        Ok(plan) => plan,
+        Err(error) => {
+            eprintln!("CSV import error: {error}");
+            return render_import(
+                user,
+                Some(ImportResult::Error("Could not read your books".to_string())),
+            );
+        }
+    };
+
+    if !fields.contains_key("confirm") {
+        let count = |action| plan.iter().filter(|p| p.action == action).count();
+        let template = ImportPreviewTemplate {
+            is_authenticated: true,
+            signups_disabled: signups_disabled(),
+            username: user.username,
+            format: format.label(),
+            create_count: count(ImportAction::Create),
+            update_count: count(ImportAction::Update),
+            skip_count: count(ImportAction::Duplicate),
+            plan,
+            csv: contents,
+        };
+        return Html(template.render().unwrap()).into_response();
+    }
+
+    match apply_import(&db, &plan, &user.id).await {
+        Ok(summary) => render_import(user, Some(ImportResult::Csv(summary))),
+        Err(error) => {
+            eprintln!("CSV import error: {error}");
+            render_import(
+                user,
+                Some(ImportResult::Error("Could not import CSV".to_string())),
+            )
+        }
+    }
+}
+
+/// Restore books from an alaya JSON export.
+pub async fn json_import(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let mut fields = match read_upload(&mut multipart).await {
+        Ok(fields) => fields,
+        Err(error) => return render_import(user, Some(ImportResult::Error(error))),
+    };
+
+    let Some(contents) = fields.remove("file").filter(|c| !c.trim().is_empty()) else {
+        return render_import(
+            user,
+            Some(ImportResult::Error("Choose a file to import".to_string())),
+        );
+    };
+
+    let policy = fields
+        .get("policy")
+        .and_then(|policy| ConflictPolicy::parse(policy))
+        .unwrap_or(ConflictPolicy::Skip);
+
+    let export = match parse_export(&contents) {
+        Ok(export) => export,
+        Err(error) => return render_import(user, Some(ImportResult::Error(error))),
+    };
+
+    match restore_library(&db, &export, policy, RestoreAs::User(&user)).await {
+        Ok(summary) => render_import(user, Some(ImportResult::Restore(summary))),
+        Err(error) => {
+            eprintln!("JSON import error: {error}");
+            render_import(
+                user,
+                Some(ImportResult::Error("Could not import JSON".to_string())),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::tests::test_database;
+
+    const GOODREADS: &str = "\u{feff}Book Id,Title,Author,Year Published,Original Publication Year,My Review,Private Notes,Bookshelves,Exclusive Shelf,Date Read
+1,Invisible Cities,Italo Calvino,1978,1972,Dreamlike.<br/>Reread it,,\"fiction, read\",read,2024/06/03
+2,The Periodic Table,Primo Levi,1984,,,,to-read,to-read,
+3,Invisible Cities,Italo Calvino,1978,1972,,Borrowed,favourites,read,
+";
+
+    const STORYGRAPH: &str = "Title,Authors,Read Status,Dates Read,Last Date Read,Tags,Review
+Invisible Cities,Italo Calvino,read,\"2020/01/02-2020/01/20, 2024/05/01-2024/06/03\",2024/06/03,fiction,Dreamlike
+Ficciones,Jorge Luis Borges,did-not-finish,,2024/02/01,,
+";
+
+    fn reading(
+        status: ReadingStatus,
+        started: Option<&str>,
+        finished: Option<&str>,
+    ) -> ImportedReading {
+        ImportedReading {
+            status,
+            started_on: started.map(String::from),
+            finished_on: finished.map(String::from),
+        }
+    }
+
+    #[test]
+    fn parses_goodreads_export() {
+        let (format, books) = parse_csv(GOODREADS).unwrap();
+        assert_eq!(format, CsvFormat::Goodreads);
+        assert_eq!(books.len(), 3);
+
+        let calvino = &books[0];
+        assert_eq!(calvino.title, "Invisible Cities");
+        assert_eq!(calvino.author.as_deref(), Some("Italo Calvino"));
+        assert_eq!(calvino.publication_year, Some(1972));
+        assert_eq!(calvino.notes.as_deref(), Some("Dreamlike.\nReread it"));
+        assert_eq!(calvino.tags, ["fiction"]);
+        assert_eq!(
+            calvino.readings,
+            [reading(ReadingStatus::Finished, None, Some("2024-06-03"))]
+        );
+
+        assert_eq!(books[1].publication_year, Some(1984));
+        assert!(books[1].tags.is_empty());
+        assert_eq!(
+            books[1].readings,
+            [reading(ReadingStatus::WantToRead, None, None)]
+        );
+    }
+
+    #[test]
+    fn parses_storygraph_export() {
+        let (format, books) = parse_csv(STORYGRAPH).unwrap();
+        assert_eq!(format, CsvFormat::StoryGraph);
+
+        assert_eq!(
+            books[0].readings,
+            [
+                reading(
+                    ReadingStatus::Finished,
+                    Some("2020-01-02"),
+                    Some("2020-01-20")
+                ),
+                reading(
+                    ReadingStatus::Finished,
+                    Some("2024-05-01"),
+                    Some("2024-06-03")
+                ),
+            ]
+        );
+        assert_eq!(books[0].notes.as_deref(), Some("Dreamlike"));
+        assert_eq!(
+            books[1].readings,
+            [reading(ReadingStatus::Abandoned, None, Some("2024-02-01"))]
+        );
+    }
+
+    #[test]
+    fn rejects_other_csv_files() {
+        assert!(parse_csv("Title,Author\nInvisible Cities,Italo Calvino\n").is_err());
+    }
+
+    #[test]
+    fn merges_rows_naming_the_same_book() {
+        let (_, books) = parse_csv(GOODREADS).unwrap();
+        let merged = merge_duplicate_rows(books);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].tags, ["fiction", "favourites"]);
+        assert_eq!(merged[0].notes.as_deref(), Some("Dreamlike.\nReread it"));
+        assert_eq!(merged[0].readings.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn importing_twice_changes_nothing() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+
+        let (_, books) = parse_csv(GOODREADS).unwrap();
+        let plan = plan_import(&db, books, &user_id).await.unwrap();
+        assert!(plan.iter().all(|p| p.action == ImportAction::Create));
+        let summary = apply_import(&db, &plan, &user_id).await.unwrap();
+        assert_eq!(summary.created, 2);
+
+        let (_, books) = parse_csv(GOODREADS).unwrap();
+        let plan = plan_import(&db, books, &user_id).await.unwrap();
+        assert!(plan.iter().all(|p| p.action == ImportAction::Duplicate));
+        assert_eq!(db.get_owned_book_count(&user_id).await.unwrap(), 2);
+    }
+}
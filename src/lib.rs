@@ -1,13 +1,28 @@
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post, put},
 };
 use std::sync::Arc;
 
+pub mod api;
 pub mod auth;
+pub mod authors;
+pub mod backup;
 pub mod books;
+pub mod clippings;
+pub mod covers;
 pub mod database;
+pub mod export;
+pub mod files;
 pub mod gpt;
+pub mod highlights;
+pub mod imports;
+pub mod markdown;
+pub mod metadata;
+pub mod reader;
+pub mod readings;
 pub mod templates;
 
 pub use auth::User;
@@ -17,25 +32,83 @@ pub use database::Database;
 // Application state
 pub type AppState = Arc<Database>;
 
+// Uploaded import files, eg. a Kindle's My Clippings.txt, outgrow the default limit
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+// Cover images are resized on upload, but phone photos can be large
+const COVER_BODY_LIMIT: usize = 16 * 1024 * 1024;
+
 // App creation function
 pub fn create_app(db: AppState) -> Router {
+    use api::{
+        api_book_create, api_book_delete, api_book_get, api_book_notes_update, api_book_update,
+        api_books_list, api_not_found,
+    };
     use auth::{
-        change_password, change_password_page, login_page, login_submit, logout, profile_page,
+        api_token_create, api_token_delete, change_password, change_password_page, login_page,
+        login_submit, logout, profile_page, renew_session, session_delete, session_delete_all,
         signup_page, signup_submit,
     };
+    use authors::{author_detail, author_list, author_merge};
     use books::{
         book_create, book_delete, book_detail, book_download, book_edit_chat_apply,
-        book_edit_chat_page, book_edit_chat_submit, book_edit_notes_page, book_edit_notes_submit,
-        book_edit_page, book_edit_submit, book_form_page, book_list, quick_add_page,
-        quick_add_submit,
+        book_edit_chat_page, book_edit_chat_submit, book_edit_notes_page, book_edit_notes_preview,
+        book_edit_notes_submit, book_edit_page, book_edit_submit, book_form_page, book_list,
+        book_search, book_sharing_submit, quick_add_page, quick_add_submit, tag_list,
+    };
+    use clippings::clippings_import;
+    use covers::{book_cover, book_cover_upload};
+    use export::{export_csv, export_json, export_markdown};
+    use highlights::{
+        highlight_create, highlight_delete, highlight_edit_page, highlight_edit_submit,
+        highlight_move, highlight_new_page,
     };
+    use imports::{csv_import, import_page, json_import};
+    use reader::{book_read, epub_resource, reading_position_submit};
+    use readings::{reading_delete, reading_status_submit, reading_update};
+
+    let api = Router::new()
+        .route("/books", get(api_books_list).post(api_book_create))
+        .route(
+            "/books/{id}",
+            get(api_book_get)
+                .put(api_book_update)
+                .delete(api_book_delete),
+        )
+        .route("/books/{id}/notes", put(api_book_notes_update))
+        .fallback(api_not_found);
 
     Router::new()
         .route("/", get(book_list))
+        .route("/search", get(book_search))
+        .route("/tags", get(tag_list))
+        .route("/authors", get(author_list))
+        .route("/authors/{id}", get(author_detail))
+        .route("/authors/{id}/merge", post(author_merge))
         .route("/login", get(login_page).post(login_submit))
         .route("/signup", get(signup_page).post(signup_submit))
         .route("/logout", post(logout))
         .route("/profile", get(profile_page))
+        .route("/import", get(import_page))
+        .route(
+            "/import/json",
+            post(json_import).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route("/export/books.json", get(export_json))
+        .route("/export/books.csv", get(export_csv))
+        .route("/export/books.zip", get(export_markdown))
+        .route(
+            "/import/clippings",
+            post(clippings_import).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route(
+            "/import/csv",
+            post(csv_import).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route("/profile/tokens", post(api_token_create))
+        .route("/profile/tokens/{id}/delete", post(api_token_delete))
+        .route("/profile/sessions/delete-all", post(session_delete_all))
+        .route("/profile/sessions/{id}/delete", post(session_delete))
         .route(
             "/profile/password",
             get(change_password_page).post(change_password),
@@ -54,12 +127,50 @@ pub fn create_app(db: AppState) -> Router {
             "/books/{id}/edit-notes",
             get(book_edit_notes_page).post(book_edit_notes_submit),
         )
+        .route(
+            "/books/{id}/edit-notes/preview",
+            post(book_edit_notes_preview),
+        )
         .route(
             "/books/{id}/edit-chat",
             get(book_edit_chat_page).post(book_edit_chat_submit),
         )
         .route("/books/{id}/edit-chat/apply", post(book_edit_chat_apply))
+        .route("/books/{id}/sharing", post(book_sharing_submit))
+        .route("/books/{id}/highlights", post(highlight_create))
+        .route("/books/{id}/highlights/new", get(highlight_new_page))
+        .route(
+            "/books/{id}/highlights/{highlight_id}/edit",
+            get(highlight_edit_page).post(highlight_edit_submit),
+        )
+        .route(
+            "/books/{id}/highlights/{highlight_id}/delete",
+            post(highlight_delete),
+        )
+        .route(
+            "/books/{id}/highlights/{highlight_id}/move",
+            post(highlight_move),
+        )
+        .route("/books/{id}/status", post(reading_status_submit))
+        .route("/books/{id}/readings/{reading_id}", post(reading_update))
+        .route(
+            "/books/{id}/readings/{reading_id}/delete",
+            post(reading_delete),
+        )
         .route("/books/{id}/delete", post(book_delete))
         .route("/books/{id}/download", get(book_download))
+        .route(
+            "/books/{id}/read",
+            get(book_read).post(reading_position_submit),
+        )
+        .route("/books/{id}/epub/{*path}", get(epub_resource))
+        .route(
+            "/books/{id}/cover",
+            get(book_cover)
+                .post(book_cover_upload)
+                .layer(DefaultBodyLimit::max(COVER_BODY_LIMIT)),
+        )
+        .nest("/api/v1", api)
+        .layer(middleware::from_fn_with_state(db.clone(), renew_session))
         .with_state(db)
 }
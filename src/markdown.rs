@@ -0,0 +1,124 @@
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+use std::collections::HashMap;
+
+use crate::database::Database;
+
+fn parser_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_WIKILINKS
+}
+
+/// Book titles referenced as `[[Book Title]]` or `[[Book Title|label]]`.
+pub fn wiki_link_titles(markdown: &str) -> Vec<String> {
+    let mut titles: Vec<String> = Vec::new();
+    for event in Parser::new_ext(markdown, parser_options()) {
+        if let Event::Start(Tag::Link {
+            link_type: LinkType::WikiLink { .. },
+            dest_url,
+            ..
+        }) = event
+            && !titles
+                .iter()
+                .any(|title| title.as_str() == dest_url.as_ref())
+        {
+            titles.push(dest_url.into_string());
+        }
+    }
+    titles
+}
+
+/// Render Markdown to sanitized HTML. Wiki links point at the book ids in
+/// `book_ids`, keyed by the title as written; unknown titles become a search.
+pub fn render_markdown(markdown: &str, book_ids: &HashMap<String, String>) -> String {
+    let parser = Parser::new_ext(markdown, parser_options()).map(|event| match event {
+        Event::Start(Tag::Link {
+            link_type: link_type @ LinkType::WikiLink { .. },
+            dest_url,
+            title,
+            id,
+        }) => {
+            let href = match book_ids.get(dest_url.as_ref()) {
+                Some(book_id) => format!("/books/{book_id}"),
+                None => format!(
+                    "/search?{}",
+                    serde_urlencoded::to_string([("q", dest_url.as_ref())]).unwrap_or_default()
+                ),
+            };
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url: href.into(),
+                title,
+                id,
+            })
+        }
+        other => other,
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    ammonia::clean(&html)
+}
+
+/// Render book notes, resolving wiki links against the books the viewer can see.
+pub async fn render_notes(db: &Database, notes: &str, viewer_id: Option<&str>) -> String {
+    let titles = wiki_link_titles(notes);
+    let book_ids = if titles.is_empty() {
+        HashMap::new()
+    } else {
+        db.get_visible_book_ids_by_title(&titles, viewer_id)
+            .await
+            .unwrap_or_else(|error| {
+                eprintln!("Error resolving wiki links: {error}");
+                HashMap::new()
+            })
+    };
+    render_markdown(notes, &book_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_and_handlers_are_removed() {
+        let html = render_markdown(
+            "Hello <script>alert(1)</script>\n\n<a href=\"/books\" onclick=\"steal()\">link</a>",
+            &HashMap::new(),
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("alert(1)"));
+        assert!(!html.contains("onclick"));
+        assert!(html.contains("Hello"));
+        assert!(html.contains("href=\"/books\""));
+    }
+
+    #[test]
+    fn wiki_links_find_titles_once() {
+        assert_eq!(
+            wiki_link_titles(
+                "See [[Invisible Cities]], [[Invisible Cities|again]] and [[Ficciones]]."
+            ),
+            ["Invisible Cities", "Ficciones"]
+        );
+        assert!(wiki_link_titles("No [links](/books) here").is_empty());
+    }
+
+    #[test]
+    fn wiki_links_point_at_books_or_search() {
+        let book_ids = HashMap::from([("Invisible Cities".to_string(), "abc".to_string())]);
+
+        let html = render_markdown("[[Invisible Cities]]", &book_ids);
+        assert!(html.contains("<a href=\"/books/abc\""));
+        assert!(html.contains(">Invisible Cities</a>"));
+
+        let html = render_markdown("[[Invisible Cities|Calvino's cities]]", &book_ids);
+        assert!(html.contains("<a href=\"/books/abc\""));
+        assert!(html.contains(">Calvino's cities</a>"));
+
+        let html = render_markdown("[[Le città & i segni]]", &book_ids);
+        assert!(html.contains("<a href=\"/search?q=Le+citt%C3%A0+%26+i+segni\""));
+        assert!(!html.contains("/books/"));
+    }
+}
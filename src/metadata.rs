@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub mod comic;
+pub mod djvu;
+pub mod docx;
+pub mod epub;
+pub mod fb2;
+pub mod gutenberg;
+pub mod mobi;
+pub mod pdf;
+
+/// What a book file says about itself. Every field is optional, as formats
+/// and files vary in how much they record.
+#[derive(Debug, Default, Clone)]
+pub struct BookMetadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub translators: Vec<String>,
+    pub editors: Vec<String>,
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+    pub description: Option<String>,
+    pub identifiers: Vec<Identifier>,
+    /// Publication date, in whatever form the file gives it
+    pub date: Option<String>,
+    pub series: Option<String>,
+    /// Position in the series, eg. "3" or "2.5"
+    pub series_index: Option<String>,
+    pub page_count: Option<u32>,
+}
+
+/// An identifier such as an ISBN, with its scheme in lowercase, eg. "isbn",
+/// "asin", "uuid" or "gutenberg".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Identifier {
+    pub scheme: String,
+    pub value: String,
+}
+
+impl Identifier {
+    pub fn new(scheme: &str, value: &str) -> Self {
+        Identifier {
+            scheme: scheme.to_lowercase(),
+            value: value.trim().to_string(),
+        }
+    }
+
+    /// An identifier whose scheme is given by the file, or guessed from URN
+    /// prefixes and ISBN-shaped values when it is not.
+    pub fn guess(value: &str, scheme: Option<&str>) -> Self {
+        let value = value.trim();
+        if let Some(scheme) = scheme.filter(|scheme| !scheme.trim().is_empty()) {
+            return Identifier::new(scheme.trim(), value);
+        }
+
+        let lower = value.to_lowercase();
+        for (prefix, scheme) in [
+            ("urn:isbn:", "isbn"),
+            ("urn:uuid:", "uuid"),
+            ("isbn:", "isbn"),
+        ] {
+            if lower.starts_with(prefix) {
+                return Identifier::new(scheme, &value[prefix.len()..]);
+            }
+        }
+
+        let digits: String = value.chars().filter(|c| *c != '-' && *c != ' ').collect();
+        let looks_like_isbn = matches!(digits.len(), 10 | 13)
+            && digits
+                .chars()
+                .enumerate()
+                .all(|(i, c)| c.is_ascii_digit() || (i == 9 && digits.len() == 10 && c == 'X'));
+        if looks_like_isbn {
+            Identifier::new("isbn", value)
+        } else {
+            Identifier::new("identifier", value)
+        }
+    }
+}
+
+impl Identifier {
+    /// The scheme as shown on the book page, eg. "ISBN".
+    pub fn label(&self) -> String {
+        match self.scheme.as_str() {
+            "isbn" | "asin" | "uuid" | "doi" | "oclc" | "lccn" => self.scheme.to_uppercase(),
+            "gutenberg" => "Project Gutenberg".to_string(),
+            scheme => {
+                let mut chars = scheme.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl BookMetadata {
+    /// Authors joined into the single author field books have.
+    pub fn author(&self) -> Option<String> {
+        if self.authors.is_empty() {
+            None
+        } else {
+            Some(join_author_names(&self.authors))
+        }
+    }
+
+    pub fn publication_year(&self) -> Option<i32> {
+        parse_year(self.date.as_deref()?)
+    }
+
+    /// The first identifier with this scheme.
+    pub fn identifier(&self, scheme: &str) -> Option<&str> {
+        self.identifiers
+            .iter()
+            .find(|identifier| identifier.scheme == scheme)
+            .map(|identifier| identifier.value.as_str())
+    }
+}
+
+/// Reads one or more book file formats.
+pub trait MetadataExtractor: Sync {
+    /// File extensions handled, in lowercase without the dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Read a file's metadata. `None` if the file could not be read as this
+    /// format.
+    fn extract(&self, path: &Path) -> Option<BookMetadata>;
+
+    /// The cover image stored in the file, if the format has one.
+    fn cover(&self, _path: &Path) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+static EXTRACTORS: &[&dyn MetadataExtractor] = &[
+    &epub::EpubExtractor,
+    &pdf::PdfExtractor,
+    &mobi::MobiExtractor,
+    &docx::DocxExtractor,
+    &gutenberg::GutenbergExtractor,
+    &fb2::Fb2Extractor,
+    &comic::ComicExtractor,
+    &djvu::DjvuExtractor,
+];
+
+/// Every known format's extractor.
+pub fn extractors() -> &'static [&'static dyn MetadataExtractor] {
+    EXTRACTORS
+}
+
+/// File extensions of the book formats the library knows about.
+pub fn supported_extensions() -> impl Iterator<Item = &'static str> {
+    EXTRACTORS
+        .iter()
+        .flat_map(|extractor| extractor.extensions().iter().copied())
+}
+
+/// The extractor for a file, by its extension.
+pub fn extractor_for(path: &Path) -> Option<&'static dyn MetadataExtractor> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    EXTRACTORS
+        .iter()
+        .copied()
+        .find(|extractor| extractor.extensions().contains(&extension.as_str()))
+}
+
+/// Read a book file's metadata with the extractor for its format.
+pub fn extract_metadata(path: &Path) -> Option<BookMetadata> {
+    extractor_for(path)?.extract(path)
+}
+
+/// Parse a year from various date formats
+pub fn parse_year(date: &str) -> Option<i32> {
+    // Only work with ASCII digits to avoid UTF-8 boundary issues
+    let chars: Vec<char> = date.chars().collect();
+
+    // Try to extract a 4-digit year from the beginning
+    if chars.len() >= 4 {
+        let first_four: String = chars[..4].iter().collect();
+        if let Ok(year) = first_four.parse::<i32>()
+            && (1000..=2100).contains(&year)
+        {
+            return Some(year);
+        }
+    }
+
+    // Try to find any 4-digit year in the string
+    for i in 0..chars.len().saturating_sub(3) {
+        let four_chars: String = chars[i..i + 4].iter().collect();
+        if let Ok(year) = four_chars.parse::<i32>()
+            && (1800..=2100).contains(&year)
+        {
+            return Some(year);
+        }
+    }
+
+    None
+}
+
+/// Split an author field into names. Names are separated by commas, or by
+/// semicolons and ampersands when written "Last, First". A lone "Calvino, Italo"
+/// with a single word on either side of the comma is one name, and so is one
+/// whose last name starts with a particle, like "de la Cruz, Juana Inés".
+pub fn split_author_names(text: &str) -> Vec<String> {
+    let names: Vec<&str> = if text.contains([';', '&']) {
+        text.split([';', '&']).collect()
+    } else {
+        match text.split_once(',') {
+            Some((last, first))
+                if !first.contains(',')
+                    && (!last.trim().contains(' ')
+                        || !first.trim().contains(' ')
+                        || last.trim_start().starts_with(char::is_lowercase)) =>
+            {
+                vec![text]
+            }
+            _ => text.split(',').collect(),
+        }
+    };
+
+    let mut unique: Vec<String> = Vec::new();
+    for name in names.into_iter().filter_map(non_empty) {
+        if !unique.iter().any(|seen| seen.eq_ignore_ascii_case(&name)) {
+            unique.push(name);
+        }
+    }
+    unique
+}
+
+/// Join names into an author field that `split_author_names` reads back the same.
+/// A lone name that would be split at its comma, like "Le Guin, Ursula K.", ends
+/// with a semicolon to keep it whole.
+pub fn join_author_names<S: AsRef<str>>(names: &[S]) -> String {
+    let names: Vec<&str> = names.iter().map(|name| name.as_ref().trim()).collect();
+    if let [name] = names.as_slice()
+        && split_author_names(name).len() > 1
+    {
+        format!("{name};")
+    } else if names.iter().any(|name| name.contains([',', ';', '&'])) {
+        names.join("; ")
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Plain text from a description that may be HTML, as EPUB and MOBI ones often
+/// are. Paragraphs and line breaks become new lines, other tags are dropped.
+fn plain_text(markup: &str) -> Option<String> {
+    let mut text = String::new();
+    let mut rest = markup;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .to_lowercase();
+        if ["p", "br", "div", "li"].iter().any(|name| {
+            tag == *name
+                || tag.starts_with(&format!("{name} "))
+                || tag.starts_with(&format!("{name}/"))
+        }) {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    non_empty(&lines.join("\n"))
+}
+
+/// Trim a value, treating blank ones as missing.
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_last_first_names() {
+        assert_eq!(split_author_names("Calvino, Italo"), ["Calvino, Italo"]);
+        assert_eq!(
+            split_author_names("de la Cruz, Juana Inés"),
+            ["de la Cruz, Juana Inés"]
+        );
+        assert_eq!(
+            split_author_names("Calvino, Italo; Weaver, William"),
+            ["Calvino, Italo", "Weaver, William"]
+        );
+        assert_eq!(
+            split_author_names("de la Cruz, Juana Inés & Paz, Octavio"),
+            ["de la Cruz, Juana Inés", "Paz, Octavio"]
+        );
+    }
+
+    #[test]
+    fn split_comma_separated_names() {
+        assert_eq!(
+            split_author_names("Italo Calvino, William Weaver"),
+            ["Italo Calvino", "William Weaver"]
+        );
+        assert_eq!(
+            split_author_names("Italo Calvino, William Weaver, Umberto Eco"),
+            ["Italo Calvino", "William Weaver", "Umberto Eco"]
+        );
+        assert_eq!(
+            split_author_names(" Italo Calvino ,, italo calvino "),
+            ["Italo Calvino"]
+        );
+        assert!(split_author_names(" ; ").is_empty());
+    }
+
+    #[test]
+    fn join_reads_back() {
+        for names in [
+            vec!["Italo Calvino", "William Weaver"],
+            vec!["Calvino, Italo", "Weaver, William"],
+            vec!["de la Cruz, Juana Inés"],
+            vec!["Juana Inés de la Cruz", "Octavio Paz"],
+            vec!["Le Guin, Ursula K."],
+            vec!["Le Guin, Ursula K.", "Calvino, Italo"],
+        ] {
+            assert_eq!(split_author_names(&join_author_names(&names)), names);
+        }
+        assert_eq!(join_author_names(&["Calvino, Italo"]), "Calvino, Italo");
+        assert_eq!(
+            join_author_names(&["Le Guin, Ursula K."]),
+            "Le Guin, Ursula K.;"
+        );
+    }
+}
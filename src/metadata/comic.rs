@@ -0,0 +1,178 @@
+use std::io::Read;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+use super::{BookMetadata, Identifier, MetadataExtractor, non_empty};
+
+const IMAGE_EXTENSIONS: &[&str] = &[".jpg", ".jpeg", ".png", ".gif", ".webp"];
+
+/// Comic book archives. CBZs are zip files, usually with a `ComicInfo.xml`;
+/// CBRs are RAR archives, which are not read here.
+pub struct ComicExtractor;
+
+impl MetadataExtractor for ComicExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["cbz", "cbr"]
+    }
+
+    /// Read `ComicInfo.xml` from a CBZ archive.
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).ok()?).ok()?;
+        let name = archive
+            .file_names()
+            .find(|name| name.eq_ignore_ascii_case("ComicInfo.xml"))?
+            .to_string();
+        let mut content = Vec::new();
+        archive
+            .by_name(&name)
+            .ok()?
+            .read_to_end(&mut content)
+            .ok()?;
+
+        let mut metadata = BookMetadata::default();
+        let mut year = None;
+        let mut current: Option<String> = None;
+        for event in EventReader::new(content.as_slice()) {
+            match event.ok()? {
+                XmlEvent::StartElement { name, .. } => current = Some(name.local_name),
+                XmlEvent::Characters(text) => {
+                    let Some(text) = non_empty(&text) else {
+                        continue;
+                    };
+                    match current.as_deref() {
+                        Some("Title") => metadata.title = Some(text),
+                        Some("Series") => metadata.series = Some(text),
+                        Some("Number") => metadata.series_index = Some(text),
+                        Some("Writer") => metadata
+                            .authors
+                            .extend(text.split(',').filter_map(non_empty)),
+                        Some("Editor") => metadata
+                            .editors
+                            .extend(text.split(',').filter_map(non_empty)),
+                        Some("Translator") => metadata
+                            .translators
+                            .extend(text.split(',').filter_map(non_empty)),
+                        Some("Publisher") => metadata.publisher = Some(text),
+                        Some("Summary") => metadata.description = Some(text),
+                        Some("LanguageISO") => metadata.language = Some(text),
+                        Some("GTIN") => metadata.identifiers.push(Identifier::guess(&text, None)),
+                        Some("Year") => year = Some(text),
+                        Some("PageCount") => metadata.page_count = text.parse().ok(),
+                        _ => {}
+                    }
+                }
+                XmlEvent::EndElement { .. } => current = None,
+                _ => {}
+            }
+        }
+        metadata.date = year;
+
+        // An issue without a title of its own goes by its series and number
+        if metadata.title.is_none() {
+            metadata.title = match (&metadata.series, &metadata.series_index) {
+                (Some(series), Some(number)) => Some(format!("{} #{}", series, number)),
+                (series, _) => series.clone(),
+            };
+        }
+
+        Some(metadata)
+    }
+
+    /// The first image in a CBZ, by name, which is its cover.
+    fn cover(&self, path: &Path) -> Option<Vec<u8>> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).ok()?).ok()?;
+        let name = archive
+            .file_names()
+            .filter(|name| {
+                let name = name.to_lowercase();
+                IMAGE_EXTENSIONS
+                    .iter()
+                    .any(|extension| name.ends_with(extension))
+            })
+            .min()?
+            .to_string();
+        let mut image = Vec::new();
+        archive.by_name(&name).ok()?.read_to_end(&mut image).ok()?;
+        Some(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A CBZ file holding the given files, written to a temporary directory.
+    fn cbz(files: &[(&str, &[u8])]) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("issue.cbz");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for (name, contents) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn reads_comic_info() {
+        let info = br#"<?xml version="1.0"?>
+<ComicInfo>
+  <Title>The Sound of Her Wings</Title>
+  <Series>The Sandman</Series>
+  <Number>8</Number>
+  <Writer>Neil Gaiman</Writer>
+  <Editor>Karen Berger, Tom Peyer</Editor>
+  <Publisher>DC Comics</Publisher>
+  <Summary>Death takes Dream along on her rounds.</Summary>
+  <LanguageISO>en</LanguageISO>
+  <GTIN>9781401225759</GTIN>
+  <Year>1989</Year>
+  <PageCount>24</PageCount>
+</ComicInfo>"#;
+        let (_dir, path) = cbz(&[("ComicInfo.xml", info), ("001.jpg", b"page")]);
+        let metadata = ComicExtractor.extract(&path).unwrap();
+
+        assert_eq!(metadata.title.as_deref(), Some("The Sound of Her Wings"));
+        assert_eq!(metadata.series.as_deref(), Some("The Sandman"));
+        assert_eq!(metadata.series_index.as_deref(), Some("8"));
+        assert_eq!(metadata.authors, ["Neil Gaiman"]);
+        assert_eq!(metadata.editors, ["Karen Berger", "Tom Peyer"]);
+        assert_eq!(metadata.publisher.as_deref(), Some("DC Comics"));
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("Death takes Dream along on her rounds.")
+        );
+        assert_eq!(metadata.language.as_deref(), Some("en"));
+        assert_eq!(metadata.identifier("isbn"), Some("9781401225759"));
+        assert_eq!(metadata.publication_year(), Some(1989));
+        assert_eq!(metadata.page_count, Some(24));
+    }
+
+    #[test]
+    fn untitled_issues_go_by_series_and_number() {
+        let info = b"<ComicInfo><Series>Saga</Series><Number>12</Number></ComicInfo>";
+        let (_dir, path) = cbz(&[("comicinfo.xml", info)]);
+        let metadata = ComicExtractor.extract(&path).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Saga #12"));
+
+        let (_dir, path) = cbz(&[("001.jpg", b"page")]);
+        assert!(ComicExtractor.extract(&path).is_none());
+    }
+
+    #[test]
+    fn cover_is_first_image_by_name() {
+        let (_dir, path) = cbz(&[
+            ("ComicInfo.xml", b"<ComicInfo/>"),
+            ("pages/002.png", b"second"),
+            ("pages/001.JPG", b"first"),
+            ("pages/notes.txt", b"not an image"),
+        ]);
+        assert_eq!(ComicExtractor.cover(&path).as_deref(), Some(&b"first"[..]));
+
+        let (_dir, path) = cbz(&[("ComicInfo.xml", b"<ComicInfo/>")]);
+        assert!(ComicExtractor.cover(&path).is_none());
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::Path;
+
+use super::{BookMetadata, MetadataExtractor, non_empty};
+
+/// Scanned books in DjVu.
+pub struct DjvuExtractor;
+
+impl MetadataExtractor for DjvuExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["djvu", "djv"]
+    }
+
+    /// Read the metadata annotations. Only uncompressed `ANTa` and `METa`
+    /// chunks are read; books whose annotations are BZZ-compressed get none.
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let data = std::fs::read(path).ok()?;
+        let body = data.strip_prefix(b"AT&T")?;
+
+        let mut annotations = String::new();
+        let mut pages = 0;
+        collect_annotations(body, &mut annotations, &mut pages, 0);
+
+        Some(BookMetadata {
+            title: annotation(&annotations, "title"),
+            authors: annotation(&annotations, "author").into_iter().collect(),
+            publisher: annotation(&annotations, "publisher"),
+            date: annotation(&annotations, "year"),
+            description: annotation(&annotations, "note"),
+            page_count: Some(pages).filter(|pages| *pages > 0),
+            ..BookMetadata::default()
+        })
+    }
+}
+
+/// How deep `FORM`s are followed. Documents nest pages one level below the
+/// top, so anything past this is a broken or hostile file.
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Walk the IFF chunks of a DjVu file, descending into `FORM`s, and gather the
+/// text of annotation chunks. Each page is a `FORM:DJVU`, so those are counted.
+fn collect_annotations(mut data: &[u8], annotations: &mut String, pages: &mut u32, depth: usize) {
+    while data.len() >= 8 {
+        let id = &data[0..4];
+        let size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let Some(chunk) = data.get(8..8 + size) else {
+            return;
+        };
+        match id {
+            b"FORM" if size >= 4 => {
+                if &chunk[0..4] == b"DJVU" {
+                    *pages += 1;
+                }
+                if depth < MAX_FORM_DEPTH {
+                    collect_annotations(&chunk[4..], annotations, pages, depth + 1)
+                }
+            }
+            b"ANTa" | b"METa" => {
+                annotations.push_str(&String::from_utf8_lossy(chunk));
+                annotations.push('\n');
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even length
+        let next = 8 + size + size % 2;
+        data = data.get(next..).unwrap_or_default();
+    }
+}
+
+/// The quoted value of `(key "value")` in DjVu annotations.
+fn annotation(annotations: &str, key: &str) -> Option<String> {
+    let start = annotations.find(&format!("({} \"", key))? + key.len() + 3;
+    let mut value = String::new();
+    let mut chars = annotations[start..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => value.extend(chars.next()),
+            _ => value.push(c),
+        }
+    }
+    non_empty(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IFF chunk, padded to an even length.
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend((data.len() as u32).to_be_bytes());
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn form(kind: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = kind.to_vec();
+        for inner in chunks {
+            data.extend(inner);
+        }
+        chunk(b"FORM", &data)
+    }
+
+    fn write_djvu(form: Vec<u8>) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.djvu");
+        let mut data = b"AT&T".to_vec();
+        data.extend(form);
+        std::fs::write(&path, data).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn reads_annotations_and_counts_pages() {
+        let annotations = br#"(metadata (title "Principia \"Mathematica\"") (author "Isaac Newton") (publisher "Royal Society") (year "1687") (note "Book one"))"#;
+        let page = form(b"DJVU", &[chunk(b"INFO", b"odd")]);
+        let (_dir, path) = write_djvu(form(
+            b"DJVM",
+            &[
+                chunk(b"DIRM", b"x"),
+                chunk(b"ANTa", annotations),
+                page.clone(),
+                page.clone(),
+                page,
+            ],
+        ));
+        let metadata = DjvuExtractor.extract(&path).unwrap();
+
+        assert_eq!(metadata.title.as_deref(), Some("Principia \"Mathematica\""));
+        assert_eq!(metadata.authors, ["Isaac Newton"]);
+        assert_eq!(metadata.publisher.as_deref(), Some("Royal Society"));
+        assert_eq!(metadata.publication_year(), Some(1687));
+        assert_eq!(metadata.description.as_deref(), Some("Book one"));
+        assert_eq!(metadata.page_count, Some(3));
+    }
+
+    #[test]
+    fn deep_and_truncated_forms_are_not_followed() {
+        let mut nested = form(b"DJVU", &[chunk(b"ANTa", br#"(title "Too deep")"#)]);
+        for _ in 0..MAX_FORM_DEPTH {
+            nested = form(b"DJVM", &[nested]);
+        }
+        let (_dir, path) = write_djvu(nested);
+        let metadata = DjvuExtractor.extract(&path).unwrap();
+        assert_eq!(metadata.title, None);
+
+        let mut truncated = form(b"DJVU", &[chunk(b"ANTa", br#"(title "Cut")"#)]);
+        truncated.truncate(truncated.len() - 4);
+        let (_dir, path) = write_djvu(truncated);
+        let metadata = DjvuExtractor.extract(&path).unwrap();
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.page_count, None);
+    }
+
+    #[test]
+    fn skips_files_without_the_djvu_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.djvu");
+        std::fs::write(&path, form(b"DJVU", &[])).unwrap();
+        assert!(DjvuExtractor.extract(&path).is_none());
+    }
+}
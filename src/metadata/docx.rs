@@ -0,0 +1,116 @@
+use std::io::Read;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+use super::{BookMetadata, MetadataExtractor};
+
+pub struct DocxExtractor;
+
+impl MetadataExtractor for DocxExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["docx"]
+    }
+
+    /// Read the document properties in `docProps/core.xml`.
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let file = std::fs::File::open(path).ok()?;
+        let mut archive = zip::ZipArchive::new(file).ok()?;
+        let mut core = Vec::new();
+        archive
+            .by_name("docProps/core.xml")
+            .ok()?
+            .read_to_end(&mut core)
+            .ok()?;
+
+        let mut metadata = BookMetadata::default();
+        let mut creator: Option<String> = None;
+        let mut current: Option<String> = None;
+        for event in EventReader::new(core.as_slice()) {
+            match event.ok()? {
+                XmlEvent::StartElement { name, .. } => current = Some(name.local_name),
+                XmlEvent::Characters(text) => {
+                    let text = text.trim();
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let field = match current.as_deref() {
+                        Some("title") => &mut metadata.title,
+                        Some("creator") => &mut creator,
+                        Some("created") => &mut metadata.date,
+                        Some("description") => &mut metadata.description,
+                        Some("language") => &mut metadata.language,
+                        _ => continue,
+                    };
+                    field.get_or_insert_with(String::new).push_str(text);
+                }
+                XmlEvent::EndElement { .. } => current = None,
+                _ => {}
+            }
+        }
+        metadata.authors.extend(creator);
+
+        Some(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const CORE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <dc:title>Invisible Cities &amp; Other Stories</dc:title>
+  <dc:creator>Italo Calvino</dc:creator>
+  <dc:description>Marco Polo describes cities to Kublai Khan.</dc:description>
+  <dc:language>en-GB</dc:language>
+  <cp:lastModifiedBy>Someone Else</cp:lastModifiedBy>
+  <dcterms:created xsi:type="dcterms:W3CDTF">1972-11-01T00:00:00Z</dcterms:created>
+</cp:coreProperties>"#;
+
+    /// A DOCX file holding the given files, written to a temporary directory.
+    fn docx(files: &[(&str, &str)]) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.docx");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for (name, contents) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn reads_core_properties() {
+        let (_dir, path) = docx(&[
+            ("word/document.xml", "<w:document/>"),
+            ("docProps/core.xml", CORE),
+        ]);
+        let metadata = DocxExtractor.extract(&path).unwrap();
+
+        assert_eq!(
+            metadata.title.as_deref(),
+            Some("Invisible Cities & Other Stories")
+        );
+        assert_eq!(metadata.authors, ["Italo Calvino"]);
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("Marco Polo describes cities to Kublai Khan.")
+        );
+        assert_eq!(metadata.language.as_deref(), Some("en-GB"));
+        assert_eq!(metadata.publication_year(), Some(1972));
+    }
+
+    #[test]
+    fn skips_files_without_core_properties() {
+        let (_dir, path) = docx(&[("word/document.xml", "<w:document/>")]);
+        assert!(DocxExtractor.extract(&path).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.docx");
+        std::fs::write(&path, "not a zip").unwrap();
+        assert!(DocxExtractor.extract(&path).is_none());
+    }
+}
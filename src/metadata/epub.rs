@@ -0,0 +1,85 @@
+use epub::doc::{EpubDoc, MetadataItem};
+use std::path::Path;
+
+use super::{BookMetadata, Identifier, MetadataExtractor, non_empty, plain_text};
+
+pub struct EpubExtractor;
+
+impl MetadataExtractor for EpubExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["epub"]
+    }
+
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let doc = EpubDoc::new(path).ok()?;
+        let values = |property: &str| -> Vec<&MetadataItem> {
+            doc.metadata
+                .iter()
+                .filter(|item| item.property == property)
+                .collect()
+        };
+        let first = |property: &str| doc.mdata(property).and_then(|item| non_empty(&item.value));
+
+        let identifiers = values("identifier")
+            .into_iter()
+            .filter(|item| !item.value.trim().is_empty())
+            .map(|item| {
+                // EPUB 2 gives the scheme as an attribute, EPUB 3 as a refinement
+                let scheme = item
+                    .refinement("scheme")
+                    .or_else(|| item.refinement("identifier-type"))
+                    .map(|refinement| refinement.value.as_str());
+                Identifier::guess(&item.value, scheme)
+            })
+            .collect();
+
+        // EPUB 3 collections, or the calibre metadata most EPUB 2 files carry
+        let (series, series_index) = match doc.mdata("belongs-to-collection") {
+            Some(collection) => (
+                non_empty(&collection.value),
+                collection
+                    .refinement("group-position")
+                    .and_then(|position| non_empty(&position.value)),
+            ),
+            None => (first("calibre:series"), first("calibre:series_index")),
+        };
+
+        // Creators and contributors by their MARC relator role, creators without
+        // one being authors
+        let credited = |role: &str| -> Vec<String> {
+            values("creator")
+                .into_iter()
+                .chain(values("contributor"))
+                .filter(|item| {
+                    let item_role = item
+                        .refinement("role")
+                        .map(|refinement| refinement.value.trim().to_lowercase());
+                    match item_role.as_deref() {
+                        Some(item_role) => item_role == role,
+                        None => role == "aut" && item.property == "creator",
+                    }
+                })
+                .filter_map(|item| non_empty(&item.value))
+                .collect()
+        };
+
+        Some(BookMetadata {
+            title: first("title"),
+            authors: credited("aut"),
+            translators: credited("trl"),
+            editors: credited("edt"),
+            publisher: first("publisher"),
+            language: first("language"),
+            description: first("description").and_then(|description| plain_text(&description)),
+            identifiers,
+            date: first("date"),
+            series,
+            series_index,
+            page_count: None,
+        })
+    }
+
+    fn cover(&self, path: &Path) -> Option<Vec<u8>> {
+        EpubDoc::new(path).ok()?.get_cover().map(|(image, _)| image)
+    }
+}
@@ -0,0 +1,197 @@
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+use super::{BookMetadata, Identifier, MetadataExtractor};
+
+/// FictionBook 2 files.
+pub struct Fb2Extractor;
+
+impl MetadataExtractor for Fb2Extractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["fb2"]
+    }
+
+    /// Read the `<title-info>` and `<publish-info>` of the description.
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let file = std::io::BufReader::new(std::fs::File::open(path).ok()?);
+
+        let mut metadata = BookMetadata::default();
+        let mut stack: Vec<String> = Vec::new();
+        let mut author: Vec<String> = Vec::new();
+        let mut text = String::new();
+        let mut annotation = String::new();
+
+        for event in EventReader::new(file) {
+            match event.ok()? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    text.clear();
+                    let attribute = |key: &str| {
+                        attributes
+                            .iter()
+                            .find(|attribute| attribute.name.local_name == key)
+                            .map(|attribute| attribute.value.clone())
+                    };
+                    match (stack.last().map(String::as_str), name.local_name.as_str()) {
+                        (Some("title-info"), "date") => metadata.date = attribute("value"),
+                        (Some("title-info"), "sequence") if metadata.series.is_none() => {
+                            metadata.series = attribute("name");
+                            metadata.series_index = attribute("number");
+                        }
+                        _ => {}
+                    }
+                    stack.push(name.local_name);
+                }
+                XmlEvent::Characters(chunk) => {
+                    // Annotations are paragraphs of their own, so gather them separately
+                    if stack.iter().any(|name| name == "annotation") {
+                        annotation.push_str(&chunk);
+                        annotation.push(' ');
+                    }
+                    text.push_str(&chunk);
+                }
+                XmlEvent::EndElement { .. } => {
+                    let name = stack.pop().unwrap_or_default();
+                    let parent = stack.last().map(String::as_str);
+                    let value = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    text.clear();
+
+                    match (parent, name.as_str()) {
+                        (Some("title-info"), "book-title") if !value.is_empty() => {
+                            metadata.title = Some(value)
+                        }
+                        (Some("title-info"), "date")
+                            if metadata.date.is_none() && !value.is_empty() =>
+                        {
+                            metadata.date = Some(value)
+                        }
+                        (Some("title-info"), "lang") if !value.is_empty() => {
+                            metadata.language = Some(value)
+                        }
+                        (Some("title-info"), "annotation") => {
+                            let description = annotation.split_whitespace().collect::<Vec<_>>();
+                            if !description.is_empty() {
+                                metadata.description = Some(description.join(" "));
+                            }
+                        }
+                        (
+                            Some("author" | "translator"),
+                            "first-name" | "middle-name" | "last-name" | "nickname",
+                        ) if !value.is_empty() => author.push(value),
+                        (Some("title-info"), "author") if !author.is_empty() => {
+                            metadata.authors.push(author.join(" "))
+                        }
+                        (Some("title-info"), "translator") if !author.is_empty() => {
+                            metadata.translators.push(author.join(" "))
+                        }
+                        (Some("publish-info"), "publisher") if !value.is_empty() => {
+                            metadata.publisher = Some(value)
+                        }
+                        (Some("publish-info"), "isbn") if !value.is_empty() => {
+                            metadata.identifiers.push(Identifier::new("isbn", &value))
+                        }
+                        // The rest of the file is the text itself
+                        (_, "description") => break,
+                        _ => {}
+                    }
+                    // The file's own authors in <document-info> have names too
+                    if name == "author" || name == "translator" {
+                        author.clear();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOK: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0">
+  <description>
+    <title-info>
+      <genre>prose_classic</genre>
+      <author>
+        <first-name>Mikhail</first-name>
+        <middle-name>Afanasyevich</middle-name>
+        <last-name>Bulgakov</last-name>
+      </author>
+      <book-title>The Master and
+        Margarita</book-title>
+      <annotation>
+        <p>The Devil visits Moscow.</p>
+        <p>A manuscript does not burn.</p>
+      </annotation>
+      <date value="1967-01-01">1967</date>
+      <lang>en</lang>
+      <translator>
+        <first-name>Michael</first-name>
+        <last-name>Glenny</last-name>
+      </translator>
+      <sequence name="Collected Works" number="8"/>
+    </title-info>
+    <document-info>
+      <author><nickname>scanner</nickname></author>
+    </document-info>
+    <publish-info>
+      <publisher>Harvill</publisher>
+      <isbn>978-0-00-271069-5</isbn>
+    </publish-info>
+  </description>
+  <body><p>Never talk to strangers"#;
+
+    #[test]
+    fn reads_title_and_publish_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.fb2");
+        std::fs::write(&path, BOOK).unwrap();
+        let metadata = Fb2Extractor.extract(&path).unwrap();
+
+        assert_eq!(metadata.title.as_deref(), Some("The Master and Margarita"));
+        assert_eq!(metadata.authors, ["Mikhail Afanasyevich Bulgakov"]);
+        assert_eq!(metadata.translators, ["Michael Glenny"]);
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("The Devil visits Moscow. A manuscript does not burn.")
+        );
+        assert_eq!(metadata.date.as_deref(), Some("1967-01-01"));
+        assert_eq!(metadata.language.as_deref(), Some("en"));
+        assert_eq!(metadata.series.as_deref(), Some("Collected Works"));
+        assert_eq!(metadata.series_index.as_deref(), Some("8"));
+        assert_eq!(metadata.publisher.as_deref(), Some("Harvill"));
+        assert_eq!(metadata.identifier("isbn"), Some("978-0-00-271069-5"));
+    }
+
+    #[test]
+    fn reads_date_text_without_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.fb2");
+        std::fs::write(
+            &path,
+            r#"<FictionBook><description><title-info>
+                <book-title>Heart of a Dog</book-title>
+                <date>1925</date>
+            </title-info></description></FictionBook>"#,
+        )
+        .unwrap();
+        let metadata = Fb2Extractor.extract(&path).unwrap();
+
+        assert_eq!(metadata.title.as_deref(), Some("Heart of a Dog"));
+        assert!(metadata.authors.is_empty());
+        assert_eq!(metadata.publication_year(), Some(1925));
+    }
+
+    #[test]
+    fn skips_files_that_are_not_xml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.fb2");
+        std::fs::write(&path, "<FictionBook><description></title-info>").unwrap();
+        assert!(Fb2Extractor.extract(&path).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::Read;
+use std::path::Path;
+
+use super::{BookMetadata, Identifier, MetadataExtractor};
+
+/// Project Gutenberg headers end well within this many bytes.
+const HEADER_BYTES: u64 = 16 * 1024;
+
+/// Plain text files, which only have metadata when they come from Project
+/// Gutenberg.
+pub struct GutenbergExtractor;
+
+impl MetadataExtractor for GutenbergExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt"]
+    }
+
+    /// Read the "Title:", "Author:", "Language:" and "Release Date:" lines of a
+    /// Project Gutenberg text. `None` for other text files.
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let mut header = Vec::new();
+        std::fs::File::open(path)
+            .ok()?
+            .take(HEADER_BYTES)
+            .read_to_end(&mut header)
+            .ok()?;
+        let header = String::from_utf8_lossy(&header);
+        if !header.contains("Project Gutenberg") {
+            return None;
+        }
+
+        let mut metadata = BookMetadata::default();
+        let mut lines = header.lines().peekable();
+        while let Some(line) = lines.next() {
+            let line = line.trim_start_matches('\u{feff}');
+            if line.starts_with("*** START OF") {
+                break;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim();
+            if !matches!(
+                key,
+                "Title" | "Author" | "Language" | "Release Date" | "Release date"
+            ) {
+                continue;
+            }
+
+            // Long titles carry on over indented lines
+            let mut value = value.trim().to_string();
+            while let Some(next) = lines.peek() {
+                if !next.starts_with(char::is_whitespace) || next.trim().is_empty() {
+                    break;
+                }
+                value.push(' ');
+                value.push_str(next.trim());
+                lines.next();
+            }
+            if value.is_empty() {
+                continue;
+            }
+
+            match key {
+                "Title" if metadata.title.is_none() => metadata.title = Some(value),
+                "Author" if metadata.authors.is_empty() => metadata.authors.push(value),
+                "Language" if metadata.language.is_none() => metadata.language = Some(value),
+                // The release date is when the ebook came out, not the book, so
+                // only the ebook number is kept: "June, 1998 [eBook #1342]"
+                "Release Date" | "Release date" => {
+                    if let Some((_, number)) = value.split_once('#')
+                        && let Some(number) = number.split(']').next()
+                        && !number.trim().is_empty()
+                        && metadata.identifier("gutenberg").is_none()
+                    {
+                        metadata
+                            .identifiers
+                            .push(Identifier::new("gutenberg", number));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(text: &str) -> Option<BookMetadata> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.txt");
+        std::fs::write(&path, text).unwrap();
+        GutenbergExtractor.extract(&path)
+    }
+
+    #[test]
+    fn reads_gutenberg_header() {
+        let metadata = extract(
+            "\u{feff}The Project Gutenberg eBook of Pride and Prejudice
+
+Title: Pride and Prejudice,
+       a Novel
+
+Author: Jane Austen
+
+Release Date: June, 1998 [eBook #1342]
+Language: English
+
+*** START OF THE PROJECT GUTENBERG EBOOK PRIDE AND PREJUDICE ***
+
+Title: Chapter headings after the start are text
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            metadata.title.as_deref(),
+            Some("Pride and Prejudice, a Novel")
+        );
+        assert_eq!(metadata.authors, ["Jane Austen"]);
+        assert_eq!(metadata.language.as_deref(), Some("English"));
+        assert_eq!(metadata.identifier("gutenberg"), Some("1342"));
+        assert_eq!(metadata.publication_year(), None);
+    }
+
+    #[test]
+    fn ignores_other_text_files() {
+        assert!(extract("Title: My shopping list\nAuthor: Me\n").is_none());
+    }
+}
@@ -0,0 +1,386 @@
+use std::path::Path;
+
+use super::{BookMetadata, Identifier, MetadataExtractor, plain_text};
+
+/// EXTH record types read here
+const EXTH_AUTHOR: u32 = 100;
+const EXTH_PUBLISHER: u32 = 101;
+const EXTH_DESCRIPTION: u32 = 103;
+const EXTH_ISBN: u32 = 104;
+const EXTH_PUBLISHED: u32 = 106;
+const EXTH_ASIN: u32 = 113;
+const EXTH_COVER_OFFSET: u32 = 201;
+const EXTH_TITLE: u32 = 503;
+const EXTH_CDE_ASIN: u32 = 504;
+const EXTH_LANGUAGE: u32 = 524;
+
+/// The MOBI header's "no image" value for the first image record.
+const NO_RECORD: u32 = 0xFFFF_FFFF;
+
+const UTF8_ENCODING: u32 = 65001;
+
+/// Metadata of a Kindle book: a MOBI, AZW or AZW3 file.
+#[derive(Debug, Default)]
+pub struct MobiMetadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub description: Option<String>,
+    pub published: Option<String>,
+    pub isbn: Option<String>,
+    pub asin: Option<String>,
+    pub language: Option<String>,
+    /// Raw image data, usually JPEG
+    pub cover: Option<Vec<u8>>,
+}
+
+pub struct MobiExtractor;
+
+impl MetadataExtractor for MobiExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mobi", "azw", "azw3"]
+    }
+
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let mobi = parse_mobi(&std::fs::read(path).ok()?)?;
+        let identifiers = [("isbn", &mobi.isbn), ("asin", &mobi.asin)]
+            .into_iter()
+            .filter_map(|(scheme, value)| Some(Identifier::new(scheme, value.as_deref()?)))
+            .collect();
+
+        Some(BookMetadata {
+            title: mobi.title,
+            authors: mobi.authors,
+            translators: Vec::new(),
+            editors: Vec::new(),
+            publisher: mobi.publisher,
+            language: mobi.language,
+            description: mobi.description.as_deref().and_then(plain_text),
+            identifiers,
+            date: mobi.published,
+            series: None,
+            series_index: None,
+            page_count: None,
+        })
+    }
+
+    fn cover(&self, path: &Path) -> Option<Vec<u8>> {
+        parse_mobi(&std::fs::read(path).ok()?)?.cover
+    }
+}
+
+/// Parse the metadata of a MOBI file. `None` if it is not a Palm database with
+/// a MOBI header.
+///
+/// The first record holds a PalmDOC header, then a MOBI header whose EXTH block
+/// lists the metadata as typed records. Images, including the cover, are stored
+/// as later records.
+pub fn parse_mobi(data: &[u8]) -> Option<MobiMetadata> {
+    let records = palm_records(data)?;
+    let record0 = record(data, &records, 0)?;
+
+    // PalmDOC header is 16 bytes, then the MOBI header
+    let mobi = record0.get(16..)?;
+    if mobi.get(0..4)? != b"MOBI" {
+        return None;
+    }
+    let header_length = read_u32(mobi, 4)? as usize;
+    let encoding = read_u32(mobi, 12)?;
+    let first_image = read_u32(mobi, 92).unwrap_or(NO_RECORD);
+    let exth_flags = read_u32(mobi, 112).unwrap_or(0);
+
+    let mut metadata = MobiMetadata::default();
+    let mut cover_offset = None;
+
+    if exth_flags & 0x40 != 0
+        && let Some(exth) = mobi.get(header_length..)
+        && exth.get(0..4) == Some(b"EXTH")
+    {
+        let count = read_u32(exth, 8).unwrap_or(0);
+        let mut position = 12;
+        for _ in 0..count {
+            let Some(kind) = read_u32(exth, position) else {
+                break;
+            };
+            let Some(length) = read_u32(exth, position + 4).map(|length| length as usize) else {
+                break;
+            };
+            if length < 8 {
+                break;
+            }
+            let Some(value) = exth.get(position + 8..position + length) else {
+                break;
+            };
+            position += length;
+
+            match kind {
+                EXTH_AUTHOR => {
+                    if let Some(author) = decode_text(value, encoding) {
+                        metadata.authors.push(author);
+                    }
+                }
+                EXTH_PUBLISHER => metadata.publisher = decode_text(value, encoding),
+                EXTH_DESCRIPTION => metadata.description = decode_text(value, encoding),
+                EXTH_ISBN => metadata.isbn = decode_text(value, encoding),
+                EXTH_PUBLISHED => metadata.published = decode_text(value, encoding),
+                EXTH_ASIN | EXTH_CDE_ASIN if metadata.asin.is_none() => {
+                    metadata.asin = decode_text(value, encoding);
+                }
+                EXTH_TITLE => metadata.title = decode_text(value, encoding),
+                EXTH_LANGUAGE => metadata.language = decode_text(value, encoding),
+                EXTH_COVER_OFFSET => cover_offset = read_u32(value, 0),
+                _ => {}
+            }
+        }
+    }
+
+    // Without an updated title in EXTH, use the full name from the MOBI header
+    if metadata.title.is_none() {
+        let offset = read_u32(mobi, 68)? as usize;
+        let length = read_u32(mobi, 72)? as usize;
+        metadata.title = record0
+            .get(offset..offset.checked_add(length)?)
+            .and_then(|name| decode_text(name, encoding));
+    }
+
+    if let Some(offset) = cover_offset
+        && offset != NO_RECORD
+        && first_image != NO_RECORD
+    {
+        metadata.cover = first_image
+            .checked_add(offset)
+            .and_then(|index| record(data, &records, index as usize))
+            .filter(|image| !image.is_empty())
+            .map(<[u8]>::to_vec);
+    }
+
+    Some(metadata)
+}
+
+/// Offsets of the records in a Palm database.
+fn palm_records(data: &[u8]) -> Option<Vec<usize>> {
+    let kind = data.get(60..68)?;
+    if kind != b"BOOKMOBI" {
+        return None;
+    }
+    let count = read_u16(data, 76)? as usize;
+    (0..count)
+        .map(|index| read_u32(data, 78 + index * 8).map(|offset| offset as usize))
+        .collect()
+}
+
+/// A record's bytes, up to where the next one starts.
+fn record<'a>(data: &'a [u8], records: &[usize], index: usize) -> Option<&'a [u8]> {
+    let start = *records.get(index)?;
+    let end = records.get(index + 1).copied().unwrap_or(data.len());
+    data.get(start..end)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Text is UTF-8 or Windows-1252, as the MOBI header says.
+fn decode_text(bytes: &[u8], encoding: u32) -> Option<String> {
+    let text: String = if encoding == UTF8_ENCODING {
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        bytes.iter().map(|&byte| windows_1252(byte)).collect()
+    };
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9F => HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An EXTH block with the given records.
+    fn exth(records: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (kind, value) in records {
+            body.extend(kind.to_be_bytes());
+            body.extend((value.len() as u32 + 8).to_be_bytes());
+            body.extend(*value);
+        }
+        let mut block = b"EXTH".to_vec();
+        block.extend((body.len() as u32 + 12).to_be_bytes());
+        block.extend((records.len() as u32).to_be_bytes());
+        block.extend(body);
+        block
+    }
+
+    /// A Palm database whose first record has a MOBI header followed by
+    /// `exth` and the full name, and whose later records are `images`.
+    fn mobi_file(exth: &[u8], name: &str, encoding: u32, images: &[&[u8]]) -> Vec<u8> {
+        let header_length = 232;
+        let mut header = vec![0; header_length];
+        header[0..4].copy_from_slice(b"MOBI");
+        header[4..8].copy_from_slice(&(header_length as u32).to_be_bytes());
+        header[12..16].copy_from_slice(&encoding.to_be_bytes());
+        let name_offset = 16 + header_length + exth.len();
+        header[68..72].copy_from_slice(&(name_offset as u32).to_be_bytes());
+        header[72..76].copy_from_slice(&(name.len() as u32).to_be_bytes());
+        let first_image = if images.is_empty() { NO_RECORD } else { 1 };
+        header[92..96].copy_from_slice(&first_image.to_be_bytes());
+        let flags: u32 = if exth.is_empty() { 0 } else { 0x40 };
+        header[112..116].copy_from_slice(&flags.to_be_bytes());
+
+        let mut record0 = vec![0; 16];
+        record0.extend(header);
+        record0.extend(exth);
+        record0.extend(name.as_bytes());
+
+        let mut records = vec![record0];
+        records.extend(images.iter().map(|image| image.to_vec()));
+
+        let mut data = vec![0; 78];
+        data[60..68].copy_from_slice(b"BOOKMOBI");
+        data[76..78].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        let mut offset = 78 + records.len() * 8;
+        for record in &records {
+            data.extend((offset as u32).to_be_bytes());
+            data.extend([0; 4]);
+            offset += record.len();
+        }
+        for record in records {
+            data.extend(record);
+        }
+        data
+    }
+
+    #[test]
+    fn reads_exth_records() {
+        let exth = exth(&[
+            (EXTH_TITLE, b"Invisible Cities"),
+            (EXTH_AUTHOR, b"Italo Calvino"),
+            (EXTH_AUTHOR, b"William Weaver"),
+            (EXTH_PUBLISHER, b"Harcourt"),
+            (EXTH_ISBN, b"9780156453806"),
+            (EXTH_ASIN, b"B00000000A"),
+            (EXTH_CDE_ASIN, b"B00000000B"),
+            (EXTH_LANGUAGE, b"en"),
+            (EXTH_COVER_OFFSET, &0u32.to_be_bytes()),
+        ]);
+        let data = mobi_file(&exth, "Fallback", UTF8_ENCODING, &[b"cover image"]);
+        let mobi = parse_mobi(&data).unwrap();
+
+        assert_eq!(mobi.title.as_deref(), Some("Invisible Cities"));
+        assert_eq!(mobi.authors, ["Italo Calvino", "William Weaver"]);
+        assert_eq!(mobi.publisher.as_deref(), Some("Harcourt"));
+        assert_eq!(mobi.isbn.as_deref(), Some("9780156453806"));
+        assert_eq!(mobi.asin.as_deref(), Some("B00000000A"));
+        assert_eq!(mobi.language.as_deref(), Some("en"));
+        assert_eq!(mobi.cover.as_deref(), Some(&b"cover image"[..]));
+    }
+
+    #[test]
+    fn falls_back_to_full_name() {
+        let exth = exth(&[(EXTH_AUTHOR, b"Italo Calvino")]);
+        let mobi = parse_mobi(&mobi_file(&exth, "Invisible Cities", UTF8_ENCODING, &[])).unwrap();
+        assert_eq!(mobi.title.as_deref(), Some("Invisible Cities"));
+        assert_eq!(mobi.cover, None);
+
+        let mobi = parse_mobi(&mobi_file(&[], "Invisible Cities", UTF8_ENCODING, &[])).unwrap();
+        assert_eq!(mobi.title.as_deref(), Some("Invisible Cities"));
+        assert!(mobi.authors.is_empty());
+    }
+
+    #[test]
+    fn decodes_windows_1252() {
+        let exth = exth(&[(EXTH_TITLE, b"\x93Caf\xe9\x94")]);
+        let mobi = parse_mobi(&mobi_file(&exth, "", 1252, &[])).unwrap();
+        assert_eq!(mobi.title.as_deref(), Some("“Café”"));
+    }
+
+    #[test]
+    fn stops_at_record_past_the_end() {
+        let mut exth = exth(&[
+            (EXTH_AUTHOR, b"Italo Calvino"),
+            (EXTH_PUBLISHER, b"Harcourt"),
+        ]);
+        // The publisher record claims more bytes than the file has
+        let publisher = 12 + 8 + b"Italo Calvino".len();
+        exth[publisher + 4..publisher + 8].copy_from_slice(&0xFFFF_FFFFu32.to_be_bytes());
+
+        let mobi = parse_mobi(&mobi_file(&exth, "Invisible Cities", UTF8_ENCODING, &[])).unwrap();
+        assert_eq!(mobi.authors, ["Italo Calvino"]);
+        assert_eq!(mobi.publisher, None);
+        assert_eq!(mobi.title.as_deref(), Some("Invisible Cities"));
+    }
+
+    #[test]
+    fn stops_at_record_shorter_than_its_header() {
+        let mut exth = exth(&[
+            (EXTH_AUTHOR, b"Italo Calvino"),
+            (EXTH_PUBLISHER, b"Harcourt"),
+        ]);
+        let publisher = 12 + 8 + b"Italo Calvino".len();
+        exth[publisher + 4..publisher + 8].copy_from_slice(&4u32.to_be_bytes());
+
+        let mobi = parse_mobi(&mobi_file(&exth, "Invisible Cities", UTF8_ENCODING, &[])).unwrap();
+        assert_eq!(mobi.authors, ["Italo Calvino"]);
+        assert_eq!(mobi.publisher, None);
+    }
+
+    #[test]
+    fn stops_when_count_exceeds_records() {
+        let mut exth = exth(&[(EXTH_AUTHOR, b"Italo Calvino")]);
+        exth[8..12].copy_from_slice(&1000u32.to_be_bytes());
+
+        // The full name follows the block and is read as a broken record
+        let mobi = parse_mobi(&mobi_file(&exth, "Invisible Cities", UTF8_ENCODING, &[])).unwrap();
+        assert_eq!(mobi.authors, ["Italo Calvino"]);
+        assert_eq!(mobi.title.as_deref(), Some("Invisible Cities"));
+    }
+
+    #[test]
+    fn truncated_exth_header_keeps_full_name() {
+        let mobi = parse_mobi(&mobi_file(b"EXTH", "Ti", UTF8_ENCODING, &[])).unwrap();
+        assert_eq!(mobi.title.as_deref(), Some("Ti"));
+        assert!(mobi.authors.is_empty());
+    }
+
+    #[test]
+    fn cover_record_out_of_range() {
+        let exth = exth(&[(EXTH_COVER_OFFSET, &5u32.to_be_bytes())]);
+        let mobi = parse_mobi(&mobi_file(&exth, "Title", UTF8_ENCODING, &[b"image"])).unwrap();
+        assert_eq!(mobi.cover, None);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse_mobi(b"").is_none());
+        assert!(parse_mobi(&[0; 100]).is_none());
+
+        let mut data = mobi_file(&[], "Title", UTF8_ENCODING, &[]);
+        let record0 = 78 + 8;
+        data[record0 + 16..record0 + 20].copy_from_slice(b"TEXT");
+        assert!(parse_mobi(&data).is_none());
+
+        let data = mobi_file(&[], "Title", UTF8_ENCODING, &[]);
+        assert!(parse_mobi(&data[..100]).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use image::{DynamicImage, ImageFormat};
+use lopdf::Document;
+use std::path::Path;
+
+use super::{BookMetadata, MetadataExtractor};
+
+pub struct PdfExtractor;
+
+impl MetadataExtractor for PdfExtractor {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["pdf"]
+    }
+
+    /// Read the document information dictionary and count the pages.
+    fn extract(&self, path: &Path) -> Option<BookMetadata> {
+        let doc = Document::load(path).ok()?;
+        let mut metadata = BookMetadata {
+            page_count: u32::try_from(doc.get_pages().len())
+                .ok()
+                .filter(|count| *count > 0),
+            ..BookMetadata::default()
+        };
+
+        // Get the Info dictionary reference from trailer
+        let info_dict = doc
+            .trailer
+            .get(b"Info")
+            .and_then(|info_ref| info_ref.as_reference())
+            .and_then(|info_ref| doc.get_dictionary(info_ref));
+        if let Ok(info_dict) = info_dict {
+            metadata.title = get_pdf_string(&doc, info_dict, b"Title");
+            metadata.authors = get_pdf_string(&doc, info_dict, b"Author")
+                .into_iter()
+                .collect();
+            metadata.description = get_pdf_string(&doc, info_dict, b"Subject");
+            metadata.date = get_pdf_string(&doc, info_dict, b"CreationDate");
+        }
+
+        Some(metadata)
+    }
+
+    /// The largest image on the first page. Pages are not rendered, so a first
+    /// page made of text and vector drawings has no cover.
+    fn cover(&self, path: &Path) -> Option<Vec<u8>> {
+        let doc = Document::load(path).ok()?;
+        let (_, page_id) = doc.get_pages().into_iter().next()?;
+        let images = doc.get_page_images(page_id).ok()?;
+        let image = images
+            .iter()
+            .max_by_key(|image| image.width * image.height)?;
+
+        let filters = image.filters.clone().unwrap_or_default();
+        if filters.iter().any(|filter| filter == "DCTDecode") {
+            // JPEG data is stored as is
+            return Some(image.content.to_vec());
+        }
+
+        // Otherwise only plain 8-bit RGB or greyscale pixels can be turned into an image
+        if image.bits_per_component != Some(8) {
+            return None;
+        }
+        let pixels = doc
+            .get_object(image.id)
+            .ok()?
+            .as_stream()
+            .ok()?
+            .decompressed_content()
+            .ok()?;
+        let (width, height) = (
+            u32::try_from(image.width).ok()?,
+            u32::try_from(image.height).ok()?,
+        );
+        let decoded = match image.color_space.as_deref() {
+            Some("DeviceRGB") => {
+                DynamicImage::ImageRgb8(image::RgbImage::from_raw(width, height, pixels)?)
+            }
+            Some("DeviceGray") => {
+                DynamicImage::ImageLuma8(image::GrayImage::from_raw(width, height, pixels)?)
+            }
+            _ => return None,
+        };
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        decoded.write_to(&mut png, ImageFormat::Png).ok()?;
+        Some(png.into_inner())
+    }
+}
+
+/// A text value of the info dictionary, skipping binary junk some producers
+/// leave there.
+fn get_pdf_string(doc: &Document, dict: &lopdf::Dictionary, key: &[u8]) -> Option<String> {
+    let obj = dict.get(key).ok()?;
+
+    // Handle both direct strings and references
+    let text = match obj {
+        lopdf::Object::String(bytes, _) => decode_pdf_string(bytes)?,
+        lopdf::Object::Reference(r) => {
+            if let Ok(lopdf::Object::String(bytes, _)) = doc.get_object(*r) {
+                decode_pdf_string(bytes)?
+            } else {
+                return None;
+            }
+        }
+        _ => return None,
+    };
+
+    let text = text.trim();
+    if is_printable_text(text) {
+        Some(text.to_string())
+    } else {
+        None
+    }
+}
+
+fn decode_pdf_string(bytes: &[u8]) -> Option<String> {
+    // Try UTF-16 BE first (starts with BOM 0xFE 0xFF)
+    if bytes.len() >= 2 && bytes[0] == 0xFE && bytes[1] == 0xFF {
+        let utf16: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .filter_map(|chunk| {
+                if chunk.len() == 2 {
+                    Some(u16::from_be_bytes([chunk[0], chunk[1]]))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        String::from_utf16(&utf16).ok()
+    } else {
+        // Try as Latin-1/UTF-8
+        Some(String::from_utf8_lossy(bytes).to_string())
+    }
+}
+
+/// Check if a string contains disallowed control characters (Unicode Cc category, except \t \n \r)
+fn is_printable_text(s: &str) -> bool {
+    if s.is_empty() {
+        return false;
+    }
+
+    // Check for replacement characters (indicates failed UTF-8 decoding)
+    if s.contains('\u{FFFD}') {
+        return false;
+    }
+
+    // Disallowed control characters (Unicode Cc category except \t, \n, \r)
+    for c in s.chars() {
+        match c {
+            '\x00'..='\x08' | '\x0B' | '\x0C' | '\x0E'..='\x1F' | '\x7F' | '\u{80}'..='\u{9F}' => {
+                return false;
+            }
+            _ => {}
+        }
+    }
+
+    true
+}
@@ -0,0 +1,569 @@
+use askama::Template;
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use epub::doc::{EpubDoc, NavPoint};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use serde::Deserialize;
+use std::path::{Component, PathBuf};
+use xml::reader::{ParserConfig, XmlEvent};
+
+use crate::AppState;
+use crate::auth::{current_user, signups_disabled};
+use crate::books::Book;
+use crate::files::{book_file_response, library_file};
+use crate::templates::BookReadTemplate;
+
+/// Characters left as they are in archive paths put into URLs.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Book content runs in the reader frame, but its scripts must not run with the
+/// reader's session.
+const EPUB_CONTENT_SECURITY_POLICY: &str =
+    "sandbox allow-same-origin; script-src 'none'; object-src 'none'";
+
+/// Where a user left off in a book: a chapter in the EPUB spine, and how far
+/// through it they scrolled, from 0 to 1.
+pub struct ReadingPosition {
+    pub chapter: usize,
+    pub progress: f64,
+}
+
+/// A line of the table of contents. `depth` is 0 for top-level entries.
+pub struct TocEntry {
+    pub label: String,
+    pub chapter: usize,
+    pub fragment: Option<String>,
+    pub depth: usize,
+}
+
+/// A table of contents link before it is matched to a chapter.
+struct TocLink {
+    label: String,
+    path: String,
+    fragment: Option<String>,
+    depth: usize,
+}
+
+/// The parts of an EPUB the reader needs: the archive path of every chapter in
+/// reading order, and the table of contents pointing into them.
+struct EpubOutline {
+    chapters: Vec<String>,
+    toc: Vec<TocEntry>,
+}
+
+/// Whether the reader can open a book file.
+pub fn can_read(filepath: &str) -> bool {
+    let filepath = filepath.to_lowercase();
+    filepath.ends_with(".epub") || filepath.ends_with(".pdf")
+}
+
+fn is_pdf(filepath: &str) -> bool {
+    filepath.to_lowercase().ends_with(".pdf")
+}
+
+/// Read the spine and table of contents of an EPUB. The table of contents comes
+/// from the EPUB 3 nav document, or the EPUB 2 NCX when there is none.
+fn epub_outline(path: &std::path::Path) -> Option<EpubOutline> {
+    let mut doc = EpubDoc::new(path).ok()?;
+
+    let chapters: Vec<String> = doc
+        .spine
+        .iter()
+        .filter_map(|item| doc.resources.get(&item.idref))
+        .map(|resource| path_string(&resource.path))
+        .collect();
+
+    let mut links = Vec::new();
+    if let Some(nav) = doc.get_nav_id().and_then(|id| doc.resources.get(&id)) {
+        let nav_path = nav.path.clone();
+        if let Some(content) = doc.get_resource_by_path(&nav_path) {
+            let base = nav_path.parent().map(path_string).unwrap_or_default();
+            links = parse_nav(&content)
+                .into_iter()
+                .map(|(label, href, depth)| {
+                    let (path, fragment) = resolve_href(&base, &href);
+                    TocLink {
+                        label,
+                        path,
+                        fragment,
+                        depth,
+                    }
+                })
+                .collect();
+        }
+    }
+    if links.is_empty() {
+        flatten_ncx(&doc.toc, 0, &mut links);
+    }
+
+    let toc = links
+        .into_iter()
+        .filter_map(|link| {
+            let chapter = chapters.iter().position(|chapter| *chapter == link.path)?;
+            Some(TocEntry {
+                label: link.label,
+                chapter,
+                fragment: link.fragment,
+                depth: link.depth,
+            })
+        })
+        .collect();
+
+    Some(EpubOutline { chapters, toc })
+}
+
+/// Links from the `toc` nav of an EPUB 3 navigation document, as label, href
+/// and nesting depth.
+fn parse_nav(content: &[u8]) -> Vec<(String, String, usize)> {
+    let reader = ParserConfig::new()
+        .add_entity("nbsp", "\u{a0}")
+        .create_reader(content);
+
+    let mut links = Vec::new();
+    let mut depth = 0;
+    // Element depth of the toc nav while inside it
+    let mut toc_nav: Option<usize> = None;
+    let mut lists = 0;
+    let mut link: Option<(String, String)> = None;
+
+    for event in reader {
+        let Ok(event) = event else {
+            // Whatever was read before a malformed part is still usable
+            break;
+        };
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                depth += 1;
+                match name.local_name.as_str() {
+                    "nav" if toc_nav.is_none() => {
+                        let is_toc = attributes.iter().any(|attribute| {
+                            attribute.name.local_name == "type"
+                                && attribute.value.split_whitespace().any(|kind| kind == "toc")
+                        });
+                        if is_toc {
+                            toc_nav = Some(depth);
+                        }
+                    }
+                    "ol" if toc_nav.is_some() => lists += 1,
+                    "a" if toc_nav.is_some() => {
+                        if let Some(href) = attributes
+                            .iter()
+                            .find(|attribute| attribute.name.local_name == "href")
+                        {
+                            link = Some((href.value.clone(), String::new()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some((_, label)) = &mut link {
+                    label.push_str(&text);
+                }
+            }
+            XmlEvent::Whitespace(_) => {
+                if let Some((_, label)) = &mut link {
+                    label.push(' ');
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                match name.local_name.as_str() {
+                    "nav" if toc_nav == Some(depth) => toc_nav = None,
+                    "ol" if toc_nav.is_some() => lists -= 1,
+                    "a" => {
+                        if let Some((href, label)) = link.take() {
+                            let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+                            if !label.is_empty() {
+                                links.push((label, href, lists.max(1) - 1));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+
+    links
+}
+
+/// Table of contents entries from an EPUB 2 NCX, whose paths are already
+/// relative to the archive root.
+fn flatten_ncx(points: &[NavPoint], depth: usize, links: &mut Vec<TocLink>) {
+    for point in points {
+        let (path, fragment) = resolve_href("", &path_string(&point.content));
+        links.push(TocLink {
+            label: point.label.trim().to_string(),
+            path,
+            fragment,
+            depth,
+        });
+        flatten_ncx(&point.children, depth + 1, links);
+    }
+}
+
+/// Resolve a link found in a file under `base` to an archive path and fragment.
+fn resolve_href(base: &str, href: &str) -> (String, Option<String>) {
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment.to_string())),
+        None => (href, None),
+    };
+    let path = percent_decode_str(path).decode_utf8_lossy();
+
+    let mut resolved = PathBuf::new();
+    for component in PathBuf::from(base).join(path.as_ref()).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            _ => {}
+        }
+    }
+
+    (path_string(&resolved), fragment)
+}
+
+/// Archive paths always use `/`, whatever the platform.
+fn path_string(path: &std::path::Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Where the reader frame loads a file from the archive.
+fn resource_url(book_id: &str, path: &str) -> String {
+    let encoded: Vec<String> = path
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect();
+    format!("/books/{}/epub/{}", book_id, encoded.join("/"))
+}
+
+fn guess_content_type(path: &str) -> &'static str {
+    let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "xhtml" | "xht" => "application/xhtml+xml",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "svg" => "image/svg+xml",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "otf" => "font/otf",
+        "ttf" => "font/ttf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+async fn readable_book(
+    db: &AppState,
+    book_id: &str,
+    viewer_id: Option<&str>,
+) -> Result<Book, Response> {
+    match db.get_visible_book(book_id, viewer_id).await {
+        Ok(Some(book)) if book.filepath.as_deref().is_some_and(can_read) => Ok(book),
+        Ok(Some(_)) => {
+            Err((StatusCode::NOT_FOUND, "This book has no EPUB or PDF file").into_response())
+        }
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Book not found").into_response()),
+        Err(error) => {
+            eprintln!("Error fetching book: {error}");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReadQuery {
+    pub chapter: Option<usize>,
+    pub fragment: Option<String>,
+}
+
+pub async fn book_read(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+    Query(query): Query<ReadQuery>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let book = match readable_book(&db, &book_id, viewer_id).await {
+        Ok(book) => book,
+        Err(response) => return response,
+    };
+    let Some(full_path) = library_file(book.filepath.as_deref().unwrap_or_default()) else {
+        return (StatusCode::NOT_FOUND, "File not found on disk").into_response();
+    };
+
+    // Browsers have their own PDF viewer
+    if is_pdf(book.filepath.as_deref().unwrap_or_default()) {
+        return book_file_response(&full_path, "inline", &headers).await;
+    }
+
+    // A malformed EPUB can make the zip or XML code panic
+    let outline = match tokio::task::spawn_blocking(move || epub_outline(&full_path)).await {
+        Ok(outline) => outline,
+        Err(error) => {
+            eprintln!("Reader task failed for book {}: {error}", book.id);
+            return (StatusCode::UNPROCESSABLE_ENTITY, "Could not open this EPUB").into_response();
+        }
+    };
+    let Some(outline) = outline.filter(|outline| !outline.chapters.is_empty()) else {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "Could not open this EPUB").into_response();
+    };
+
+    // Pick up where the user left off, unless they asked for a chapter
+    let saved = match &user {
+        Some(user) if query.chapter.is_none() => db
+            .get_reading_position(&user.id, &book.id)
+            .await
+            .unwrap_or_else(|error| {
+                eprintln!("Error fetching reading position: {error}");
+                None
+            }),
+        _ => None,
+    };
+    let last = outline.chapters.len() - 1;
+    let (chapter, resume_progress) = match (query.chapter, saved) {
+        (Some(chapter), _) => (chapter.min(last), 0.0),
+        (None, Some(position)) if position.chapter <= last => (position.chapter, position.progress),
+        _ => (0, 0.0),
+    };
+
+    let mut chapter_url = resource_url(&book.id, &outline.chapters[chapter]);
+    if let Some(fragment) = query.fragment.filter(|fragment| !fragment.is_empty()) {
+        chapter_url.push('#');
+        chapter_url.push_str(&utf8_percent_encode(&fragment, PATH_SEGMENT).to_string());
+    }
+
+    let template = BookReadTemplate {
+        is_authenticated: user.is_some(),
+        signups_disabled: signups_disabled(),
+        username: user
+            .as_ref()
+            .map(|u| u.username.clone())
+            .unwrap_or_default(),
+        toc: outline.toc,
+        chapter,
+        chapter_count: outline.chapters.len(),
+        chapter_url,
+        resume_progress,
+        book,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ReadingPositionForm {
+    pub chapter: usize,
+    pub progress: f64,
+}
+
+/// Remember how far the user got, sent by the reader page as they scroll.
+pub async fn reading_position_submit(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+    Form(form): Form<ReadingPositionForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    if let Err(response) = readable_book(&db, &book_id, Some(&user.id)).await {
+        return response;
+    }
+
+    let position = ReadingPosition {
+        chapter: form.chapter,
+        progress: if form.progress.is_finite() {
+            form.progress.clamp(0.0, 1.0)
+        } else {
+            0.0
+        },
+    };
+    match db
+        .save_reading_position(&user.id, &book_id, &position)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => {
+            eprintln!("Error saving reading position: {error}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// A chapter, stylesheet, image or font from inside an EPUB.
+pub async fn epub_resource(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, path)): Path<(String, String)>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+    let viewer_id = user.as_ref().map(|u| u.id.as_str());
+
+    let book = match readable_book(&db, &book_id, viewer_id).await {
+        Ok(book) => book,
+        Err(response) => return response,
+    };
+    let filepath = book.filepath.unwrap_or_default();
+    if is_pdf(&filepath) {
+        return (StatusCode::NOT_FOUND, "Not an EPUB").into_response();
+    }
+
+    let Some(full_path) = library_file(&filepath) else {
+        return (StatusCode::NOT_FOUND, "File not found on disk").into_response();
+    };
+    let resource = tokio::task::spawn_blocking(move || {
+        let mut doc = EpubDoc::new(&full_path).ok()?;
+        let content = doc.get_resource_by_path(&path)?;
+        let content_type = doc
+            .get_resource_mime_by_path(&path)
+            .unwrap_or_else(|| guess_content_type(&path).to_string());
+        Some((content, content_type))
+    })
+    .await;
+
+    let resource = match resource {
+        Ok(resource) => resource,
+        Err(error) => {
+            eprintln!("Reader task failed for book {}: {error}", book_id);
+            return (StatusCode::UNPROCESSABLE_ENTITY, "Could not read this EPUB").into_response();
+        }
+    };
+    let Some((content, content_type)) = resource else {
+        return (StatusCode::NOT_FOUND, "Not found in this EPUB").into_response();
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                EPUB_CONTENT_SECURITY_POLICY.to_string(),
+            ),
+            (header::CACHE_CONTROL, "private, max-age=3600".to_string()),
+        ],
+        content,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hrefs_stay_inside_the_archive() {
+        assert_eq!(
+            resolve_href("OEBPS/text", "../../../../etc/passwd"),
+            ("etc/passwd".to_string(), None)
+        );
+        assert_eq!(
+            resolve_href("OEBPS/text", "../images/./map.png"),
+            ("OEBPS/images/map.png".to_string(), None)
+        );
+        assert_eq!(
+            resolve_href("OEBPS", "/mimetype"),
+            ("mimetype".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn hrefs_are_decoded_and_lose_their_fragment() {
+        assert_eq!(
+            resolve_href("OEBPS", "chapter%201.xhtml#part-2"),
+            (
+                "OEBPS/chapter 1.xhtml".to_string(),
+                Some("part-2".to_string())
+            )
+        );
+        assert_eq!(
+            resolve_href("", "Text/Capitolo%C3%A0.xhtml#"),
+            ("Text/Capitoloà.xhtml".to_string(), Some(String::new()))
+        );
+        assert_eq!(
+            path_string(std::path::Path::new("/OEBPS/./text/ch1.xhtml")),
+            "OEBPS/text/ch1.xhtml"
+        );
+    }
+
+    const NAV: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type="landmarks"><ol><li><a href="cover.xhtml">Cover</a></li></ol></nav>
+  <nav epub:type="toc">
+    <h1>Contents</h1>
+    <ol>
+      <li><a href="one.xhtml">Part
+        One</a>
+        <ol>
+          <li><a href="one.xhtml#a">Cities &amp; <em>Memory</em>&nbsp;1</a>
+            <ol><li><a href="one.xhtml#b">Diomira</a></li></ol>
+          </li>
+        </ol>
+      </li>
+      <li><a href="two.xhtml">Part Two</a></li>
+      <li><a href="empty.xhtml"> </a></li>
+    </ol>
+  </nav>
+  <nav epub:type="page-list"><ol><li><a href="one.xhtml#p1">1</a></li></ol></nav>
+</body>
+</html>"#;
+
+    #[test]
+    fn nav_links_have_their_nesting_depth() {
+        let links = parse_nav(NAV.as_bytes());
+        let links: Vec<(&str, &str, usize)> = links
+            .iter()
+            .map(|(label, href, depth)| (label.as_str(), href.as_str(), *depth))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                ("Part One", "one.xhtml", 0),
+                ("Cities & Memory 1", "one.xhtml#a", 1),
+                ("Diomira", "one.xhtml#b", 2),
+                ("Part Two", "two.xhtml", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_nav_keeps_what_was_read() {
+        let cut = &NAV[..NAV.find("Part Two").unwrap()];
+        let links = parse_nav(cut.as_bytes());
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[2].2, 2);
+
+        let broken = NAV.replace(
+            "<li><a href=\"two.xhtml\">",
+            "<li><a href=\"two.xhtml\"></li>",
+        );
+        assert_eq!(parse_nav(broken.as_bytes()).len(), 3);
+        assert!(parse_nav(b"not xml at all").is_empty());
+    }
+}
@@ -0,0 +1,459 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use crate::auth::current_user;
+
+/// Where a reader is with a book.
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ReadingStatus {
+    WantToRead,
+    Reading,
+    Finished,
+    Abandoned,
+}
+
+impl ReadingStatus {
+    pub const ALL: [ReadingStatus; 4] = [
+        ReadingStatus::WantToRead,
+        ReadingStatus::Reading,
+        ReadingStatus::Finished,
+        ReadingStatus::Abandoned,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadingStatus::WantToRead => "want_to_read",
+            ReadingStatus::Reading => "reading",
+            ReadingStatus::Finished => "finished",
+            ReadingStatus::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReadingStatus::WantToRead => "want to read",
+            ReadingStatus::Reading => "reading",
+            ReadingStatus::Finished => "finished",
+            ReadingStatus::Abandoned => "abandoned",
+        }
+    }
+
+    /// A finished or abandoned read-through is over; picking the book up again starts a re-read.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, ReadingStatus::Finished | ReadingStatus::Abandoned)
+    }
+}
+
+/// One read-through of a book by a user. Dates are `YYYY-MM-DD`.
+pub struct Reading {
+    pub id: String,
+    pub book_id: String,
+    pub status: ReadingStatus,
+    pub started_on: Option<String>,
+    pub finished_on: Option<String>,
+    pub created_at: String,
+}
+
+pub struct FinishedBook {
+    pub book_id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub finished_on: Option<String>,
+}
+
+/// Books finished in one calendar year, for the profile page.
+pub struct FinishedYear {
+    /// None groups finished books without a finish date.
+    pub year: Option<String>,
+    pub books: Vec<FinishedBook>,
+}
+
+/// Group finished books, already ordered newest first, by the year they were finished in.
+pub fn group_by_year(books: Vec<FinishedBook>) -> Vec<FinishedYear> {
+    let mut years: Vec<FinishedYear> = Vec::new();
+    for book in books {
+        let year = book
+            .finished_on
+            .as_deref()
+            .and_then(|date| date.get(..4))
+            .map(String::from);
+        match years.last_mut() {
+            Some(group) if group.year == year => group.books.push(book),
+            _ => years.push(FinishedYear {
+                year,
+                books: vec![book],
+            }),
+        }
+    }
+    years
+}
+
+#[derive(Deserialize)]
+pub struct ReadingStatusForm {
+    pub status: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReadingForm {
+    pub status: String,
+    pub started_on: String,
+    pub finished_on: String,
+}
+
+/// Accept only well-formed `YYYY-MM-DD` dates; anything else counts as unset.
+fn parse_date(value: &str) -> Option<String> {
+    chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .ok()
+        .map(|date| date.to_string())
+}
+
+fn today() -> String {
+    chrono::Utc::now().date_naive().to_string()
+}
+
+/// Move the current read-through to a new status, starting a re-read when the
+/// previous one is over.
+pub async fn reading_status_submit(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path(book_id): Path<String>,
+    Form(form): Form<ReadingStatusForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    match db.get_visible_book(&book_id, Some(&user.id)).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Redirect::to("/").into_response(),
+        Err(error) => {
+            eprintln!("Error fetching book: {error}");
+            return Redirect::to("/").into_response();
+        }
+    }
+
+    let Some(status) = ReadingStatus::parse(&form.status) else {
+        return (StatusCode::BAD_REQUEST, "Unknown reading status").into_response();
+    };
+
+    let readings = db
+        .get_readings(&user.id, &book_id)
+        .await
+        .unwrap_or_default();
+    let current = readings
+        .into_iter()
+        .next()
+        .filter(|reading| !reading.status.is_closed() || status.is_closed());
+
+    let (started_on, finished_on) = match status {
+        ReadingStatus::WantToRead => (None, None),
+        ReadingStatus::Reading => (
+            current
+                .as_ref()
+                .and_then(|r| r.started_on.clone())
+                .or_else(|| Some(today())),
+            None,
+        ),
+        ReadingStatus::Finished | ReadingStatus::Abandoned => (
+            current.as_ref().and_then(|r| r.started_on.clone()),
+            current
+                .as_ref()
+                .and_then(|r| r.finished_on.clone())
+                .or_else(|| Some(today())),
+        ),
+    };
+
+    let result = match &current {
+        Some(reading) => db
+            .update_reading(
+                &user.id,
+                &book_id,
+                &reading.id,
+                status,
+                started_on.as_deref(),
+                finished_on.as_deref(),
+            )
+            .await
+            .map(|_| ()),
+        None => db
+            .create_reading(
+                &user.id,
+                &book_id,
+                status,
+                started_on.as_deref(),
+                finished_on.as_deref(),
+            )
+            .await
+            .map(|_| ()),
+    };
+
+    if let Err(error) = result {
+        eprintln!("Reading status error: {error}");
+    }
+
+    Redirect::to(&format!("/books/{}", book_id)).into_response()
+}
+
+/// Correct the status or dates of one entry in the reading log.
+pub async fn reading_update(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, reading_id)): Path<(String, String)>,
+    Form(form): Form<ReadingForm>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let Some(status) = ReadingStatus::parse(&form.status) else {
+        return (StatusCode::BAD_REQUEST, "Unknown reading status").into_response();
+    };
+
+    let started_on = parse_date(&form.started_on);
+    let finished_on = parse_date(&form.finished_on);
+
+    match db
+        .update_reading(
+            &user.id,
+            &book_id,
+            &reading_id,
+            status,
+            started_on.as_deref(),
+            finished_on.as_deref(),
+        )
+        .await
+    {
+        Ok(true) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Reading not found").into_response(),
+        Err(error) => {
+            eprintln!("Reading update error: {error}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not update reading",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn reading_delete(
+    State(db): State<AppState>,
+    headers: HeaderMap,
+    Path((book_id, reading_id)): Path<(String, String)>,
+) -> Response {
+    let user = current_user(&db, &headers).await;
+
+    let Some(user) = user else {
+        return Redirect::to("/login").into_response();
+    };
+
+    match db.delete_reading(&user.id, &book_id, &reading_id).await {
+        Ok(true) => Redirect::to(&format!("/books/{}", book_id)).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Reading not found").into_response(),
+        Err(error) => {
+            eprintln!("Reading delete error: {error}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not delete reading",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::books::BookVisibility;
+    use crate::database::tests::{add_book, test_database};
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn post(app: &axum::Router, uri: &str, token: &str, form: &str) -> StatusCode {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(form.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    fn finished(title: &str, finished_on: Option<&str>) -> FinishedBook {
+        FinishedBook {
+            book_id: title.to_lowercase(),
+            title: title.to_string(),
+            author: None,
+            finished_on: finished_on.map(String::from),
+        }
+    }
+
+    #[test]
+    fn finished_books_group_by_year() {
+        let years = group_by_year(vec![
+            finished("Ficciones", Some("2024-12-01")),
+            finished("Aleph", Some("2024-02-11")),
+            finished("Invisible Cities", Some("2023-06-30")),
+            finished("Undated", None),
+        ]);
+        let years: Vec<(Option<&str>, usize)> = years
+            .iter()
+            .map(|year| (year.year.as_deref(), year.books.len()))
+            .collect();
+        assert_eq!(years, [(Some("2024"), 2), (Some("2023"), 1), (None, 1)]);
+    }
+
+    #[tokio::test]
+    async fn status_changes_follow_the_current_reading() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let token = db.create_api_token(&user_id, "test").await.unwrap();
+        let book_id = add_book(&db, "Ficciones", None, None, BookVisibility::Public).await;
+        let db = std::sync::Arc::new(db);
+        let app = crate::create_app(db.clone());
+        let uri = format!("/books/{book_id}/status");
+        let statuses = async || -> Vec<ReadingStatus> {
+            db.get_readings(&user_id, &book_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|reading| reading.status)
+                .collect()
+        };
+
+        assert_eq!(
+            post(&app, &uri, &token, "status=reading").await,
+            StatusCode::SEE_OTHER
+        );
+        assert_eq!(
+            post(&app, &uri, &token, "status=finished").await,
+            StatusCode::SEE_OTHER
+        );
+        assert_eq!(statuses().await, [ReadingStatus::Finished]);
+        let reading = &db.get_readings(&user_id, &book_id).await.unwrap()[0];
+        assert_eq!(reading.started_on.as_deref(), Some(today().as_str()));
+        assert_eq!(reading.finished_on.as_deref(), Some(today().as_str()));
+
+        // Picking a finished book up again is a re-read
+        post(&app, &uri, &token, "status=reading").await;
+        assert_eq!(
+            statuses().await,
+            [ReadingStatus::Reading, ReadingStatus::Finished]
+        );
+        post(&app, &uri, &token, "status=abandoned").await;
+        assert_eq!(
+            statuses().await,
+            [ReadingStatus::Abandoned, ReadingStatus::Finished]
+        );
+
+        assert_eq!(
+            post(&app, &uri, &token, "status=skimmed").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(statuses().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn readings_are_changed_only_through_their_own_book() {
+        let (_dir, db) = test_database().await;
+        let user_id = db.create_user("reader", "secret").await.unwrap();
+        let other_id = db.create_user("other", "secret").await.unwrap();
+        let token = db.create_api_token(&user_id, "test").await.unwrap();
+        let other_token = db.create_api_token(&other_id, "test").await.unwrap();
+        let book_id = add_book(&db, "Ficciones", None, None, BookVisibility::Public).await;
+        let other_book = add_book(&db, "Aleph", None, None, BookVisibility::Public).await;
+        let reading_id = db
+            .create_reading(
+                &user_id,
+                &book_id,
+                ReadingStatus::Reading,
+                Some("2024-01-01"),
+                None,
+            )
+            .await
+            .unwrap();
+        let db = std::sync::Arc::new(db);
+        let app = crate::create_app(db.clone());
+        let form = "status=finished&started_on=2024-01-01&finished_on=2024-02-01";
+
+        for (uri, token) in [
+            (format!("/books/{other_book}/readings/{reading_id}"), &token),
+            (
+                format!("/books/{book_id}/readings/{reading_id}"),
+                &other_token,
+            ),
+        ] {
+            assert_eq!(post(&app, &uri, token, form).await, StatusCode::NOT_FOUND);
+            let uri = format!("{uri}/delete");
+            assert_eq!(post(&app, &uri, token, "").await, StatusCode::NOT_FOUND);
+        }
+        assert!(
+            db.get_readings(&user_id, &other_book)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let readings = db.get_readings(&user_id, &book_id).await.unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].status, ReadingStatus::Reading);
+
+        let uri = format!("/books/{book_id}/readings/{reading_id}");
+        assert_eq!(post(&app, &uri, &token, form).await, StatusCode::SEE_OTHER);
+        let readings = db.get_readings(&user_id, &book_id).await.unwrap();
+        assert_eq!(readings[0].finished_on.as_deref(), Some("2024-02-01"));
+    }
+
+    #[tokio::test]
+    async fn finished_list_hides_books_no_longer_visible() {
+        let (_dir, db) = test_database().await;
+        let owner = db.create_user("owner", "secret").await.unwrap();
+        let reader = db.create_user("reader", "secret").await.unwrap();
+        let shared = add_book(&db, "Shared", None, Some(&owner), BookVisibility::Shared).await;
+        let public = add_book(&db, "Public", None, Some(&owner), BookVisibility::Public).await;
+        db.set_book_shares(&shared, &["reader"]).await.unwrap();
+        for (book_id, finished_on) in [(&shared, "2024-03-01"), (&public, "2023-05-01")] {
+            db.create_reading(
+                &reader,
+                book_id,
+                ReadingStatus::Finished,
+                None,
+                Some(finished_on),
+            )
+            .await
+            .unwrap();
+        }
+
+        let titles = async || -> Vec<String> {
+            db.get_finished_books(&reader)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|book| book.title)
+                .collect()
+        };
+        assert_eq!(titles().await, ["Shared", "Public"]);
+
+        db.update_book_sharing(&shared, BookVisibility::Shared, &[])
+            .await
+            .unwrap();
+        assert_eq!(titles().await, ["Public"]);
+    }
+}
@@ -1,7 +1,16 @@
 use askama::Template;
 
-use crate::books::Book;
+use crate::auth::{ApiToken, Session};
+use crate::authors::{Author, AuthorBook, AuthorCount, BookAuthor};
+use crate::books::{Book, SearchResult, TagCount};
+use crate::clippings::ClippingsSummary;
+use crate::export::RestoreSummary;
 use crate::gpt::BookEditResult;
+use crate::highlights::Highlight;
+use crate::imports::{CsvImportSummary, PlannedImport};
+use crate::metadata::Identifier;
+use crate::reader::TocEntry;
+use crate::readings::{FinishedYear, Reading};
 
 #[derive(Template)]
 #[template(path = "book_list.html")]
@@ -11,6 +20,96 @@ pub struct BookListTemplate {
     pub username: String,
     pub books: Vec<Book>,
     pub notes: bool,
+    pub mine: bool,
+    pub status: &'static str,
+    pub sort: String,
+    pub descending: bool,
+    pub sort_options: Vec<(&'static str, &'static str)>,
+    pub all_url: String,
+    pub notes_url: String,
+    pub mine_url: String,
+    pub tag: Option<String>,
+    pub untagged_url: String,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "book_search.html")]
+pub struct BookSearchTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub query: String,
+    pub results: Vec<SearchResult>,
+}
+
+#[derive(Template)]
+#[template(path = "tag_list.html")]
+pub struct TagListTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub tags: Vec<TagCount>,
+}
+
+#[derive(Template)]
+#[template(path = "author_list.html")]
+pub struct AuthorListTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub authors: Vec<AuthorCount>,
+    pub duplicates: Vec<Vec<AuthorCount>>,
+}
+
+#[derive(Template)]
+#[template(path = "author_detail.html")]
+pub struct AuthorDetailTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub author: Author,
+    pub books: Vec<AuthorBook>,
+    pub duplicates: Vec<AuthorCount>,
+}
+
+#[derive(Template)]
+#[template(path = "highlight_form.html")]
+pub struct HighlightFormTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub book: Book,
+    pub highlight: Option<Highlight>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "import.html")]
+pub struct ImportTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub clippings: Option<ClippingsSummary>,
+    pub csv: Option<CsvImportSummary>,
+    pub restore: Option<RestoreSummary>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "import_preview.html")]
+pub struct ImportPreviewTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub format: &'static str,
+    pub plan: Vec<PlannedImport>,
+    pub create_count: usize,
+    pub update_count: usize,
+    pub skip_count: usize,
+    /// The uploaded file, sent back when the import is confirmed.
+    pub csv: String,
 }
 
 #[derive(Template)]
@@ -58,6 +157,28 @@ pub struct BookDetailTemplate {
     pub signups_disabled: bool,
     pub username: String,
     pub book: Book,
+    pub authors: Vec<BookAuthor>,
+    pub tags: Vec<String>,
+    pub identifiers: Vec<Identifier>,
+    pub notes_html: Option<String>,
+    pub highlights: Vec<Highlight>,
+    pub readings: Vec<Reading>,
+    pub owner: Option<String>,
+    pub can_edit: bool,
+}
+
+#[derive(Template)]
+#[template(path = "book_read.html")]
+pub struct BookReadTemplate {
+    pub is_authenticated: bool,
+    pub signups_disabled: bool,
+    pub username: String,
+    pub book: Book,
+    pub toc: Vec<TocEntry>,
+    pub chapter: usize,
+    pub chapter_count: usize,
+    pub chapter_url: String,
+    pub resume_progress: f64,
 }
 
 #[derive(Template)]
@@ -67,6 +188,11 @@ pub struct BookEditTemplate {
     pub signups_disabled: bool,
     pub username: String,
     pub book: Book,
+    pub tags: String,
+    pub identifiers: String,
+    pub translators: String,
+    pub editors: String,
+    pub shared_with: String,
     pub error_message: Option<String>,
 }
 
@@ -77,6 +203,7 @@ pub struct BookEditNotesTemplate {
     pub signups_disabled: bool,
     pub username: String,
     pub book: Book,
+    pub preview_html: String,
     pub error_message: Option<String>,
 }
 
@@ -87,6 +214,11 @@ pub struct ProfileTemplate {
     pub signups_disabled: bool,
     pub username: String,
     pub book_count: i64,
+    pub finished_years: Vec<FinishedYear>,
+    pub api_tokens: Vec<ApiToken>,
+    pub new_token: Option<String>,
+    pub sessions: Vec<Session>,
+    pub error_message: Option<String>,
 }
 
 #[derive(Template)]